@@ -0,0 +1,45 @@
+//! A tracker of the connected peers, which keeps them in a map:
+//!
+//! ```
+//! # // Path: sample/src/lib.rs:6-9
+//! pub struct PeerTracker {
+//!     peers: HashMap<u32, String>,
+//!     max_peers: usize,
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+pub struct Peers {
+    peers: HashMap<u32, String>,
+}
+
+impl Peers {
+    /// Adds a peer, as done by the tracker:
+    ///
+    /// ```no_run
+    /// # // Path: sample/src/lib.rs#add_peer
+    /// pub fn add_peer(&mut self, id: u32, address: String) -> bool {
+    ///     if self.peers.len() >= self.max_peers {
+    ///         return false;
+    ///     }
+    ///
+    ///     self.peers.insert(id, address);
+    ///     true
+    /// }
+    /// ```
+    ///
+    /// A doc example without a path line is not checked:
+    ///
+    /// ```
+    /// let mut peers = Peers::default();
+    /// peers.add(1, "127.0.0.1".to_string());
+    /// ```
+    ///
+    /// ```text
+    /// # // Path: sample/src/lib.rs:1-2
+    /// ```
+    pub fn add(&mut self, id: u32, address: String) {
+        self.peers.insert(id, address);
+    }
+}
@@ -0,0 +1,7 @@
+// An example kept next to the chapter that shows it
+
+// ANCHOR: greeting
+fn greeting(name: &str) -> String {
+    format!("Hello, {name}!")
+}
+// ANCHOR_END: greeting
@@ -0,0 +1,4 @@
+//! Messages saved with decomposed accents (as in NFD)
+
+pub const WELCOME: &str = "Bem-vindo à rede";
+pub const FAREWELL: &str = "Adieu, à bientôt";
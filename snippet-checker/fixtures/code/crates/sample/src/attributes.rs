@@ -0,0 +1,10 @@
+/// The network a node connects to
+#[derive(Debug, Clone, Copy)]
+// Kept in sync with the chain parameters
+#[non_exhaustive]
+pub enum Network {
+    Bitcoin,
+    // The test network
+    #[allow(dead_code)]
+    Testnet,
+}
@@ -0,0 +1,5 @@
+﻿pub const MAGIC: [u8; 4] = [0xfa, 0xbf, 0xb5, 0xda];
+
+pub fn is_magic(bytes: &[u8]) -> bool {
+    bytes == MAGIC
+}
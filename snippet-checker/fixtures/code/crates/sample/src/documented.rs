@@ -0,0 +1,38 @@
+//! Protocol constants, which are mostly documentation
+//!
+//! The values below are the ones used by the reference implementation, and changing any of them
+//! would make us incompatible with the rest of the network. They are grouped by the part of the
+//! protocol where they are used.
+//!
+//! # Connections
+//!
+//! We keep a small number of outbound connections, which are the ones we choose, and accept a
+//! larger number of inbound ones. Both limits can be changed at startup, but these are the
+//! defaults.
+//!
+//! # Messages
+//!
+//! Each message starts with a magic value identifying the network, followed by the command name,
+//! the payload length and a checksum of the payload. A message larger than the maximum size is
+//! taken as a protocol violation, and the peer sending it is disconnected.
+//!
+//! # Timeouts
+//!
+//! A peer that doesn't answer a ping in time is also disconnected, as it's either gone or too slow
+//! to be useful.
+
+/// The default number of outbound connections
+pub const MAX_OUTBOUND: usize = 8;
+
+/// The default number of inbound connections
+pub const MAX_INBOUND: usize = 117;
+
+// The maximum payload of a single message, in bytes. This is 32 MiB, which is much more than any
+// message we expect, so that the limit is only reached by misbehaving peers
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+/// The seconds we wait for a pong after sending a ping
+pub const PING_TIMEOUT: u64 = 20;
+
+// Note that there is no limit on the number of messages a peer can send, only on their size. Peers
+// sending too many messages are handled by the rate limiter, which is configured elsewhere.
@@ -0,0 +1,19 @@
+//! Items that depend on the enabled features
+
+pub struct Settings {
+    pub peers: usize,
+    #[cfg(feature = "compact")]
+    pub compact_blocks: bool,
+    #[cfg(not(feature = "compact"))]
+    pub full_blocks: bool,
+}
+
+#[cfg(all(feature = "compact", not(test)))]
+pub fn block_kind() -> &'static str {
+    "compact"
+}
+
+#[cfg(any(test, not(feature = "compact")))]
+pub fn block_kind() -> &'static str {
+    "full"
+}
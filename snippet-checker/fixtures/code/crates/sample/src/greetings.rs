@@ -0,0 +1,9 @@
+//! Localized greetings
+
+pub fn greeting(language: &str) -> &'static str {
+    match language {
+        "ja" => "こんにちは、世界",
+        "zh" => "你好，世界",
+        _ => "Hello, world",
+    }
+}
@@ -0,0 +1,14 @@
+// Two functions starting with the same lines, so a snippet of either is ambiguous
+pub fn handle_ping(peer: u32) -> bool {
+    if peer == 0 {
+        return false;
+    }
+    true
+}
+
+pub fn handle_pong(peer: u32) -> bool {
+    if peer == 0 {
+        return false;
+    }
+    false
+}
@@ -0,0 +1,7 @@
+//! A file whose imports are ordered as rustfmt does
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+pub type Peers = Arc<HashMap<u32, String>>;
@@ -0,0 +1,3 @@
+// A source file saved as Latin-1
+
+pub const GREETING: &str = "Ol, mundo";
@@ -0,0 +1,52 @@
+//! A small crate used by the snippet checker self-test
+
+use std::collections::HashMap;
+
+/// Keeps track of the peers we are connected to
+pub struct PeerTracker {
+    peers: HashMap<u32, String>,
+    max_peers: usize,
+}
+
+impl PeerTracker {
+    pub fn new(max_peers: usize) -> Self {
+        PeerTracker {
+            peers: HashMap::new(),
+            max_peers,
+        }
+    }
+
+    /// Adds a peer, returning false if we already have too many
+    pub fn add_peer(&mut self, id: u32, address: String) -> bool {
+        if self.peers.len() >= self.max_peers {
+            return false;
+        }
+
+        // Replace the address if the peer was already known
+        self.peers.insert(id, address);
+        true
+    }
+
+    pub fn remove_peer(&mut self, id: u32) -> Option<String> {
+        self.peers.remove(&id)
+    }
+
+    pub fn describe(&self, id: u32) -> &str {
+        match self.peers.get(&id) {
+            Some(address) => address,
+            None => "unknown",
+        }
+    }
+
+    pub fn is_known(&self, id: u32) -> bool {
+        match self.peers.get(&id) {
+            None => false,
+            Some(_) => true,
+        }
+    }
+}
+
+pub const DEFAULT_CONFIG: &str = "\
+# Maximum number of peers
+max_peers = 8
+";
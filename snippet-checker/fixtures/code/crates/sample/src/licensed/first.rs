@@ -0,0 +1,6 @@
+/* Copyright (c) The Floresta developers
+ * SPDX-License-Identifier: MIT
+ */
+pub fn first() -> u32 {
+    1
+}
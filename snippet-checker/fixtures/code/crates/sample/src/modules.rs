@@ -0,0 +1,31 @@
+// A file with inline modules, and a module declared in another file
+mod storage;
+
+pub fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+pub fn scale(
+    value: u32,
+    factor: u32,
+) -> u32 {
+    value * factor
+}
+
+pub mod math {
+    pub fn double(x: u32) -> u32 {
+        x * 2
+    }
+
+    pub mod empty {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds() {
+        assert_eq!(add(2, 2), 4);
+    }
+}
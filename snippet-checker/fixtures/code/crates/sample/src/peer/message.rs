@@ -0,0 +1,4 @@
+pub enum Message {
+    Ping(u64),
+    Pong(u64),
+}
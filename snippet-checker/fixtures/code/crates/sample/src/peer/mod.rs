@@ -0,0 +1,3 @@
+mod message;
+
+pub use message::Message;
@@ -0,0 +1,19 @@
+//! Calls formatted by rustfmt, some of them wrapped
+
+use crate::PeerTracker;
+
+pub fn connect(tracker: &mut PeerTracker) -> bool {
+    let connected = tracker.add_peer(
+        1,
+        // The default port
+        String::from("127.0.0.1:8333"),
+    );
+    let description = tracker
+        .describe(1)
+        .to_uppercase();
+    connected && !description.is_empty()
+}
+
+pub fn disconnect(tracker: &mut PeerTracker) -> Option<String> {
+    tracker.remove_peer(1).map(|address| address.to_uppercase())
+}
@@ -0,0 +1,7 @@
+#!/bin/sh
+# Runs the tool with the default network
+
+# Use signet unless another network is given
+NETWORK=${NETWORK:-signet}
+
+cargo run -p tools -- --network "$NETWORK" "$@"
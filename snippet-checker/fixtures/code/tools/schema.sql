@@ -0,0 +1,7 @@
+-- The peers seen by the node
+
+CREATE TABLE peers (
+    -- The peer id, as given by the node
+    id INTEGER PRIMARY KEY,
+    address TEXT NOT NULL
+);
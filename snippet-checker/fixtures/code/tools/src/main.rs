@@ -0,0 +1,6 @@
+//! A workspace member that lives outside of the `crates` directory
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    println!("Running with {} arguments", args.len());
+}
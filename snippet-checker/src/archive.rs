@@ -0,0 +1,275 @@
+// Reads the floresta source from a release archive (`.tar.gz`, `.tgz`, `.tar` or `.zip`), so it
+// doesn't need to be extracted first. All the files are read into memory when opening it
+use crate::inflate::inflate;
+use crate::source::SourceResolver;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Archive {
+    // The file contents by path, relative to the floresta root directory
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path)
+            .map_err(|e| format!("Could not read code archive {}: {e}", path.display()))?;
+        Archive::parse(path, &data)
+            .map_err(|e| format!("Invalid code archive {}: {e}", path.display()))
+    }
+
+    // Parses the archive data, which format is given by the file extension in `path`
+    pub fn parse(path: &Path, data: &[u8]) -> Result<Self, String> {
+        let name = path.display().to_string();
+        let entries = if name.ends_with(".zip") {
+            read_zip(data)?
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            read_tar(&gunzip(data)?)?
+        } else if name.ends_with(".tar") {
+            read_tar(data)?
+        } else {
+            return Err("unknown format, expected .tar.gz, .tgz, .tar or .zip".to_string());
+        };
+
+        // Release archives usually have all the files inside a `floresta-<version>` directory,
+        // which is then taken as the root directory
+        let mut roots = entries
+            .iter()
+            .map(|(path, _)| path.split('/').next().unwrap_or_default());
+        let first_root = roots.next().unwrap_or_default().to_string();
+        let common_root = entries.iter().all(|(path, _)| path.contains('/'))
+            && roots.all(|root| root == first_root);
+
+        let files = entries
+            .into_iter()
+            .map(|(path, content)| {
+                let path = match common_root {
+                    true => path[first_root.len() + 1..].to_string(),
+                    false => path,
+                };
+                (PathBuf::from(path), content)
+            })
+            .collect();
+
+        Ok(Archive { files })
+    }
+
+    // Returns the archive of the files in a directory, with paths relative to it
+    pub fn subdir(&self, dir: &str) -> Archive {
+        let files = self
+            .files
+            .iter()
+            .filter_map(|(path, content)| {
+                let path = path.strip_prefix(dir).ok()?;
+                Some((path.to_path_buf(), content.clone()))
+            })
+            .collect();
+        Archive { files }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        self.files
+            .iter()
+            .map(|(path, content)| (path.as_path(), content.as_slice()))
+    }
+}
+
+impl SourceResolver for Archive {
+    fn exists(&self, rel: &str) -> Result<bool, String> {
+        Ok(self.files.contains_key(Path::new(rel)))
+    }
+
+    fn read(&self, rel: &str) -> Result<Vec<u8>, String> {
+        self.files
+            .get(Path::new(rel))
+            .cloned()
+            .ok_or(format!("{rel} is not in the code archive"))
+    }
+
+    fn files(&self) -> Vec<String> {
+        self.files
+            .keys()
+            .map(|path| path.display().to_string())
+            .collect()
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<usize, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+        .ok_or("unexpected end of data".to_string())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<usize, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+        .ok_or("unexpected end of data".to_string())
+}
+
+// Returns the decompressed content of a gzip (RFC 1952) file
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 18 || data[..3] != [0x1f, 0x8b, 8] {
+        return Err("not a gzip file".to_string());
+    }
+    let flags = data[3];
+    let mut offset = 10;
+
+    // Skip the optional extra field, file name, comment and header checksum
+    if flags & 0x04 != 0 {
+        offset += 2 + u16_at(data, offset)?;
+    }
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            let end = data
+                .get(offset..)
+                .and_then(|field| field.iter().position(|&b| b == 0))
+                .ok_or("unterminated gzip header field")?;
+            offset += end + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        offset += 2;
+    }
+
+    inflate(data.get(offset..).ok_or("unexpected end of data")?)
+}
+
+// Returns the regular files of a tar archive, with their paths
+fn read_tar(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = Vec::new();
+    let mut offset = 0;
+    // The name given by a previous GNU long name or pax header, for the next entry
+    let mut long_name = None;
+
+    while let Some(header) = data.get(offset..offset + 512) {
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let field = &header[range];
+            let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+            String::from_utf8_lossy(&field[..end]).to_string()
+        };
+
+        let size = usize::from_str_radix(field(124..136).trim(), 8)
+            .map_err(|_| format!("invalid tar entry size at offset {offset}"))?;
+        let content = data
+            .get(offset + 512..offset + 512 + size)
+            .ok_or("unexpected end of data")?;
+        offset += 512 + size.div_ceil(512) * 512;
+
+        let mut name = field(0..100);
+        let prefix = field(345..500);
+        if header[257..262] == *b"ustar" && !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+
+        match header[156] {
+            b'0' | 0 => {
+                let name = long_name.take().unwrap_or(name);
+                files.push((name.trim_start_matches("./").to_string(), content.to_vec()));
+            }
+            b'L' => {
+                let end = content.iter().position(|&b| b == 0).unwrap_or(size);
+                long_name = Some(String::from_utf8_lossy(&content[..end]).to_string());
+            }
+            b'x' => {
+                // Pax records are written as `<length> <key>=<value>\n`
+                let records = String::from_utf8_lossy(content);
+                for record in records.lines() {
+                    if let Some((_, path)) = record.split_once(" path=") {
+                        long_name = Some(path.to_string());
+                    }
+                }
+            }
+            _ => long_name = None,
+        }
+    }
+    Ok(files)
+}
+
+// Returns a tar archive with these files, in the ustar format. The paths longer than the name
+// field are written in a GNU long name entry before their own
+pub fn write_tar(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (name, content) in files {
+        if name.len() > 100 {
+            let mut long_name = name.as_bytes().to_vec();
+            long_name.push(0);
+            write_tar_entry(&mut data, "././@LongLink", b'L', &long_name);
+        }
+        write_tar_entry(&mut data, name, b'0', content);
+    }
+    data.resize(data.len() + 1024, 0);
+    data
+}
+
+fn write_tar_entry(data: &mut Vec<u8>, name: &str, kind: u8, content: &[u8]) {
+    let mut header = [0u8; 512];
+    let name = &name.as_bytes()[..name.len().min(100)];
+    header[..name.len()].copy_from_slice(name);
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", content.len()).as_bytes());
+    // The modification time is zero, so that the same files give the same archive
+    header[136..148].copy_from_slice(b"00000000000\0");
+    header[156] = kind;
+    header[257..265].copy_from_slice(b"ustar\x0000");
+
+    // The checksum is the sum of the header bytes, with its own field taken as spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+    data.extend_from_slice(&header);
+    data.extend_from_slice(content);
+    data.resize(data.len().div_ceil(512) * 512, 0);
+}
+
+// Returns the files of a zip archive, with their paths
+fn read_zip(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    // The end of central directory record is at the end, followed by a comment of up to 64 KiB
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .take(22 + u16::MAX as usize)
+        .find(|&i| data[i..i + 4] == [0x50, 0x4b, 0x05, 0x06])
+        .ok_or("not a zip file")?;
+    let count = u16_at(data, end + 10)?;
+    let mut offset = u32_at(data, end + 16)?;
+
+    let mut files = Vec::new();
+    for _ in 0..count {
+        if data.get(offset..offset + 4) != Some(&[0x50, 0x4b, 0x01, 0x02]) {
+            return Err("invalid central directory entry".to_string());
+        }
+        let method = u16_at(data, offset + 10)?;
+        let compressed_size = u32_at(data, offset + 20)?;
+        let name_length = u16_at(data, offset + 28)?;
+        let header = u32_at(data, offset + 42)?;
+        let name = data
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or("unexpected end of data")?;
+        let name = String::from_utf8_lossy(name).to_string();
+        offset += 46 + name_length + u16_at(data, offset + 30)? + u16_at(data, offset + 32)?;
+
+        if name.ends_with('/') {
+            continue;
+        }
+
+        // The content follows the local header, which repeats the name and has its own extra field
+        let start = header + 30 + u16_at(data, header + 26)? + u16_at(data, header + 28)?;
+        let compressed = data
+            .get(start..start + compressed_size)
+            .ok_or("unexpected end of data")?;
+        let content = match method {
+            0 => compressed.to_vec(),
+            8 => inflate(compressed).map_err(|e| format!("{name}: {e}"))?,
+            _ => return Err(format!("{name}: unsupported compression method {method}")),
+        };
+        files.push((name, content));
+    }
+    Ok(files)
+}
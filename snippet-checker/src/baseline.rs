@@ -0,0 +1,86 @@
+// A baseline lists the snippets that were already failing when it was written, so that the check
+// only fails for new ones. Each line is a markdown file path (relative to the book directory) and
+// a snippet index, like `ch01-01-utreexonode.md#2`, and lines starting with `#` are comments
+use crate::error::CheckError;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+pub type Snippets = BTreeSet<(String, usize)>;
+
+pub fn read(path: &Path) -> Result<Snippets, CheckError> {
+    let content = fs::read_to_string(path).map_err(|e| CheckError::Io {
+        path: path.to_path_buf(),
+        message: format!("Could not read baseline {}: {e}", path.display()),
+    })?;
+
+    let mut snippets = BTreeSet::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (md_name, index) = line
+            .rsplit_once('#')
+            .and_then(|(md_name, index)| Some((md_name, index.parse().ok()?)))
+            .ok_or_else(|| CheckError::Invalid {
+                path: path.to_path_buf(),
+                message: format!(
+                    "Invalid baseline {}, line {}: expected `<file>#<snippet index>`",
+                    path.display(),
+                    i + 1
+                ),
+            })?;
+        snippets.insert((md_name.to_string(), index));
+    }
+    Ok(snippets)
+}
+
+pub fn write(path: &Path, snippets: &Snippets) -> Result<(), CheckError> {
+    let mut content = format!(
+        "# Snippets failing when this baseline was generated, at {}\n# by snippet-checker {}\n",
+        utc_now(),
+        env!("CARGO_PKG_VERSION"),
+    );
+    for (md_name, index) in snippets {
+        content.push_str(&format!("{md_name}#{index}\n"));
+    }
+    fs::write(path, content).map_err(|e| CheckError::Io {
+        path: path.to_path_buf(),
+        message: format!("Could not write baseline {}: {e}", path.display()),
+    })
+}
+
+// The current UTC time, formatted like `2024-01-31T12:00:00Z`
+fn utc_now() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Converts the days since 1970-01-01 to a date, counting from 0000-03-01 so that leap days
+    // are at the end of each year (see http://howardhinnant.github.io/date_algorithms.html)
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
@@ -0,0 +1,155 @@
+// An offline bundle of the verification inputs, written by the `bundle` subcommand: a tar archive
+// with the book files in `book/<name>/`, the read source files in `code/`, and a `bundle.toml`
+// file with the checker version and the book names. With `--verify-bundle`, the books are checked
+// against the bundled code
+use crate::archive::{self, Archive};
+use crate::error::CheckError;
+use crate::toml::{self, Value};
+
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+
+const BUNDLE_FILE: &str = "bundle.toml";
+
+// The files of a bundle being written, by their path in the archive
+#[derive(Default)]
+pub struct BundleWriter {
+    books: Vec<String>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl BundleWriter {
+    pub fn add_book(&mut self, name: &str) {
+        self.books.push(name.to_string());
+    }
+
+    // Adds a file of the book, at this path relative to its directory
+    pub fn add_book_file(&mut self, book: &str, path: &str, content: Vec<u8>) {
+        self.add(format!("book/{book}/{path}"), content);
+    }
+
+    // Adds a source file, at this path relative to the floresta root directory
+    pub fn add_source_file(&mut self, path: &str, content: Vec<u8>) {
+        self.add(format!("code/{path}"), content);
+    }
+
+    // The number of book and source files
+    pub fn counts(&self) -> (usize, usize) {
+        let books = self
+            .files
+            .iter()
+            .filter(|(path, _)| path.starts_with("book/"));
+        let books = books.count();
+        (books, self.files.len() - books)
+    }
+
+    fn add(&mut self, path: String, content: Vec<u8>) {
+        if !self.files.iter().any(|(added, _)| *added == path) {
+            self.files.push((path, content));
+        }
+    }
+
+    pub fn write(mut self, path: &Path) -> Result<(), CheckError> {
+        let books: Vec<String> = self.books.iter().map(|name| format!("{name:?}")).collect();
+        let manifest = format!(
+            "version = {:?}\nbooks = [{}]\n",
+            env!("CARGO_PKG_VERSION"),
+            books.join(", ")
+        );
+        self.files.sort();
+        self.files
+            .insert(0, (BUNDLE_FILE.to_string(), manifest.into_bytes()));
+        fs::write(path, archive::write_tar(&self.files)).map_err(|e| CheckError::Io {
+            path: path.to_path_buf(),
+            message: format!("Could not write bundle {}: {e}", path.display()),
+        })
+    }
+}
+
+// A bundle opened to be verified, whose book files are extracted to a temporary directory
+pub struct Bundle {
+    pub code: Archive,
+    // The name and extracted directory of each book
+    pub books: Vec<(String, PathBuf)>,
+    pub extracted: ExtractedDir,
+}
+
+// The temporary directory of the extracted book files, which is removed when dropped
+pub struct ExtractedDir(PathBuf);
+
+impl Drop for ExtractedDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Whether the path stays inside the directory it is joined to
+fn is_inside(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+impl Bundle {
+    pub fn open(path: &Path) -> Result<Self, CheckError> {
+        let invalid = |message: String| CheckError::Invalid {
+            path: path.to_path_buf(),
+            message: format!("Invalid bundle {}, {message}", path.display()),
+        };
+        if path.extension().and_then(|e| e.to_str()) != Some("tar") {
+            return Err(invalid("expected a .tar file".to_string()));
+        }
+        let archive = Archive::open(path).map_err(|message| CheckError::Io {
+            path: path.to_path_buf(),
+            message,
+        })?;
+        let manifest = archive
+            .entries()
+            .find(|(file, _)| *file == Path::new(BUNDLE_FILE))
+            .ok_or_else(|| invalid(format!("it has no {BUNDLE_FILE} file")))?
+            .1;
+        let entries = toml::parse(&String::from_utf8_lossy(manifest)).map_err(invalid)?;
+        let names = match entries.into_iter().find(|(key, _)| key == "books") {
+            Some((_, Value::Array(names))) => names,
+            _ => return Err(invalid(format!("{BUNDLE_FILE} has no `books` list"))),
+        };
+
+        let dir = env::temp_dir().join(format!("snippet-checker-bundle-{}", process::id()));
+        let mut bundle = Bundle {
+            code: archive.subdir("code"),
+            books: Vec::new(),
+            extracted: ExtractedDir(dir),
+        };
+        for name in names {
+            let Value::String(name) = name else {
+                return Err(invalid("the book names must be strings".to_string()));
+            };
+            if !is_inside(Path::new(&name)) {
+                return Err(invalid(format!("invalid book name `{name}`")));
+            }
+            let book_dir = bundle.extracted.0.join(&name);
+            for (file, content) in archive.subdir(&format!("book/{name}")).entries() {
+                if !is_inside(file) {
+                    return Err(invalid(format!(
+                        "invalid book file path {}",
+                        file.display()
+                    )));
+                }
+                let file = book_dir.join(file);
+                let written = fs::create_dir_all(file.parent().unwrap_or(&book_dir))
+                    .and_then(|_| fs::write(&file, content));
+                written.map_err(|e| CheckError::Io {
+                    message: format!("Could not extract {}: {e}", file.display()),
+                    path: file,
+                })?;
+            }
+            fs::create_dir_all(&book_dir).map_err(|e| CheckError::Io {
+                path: book_dir.clone(),
+                message: format!("Could not extract {}: {e}", book_dir.display()),
+            })?;
+            bundle.books.push((name, book_dir));
+        }
+        Ok(bundle)
+    }
+}
@@ -0,0 +1,81 @@
+// Checks that the crates with the source files shown in the book compile, by running `cargo check`
+// once for each of them. A crate is given by the closest `Cargo.toml` with a `[package]` table
+use crate::error::CheckError;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The number of cargo error output lines printed for a crate that doesn't compile
+const ERROR_LINES: usize = 20;
+
+// A crate with source files referenced by the snippets
+pub struct CrateCheck {
+    // The crate directory, relative to the code directory
+    pub name: String,
+    // The number of referenced source files in the crate
+    pub sources: usize,
+    // The cargo error output if the crate doesn't compile
+    pub error: Option<String>,
+}
+
+// Runs `cargo check` for each crate with some of the source files, which are inside `code_dir`
+pub fn check_crates<'a>(
+    code_dir: &Path,
+    sources: impl Iterator<Item = &'a Path>,
+) -> Result<Vec<CrateCheck>, CheckError> {
+    // The number of referenced source files of each crate, by manifest
+    let mut crates: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    for source in sources.filter(|source| source.starts_with(code_dir)) {
+        if let Some(manifest) = crate_manifest(code_dir, source) {
+            *crates.entry(manifest).or_default() += 1;
+        }
+    }
+
+    let mut checks = Vec::new();
+    for (manifest, sources) in crates {
+        let crate_dir = manifest.parent().unwrap();
+        let name = match crate_dir.strip_prefix(code_dir) {
+            Ok(dir) if dir.as_os_str().is_empty() => ".".to_string(),
+            Ok(dir) => dir.display().to_string(),
+            Err(_) => crate_dir.display().to_string(),
+        };
+        let output = Command::new("cargo")
+            .args(["check", "--quiet", "--manifest-path"])
+            .arg(&manifest)
+            .current_dir(code_dir)
+            .output()
+            .map_err(|e| {
+                CheckError::Failed(format!("Could not run cargo check for {name}: {e}"))
+            })?;
+
+        let error = (!output.status.success()).then(|| {
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .take(ERROR_LINES)
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        checks.push(CrateCheck {
+            name,
+            sources,
+            error,
+        });
+    }
+    Ok(checks)
+}
+
+// Returns the manifest of the crate with the source file, which is the closest `Cargo.toml` with a
+// `[package]` table, as workspace manifests may not have one
+fn crate_manifest(code_dir: &Path, source: &Path) -> Option<PathBuf> {
+    source
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(code_dir))
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest| {
+            fs::read_to_string(manifest)
+                .is_ok_and(|content| content.lines().any(|line| line.trim() == "[package]"))
+        })
+}
@@ -0,0 +1,172 @@
+// A line based evaluation of the `#[cfg(...)]` attributes of a source file, to compare the
+// snippets with the code as compiled with some cfg options, like `feature="x"`. This is only a
+// best effort: the attribute must be on its own line (or before the item on the same line), and
+// the item it applies to ends at the first line where its delimiters are balanced and it ends with
+// `;`, `,` or `}`. Any cfg option not given is taken as disabled, and attributes that can't be
+// parsed are left as they are
+use crate::code_chars;
+
+// Returns the code with the `#[cfg(...)]` attribute lines and the items disabled by them replaced
+// by blank lines, so that the line numbers don't change. The options are given as `name` or
+// `name=value`
+pub fn apply(code: &str, options: &[String]) -> String {
+    let mut lines: Vec<&str> = code.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some((predicate, rest)) = cfg_attribute(lines[i]) else {
+            i += 1;
+            continue;
+        };
+        let Some(enabled) = evaluate(predicate, options) else {
+            i += 1;
+            continue;
+        };
+
+        lines[i] = "";
+        if enabled {
+            i += 1;
+            continue;
+        }
+        // The disabled item may start on the attribute line
+        let start = match rest.trim().is_empty() {
+            true => i + 1,
+            false => i,
+        };
+        let end = item_end(&lines, start, rest).min(lines.len() - 1);
+        for line in &mut lines[i..=end] {
+            *line = "";
+        }
+        i = end + 1;
+    }
+
+    let mut filtered = lines.join("\n");
+    if code.ends_with('\n') {
+        filtered.push('\n');
+    }
+    filtered
+}
+
+// Returns the predicate of a `#[cfg(...)]` attribute line, and the code after the attribute
+fn cfg_attribute(line: &str) -> Option<(&str, &str)> {
+    let attribute = line.trim_start().strip_prefix("#[cfg(")?;
+    // The attribute ends at the parenthesis that closes `cfg(`
+    let mut depth = 1;
+    let mut in_string = false;
+    for (i, c) in attribute.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    let rest = attribute[i + 1..].strip_prefix(']')?;
+                    return Some((&attribute[..i], rest));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Returns the index of the last line of the item starting at `start`, where `rest` is the code
+// after the attribute if the item starts on the attribute line
+fn item_end(lines: &[&str], start: usize, rest: &str) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    loop {
+        let line = match i == start && !rest.trim().is_empty() {
+            true => rest,
+            false => lines.get(i).copied().unwrap_or_default(),
+        };
+        for c in code_chars(line) {
+            match c {
+                '{' | '(' | '[' => depth += 1,
+                '}' | ')' | ']' => depth -= 1,
+                _ => {}
+            }
+        }
+        let trimmed = line.trim_end();
+        // More attributes can come before the item
+        let attribute = trimmed.trim_start().starts_with("#[");
+        let ended = trimmed.ends_with(';') || trimmed.ends_with(',') || trimmed.ends_with('}');
+        if (depth <= 0 && ended && !attribute) || i + 1 >= lines.len() {
+            return i;
+        }
+        i += 1;
+    }
+}
+
+// Evaluates a cfg predicate, like `all(feature = "x", not(test))`, with the given options enabled.
+// Returns `None` if it can't be parsed
+fn evaluate(predicate: &str, options: &[String]) -> Option<bool> {
+    let predicate = predicate.trim();
+    let operation = ["all", "any", "not"].into_iter().find_map(|operator| {
+        let inner = predicate
+            .strip_prefix(operator)?
+            .trim_start()
+            .strip_prefix('(')?;
+        Some((operator, inner.strip_suffix(')')?))
+    });
+    if let Some((operator, inner)) = operation {
+        let values = split_arguments(inner)
+            .into_iter()
+            .map(|argument| evaluate(argument, options))
+            .collect::<Option<Vec<_>>>()?;
+        return match operator {
+            "all" => Some(values.iter().all(|value| *value)),
+            "any" => Some(values.iter().any(|value| *value)),
+            _ => (values.len() == 1).then(|| !values[0]),
+        };
+    }
+
+    let option = match predicate.split_once('=') {
+        Some((name, value)) => {
+            let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+            format!("{}={}", name.trim(), value)
+        }
+        None => predicate.to_string(),
+    };
+    let valid = option
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_');
+    valid.then(|| options.contains(&option))
+}
+
+// Splits the arguments of `all(...)` and similar at the top level commas
+fn split_arguments(arguments: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let (mut depth, mut in_string, mut start) = (0, false, 0);
+    for (i, c) in arguments.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => {
+                split.push(&arguments[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(&arguments[start..]);
+    // A trailing comma is allowed
+    split.retain(|argument| !argument.trim().is_empty());
+    split
+}
+
+// Normalizes a cfg option given in the settings, like `feature="x"` or `feature = x`, to
+// `feature=x`
+pub fn normalize_option(option: &str) -> Result<String, String> {
+    let option = match option.split_once('=') {
+        Some((name, value)) => format!("{}={}", name.trim(), value.trim().trim_matches('"')),
+        None => option.trim().to_string(),
+    };
+    match option.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        true => Ok(option),
+        false => Err(format!(
+            "Invalid cfg option: {option}, expected `name` or `name=value`"
+        )),
+    }
+}
@@ -0,0 +1,222 @@
+// The library interface of the checker, for other tools and the tests: a `SnippetChecker` checks a
+// markdown document with the given settings, returning the result of each snippet instead of
+// printing it, and without reading the environment
+use crate::comparator::Comparator;
+use crate::config::set_check_setting;
+use crate::error::CheckError;
+use crate::source::SourceResolver;
+use crate::{check_fence_languages, get_md_snippets_diff, CheckContext};
+use similar::{ChangeTag, TextDiff};
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub struct SnippetChecker {
+    // The directory the markdown file paths are relative to
+    book_dir: PathBuf,
+    pub(crate) ctx: CheckContext,
+}
+
+// Builds a `SnippetChecker` with other settings than the default ones
+pub struct SnippetCheckerBuilder {
+    checker: SnippetChecker,
+}
+
+// A checked snippet of a markdown document, by its index in the document
+#[derive(Debug)]
+pub struct SnippetResult {
+    pub index: usize,
+    // The path as written in the snippet, without the line range or anchors
+    pub path: String,
+    // The first source line of the code the snippet was compared with, or 0 if it was not compared
+    pub start_line: usize,
+    pub outcome: SnippetOutcome,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SnippetOutcome {
+    Match,
+    // The lines from the snippet to the code it was compared with
+    Diff(Vec<DiffLine>),
+    // The snippet path is not in the code directory
+    Missing,
+    // Other problems of the snippet, like an ambiguous first line or a code block that is not
+    // found, in the order they were found
+    Invalid(Vec<Issue>),
+}
+
+// A problem of a snippet, by its kind, and with the message telling it
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IssueKind {
+    // The code block is not in the source file, as with a stale line range or anchor
+    BlockNotFound,
+    // The first snippet line is at several lines of the file, or the path matches several files
+    Ambiguous,
+    // The snippet header, its directives or its fence attributes can't be read, like an unknown
+    // manifest ID or attribute
+    MalformedHeader,
+    // The snippet path would read a file outside of the code directory, or through a symlink
+    UnsafePath,
+    // The source file is not valid UTF-8
+    UnreadableSource,
+    // The snippet matches the code, but its `sha` directive is not the hash of the code block
+    StaleHash,
+    // The other problems, like a capture shorter than the snippet or the checks of the `strict`
+    // mode
+    Other,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiffLine {
+    Equal(String),
+    // A line only in the snippet
+    Delete(String),
+    // A line only in the code
+    Insert(String),
+}
+
+impl SnippetChecker {
+    // The checker of the book in `book_dir`, with the snippet paths relative to `code_dir` (the
+    // `crates` directory of the floresta code, or the floresta root for the other files), and the
+    // default settings
+    pub fn new(book_dir: impl Into<PathBuf>, code_dir: impl Into<String>) -> Self {
+        SnippetChecker::builder(book_dir, code_dir).checker
+    }
+
+    // The builder of a checker with the same directories as `new`, whose settings can be changed
+    pub fn builder(
+        book_dir: impl Into<PathBuf>,
+        code_dir: impl Into<String>,
+    ) -> SnippetCheckerBuilder {
+        SnippetCheckerBuilder {
+            checker: SnippetChecker {
+                book_dir: book_dir.into(),
+                ctx: CheckContext::new(code_dir.into()),
+            },
+        }
+    }
+
+    // Checks the snippets of a markdown document of the book, at `md_path` (relative to the book
+    // directory), returning a result for each of them in order, but for the skipped ones. Only a
+    // file that can't be read is an error, as the problems of a snippet are its outcome
+    pub fn check_file(
+        &self,
+        md_path: impl AsRef<Path>,
+        md: &str,
+    ) -> Result<Vec<SnippetResult>, CheckError> {
+        let md_dir = self.md_dir(md_path.as_ref());
+        let check = get_md_snippets_diff(md.to_string(), &md_dir, &self.ctx)?;
+        let mut results = BTreeMap::new();
+        for snippet in &check.verified {
+            let result = SnippetResult {
+                index: snippet.index,
+                path: snippet.path.clone(),
+                start_line: snippet.start_line,
+                outcome: SnippetOutcome::Match,
+            };
+            results.insert(snippet.index, result);
+        }
+        for diff in &check.diffs {
+            let result = SnippetResult {
+                index: diff.index,
+                path: diff.path.clone(),
+                start_line: diff.start_line,
+                outcome: SnippetOutcome::Diff(diff_lines(&diff.snippet, &diff.block)),
+            };
+            results.insert(diff.index, result);
+        }
+        for missing in &check.missing {
+            let result = SnippetResult {
+                index: missing.index,
+                path: missing.path.clone(),
+                start_line: 0,
+                outcome: SnippetOutcome::Missing,
+            };
+            results.insert(missing.index, result);
+        }
+
+        // A snippet with problems is invalid, even if it was compared with the code
+        let mut issues: BTreeMap<usize, Vec<Issue>> = BTreeMap::new();
+        for issue in &check.issues {
+            issues.entry(issue.index).or_default().push(Issue {
+                kind: issue.kind,
+                message: issue.message.clone(),
+            });
+        }
+        for (index, issues) in issues {
+            let result = results.entry(index).or_insert_with(|| SnippetResult {
+                index,
+                path: check.paths[index].clone(),
+                start_line: 0,
+                outcome: SnippetOutcome::Match,
+            });
+            result.outcome = SnippetOutcome::Invalid(issues);
+        }
+        Ok(results.into_values().collect())
+    }
+
+    // The directory of a markdown file of the book, which the `./` snippet paths are relative to
+    pub(crate) fn md_dir(&self, md_path: &Path) -> PathBuf {
+        let md_path = self.book_dir.join(md_path);
+        md_path.parent().unwrap_or(&self.book_dir).to_path_buf()
+    }
+}
+
+impl SnippetCheckerBuilder {
+    // Changes a setting of the snippet checks, named as in the config file and with its value
+    // written in the same way, like `setting("max_candidates", "3")`. The settings that are not
+    // about checking the snippets of a file, like the output or the books, are unknown
+    pub fn setting(mut self, name: &str, value: &str) -> Result<Self, CheckError> {
+        set_check_setting(&mut self.checker.ctx, name, value)?;
+        Ok(self)
+    }
+
+    // Compares the snippets and the code as normalized by this comparator, after the built-in
+    // normalizations, instead of as they are
+    pub fn comparator(mut self, comparator: impl Comparator + Send + Sync + 'static) -> Self {
+        self.checker.ctx.comparator = Box::new(comparator);
+        self
+    }
+
+    // Reads the source files from this backend instead of the code directory, with the snippet
+    // paths relative to its root
+    pub fn source(mut self, source: impl SourceResolver + Send + Sync + 'static) -> Self {
+        self.checker.ctx.source = Some(Box::new(source));
+        self
+    }
+
+    // Reads this content instead of the file at this path, which is in the code directory or next
+    // to a markdown file (joined to their directory as the snippet paths), so that the files don't
+    // need to exist
+    pub fn overlay_file(mut self, path: impl Into<PathBuf>, content: impl Into<String>) -> Self {
+        self.checker.ctx.overlay.insert(path.into(), content.into());
+        self
+    }
+
+    // Returns the checker, once the settings are checked together
+    pub fn build(self) -> Result<SnippetChecker, CheckError> {
+        check_fence_languages(&self.checker.ctx)?;
+        Ok(self.checker)
+    }
+}
+
+// Returns the lines of the diff from the snippet to the code, without their line endings
+pub(crate) fn diff_lines(snippet: &str, code: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(snippet, code)
+        .iter_all_changes()
+        .map(|change| {
+            let line = change.value().trim_end_matches(['\r', '\n']).to_string();
+            match change.tag() {
+                ChangeTag::Equal => DiffLine::Equal(line),
+                ChangeTag::Delete => DiffLine::Delete(line),
+                ChangeTag::Insert => DiffLine::Insert(line),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,32 @@
+// Normalizations applied to both the snippet and the code block right before comparing them, after
+// the built-in ones (like removing the indentation). Other ones can be added by implementing
+// `Comparator`, for instance to collapse whitespace or skip a license header
+use crate::{is_code_line, Comments};
+
+pub trait Comparator {
+    // Returns the code as compared, which is also how it's shown in the diff
+    fn normalize(&self, code: &str) -> String;
+}
+
+// Compares the code as it is, which is the default
+pub struct Identity;
+
+impl Comparator for Identity {
+    fn normalize(&self, code: &str) -> String {
+        code.to_string()
+    }
+}
+
+// Skips the empty lines and the lines starting with the comment prefix. Unlike the built-in comment
+// stripping, this is done after taking the block, so with `no_strip_comments` the snippet and code
+// lines are still matched one to one, but comments can differ
+pub struct StripComments(pub &'static str);
+
+impl Comparator for StripComments {
+    fn normalize(&self, code: &str) -> String {
+        code.lines()
+            .filter(|line| is_code_line(line, Comments::Strip(self.0)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,935 @@
+// Resolves the settings from the defaults, the config file, the environment (or a `.env` file) and
+// the command line flags, in increasing order of precedence
+use crate::cfg;
+use crate::dotenv;
+use crate::manifest;
+use crate::theme::ColorTheme;
+use crate::toml::{self, Value};
+use crate::{CheckContext, LeadingAttributes, MissingPaths, OutputFormat, MDBOOK_DIR};
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// The config file that is read by default, from the current directory
+const CONFIG_FILE: &str = "snippet-checker.toml";
+
+// Where the current value of a setting comes from
+#[derive(Clone)]
+enum Source {
+    Default,
+    File,
+    // Detected from the `book.toml` at this path
+    BookToml(PathBuf),
+    Env(&'static str),
+    // A variable of the `.env` file
+    DotEnv(&'static str),
+    Flag,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    // A boolean setting, enabled by its flag
+    Switch,
+    // A setting that takes a value
+    Value,
+    // A setting that takes a value, and can be given multiple times
+    List,
+}
+
+// Every setting, as named in the config file. The flags use the same names with dashes instead of
+// underscores, e.g. `--book-dir` for `book_dir`
+const SETTINGS: &[(&str, Kind)] = &[
+    ("book_dir", Kind::List),
+    ("code_dir", Kind::Value),
+    ("code_archive", Kind::Value),
+    ("verify_bundle", Kind::Value),
+    ("baseline", Kind::Value),
+    ("baseline_write", Kind::Value),
+    ("baseline_prune", Kind::Switch),
+    ("max_diffs", Kind::Value),
+    ("max_missing", Kind::Value),
+    ("watch", Kind::Switch),
+    ("cargo_check", Kind::Switch),
+    ("fix", Kind::Switch),
+    ("interactive", Kind::Switch),
+    ("assume_yes", Kind::Switch),
+    ("fix_report", Kind::Value),
+    ("update_hashes", Kind::Switch),
+    ("stdin", Kind::Switch),
+    ("count_only", Kind::Switch),
+    ("quiet", Kind::Switch),
+    ("report", Kind::Value),
+    ("github_summary", Kind::Switch),
+    ("threads", Kind::Value),
+    ("stdin_name", Kind::Value),
+    ("format", Kind::Value),
+    ("json_pretty", Kind::Switch),
+    ("output_dir", Kind::Value),
+    ("color_theme", Kind::Value),
+    ("include", Kind::List),
+    ("exclude", Kind::List),
+    ("since", Kind::Value),
+    ("verbose", Kind::Switch),
+    ("require_balanced_braces", Kind::Switch),
+    ("max_snippet_lines", Kind::Value),
+    ("max_candidates", Kind::Value),
+    ("strip_header_lines", Kind::Value),
+    ("check_indent", Kind::Switch),
+    ("no_strip_comments", Kind::Switch),
+    ("group_by_source", Kind::Switch),
+    ("group_by_crate", Kind::Switch),
+    ("report_largest_diffs", Kind::Value),
+    ("ignore_trailing_whitespace", Kind::Switch),
+    ("check_global_order", Kind::Switch),
+    ("check_overlaps", Kind::Switch),
+    ("explain_skips", Kind::Switch),
+    ("check_intra_file_order", Kind::Switch),
+    ("check_includes", Kind::Switch),
+    ("fail_on_unparsed_fence", Kind::Switch),
+    ("diff_only_changed_lines", Kind::Switch),
+    ("detect_moves", Kind::Switch),
+    ("show_raw", Kind::Switch),
+    ("line_numbers", Kind::Switch),
+    ("diff_tool", Kind::Value),
+    ("source_context", Kind::Value),
+    ("context", Kind::Value),
+    ("rst", Kind::Switch),
+    ("doc_tests", Kind::Switch),
+    ("ast_compare", Kind::Switch),
+    ("normalize_line_wrapping", Kind::Switch),
+    ("unicode_normalize", Kind::Switch),
+    ("compact_status", Kind::Switch),
+    ("strict", Kind::Switch),
+    ("missing_paths", Kind::Value),
+    ("attributes", Kind::Value),
+    ("sort_use", Kind::Switch),
+    ("placeholder", Kind::Value),
+    ("allow_placeholder", Kind::Switch),
+    ("signature_only", Kind::Switch),
+    ("manifest", Kind::Value),
+    ("min_version", Kind::Value),
+    ("cfg", Kind::List),
+    ("ignore_attributes", Kind::List),
+    ("comment_prefix", Kind::List),
+    ("fence_language", Kind::List),
+    ("no_symlinks", Kind::Switch),
+    ("allow_glob_paths", Kind::Switch),
+    ("only_missing", Kind::Switch),
+    ("profile", Kind::Switch),
+    ("freshness", Kind::Switch),
+];
+
+// The settings that change how the snippets of a markdown file are checked, which are the ones of
+// the library interface. The others select the books and files, or are about the output and the
+// command line modes
+const CHECK_SETTINGS: &[&str] = &[
+    "require_balanced_braces",
+    "max_snippet_lines",
+    "max_candidates",
+    "strip_header_lines",
+    "check_indent",
+    "no_strip_comments",
+    "ignore_trailing_whitespace",
+    "check_intra_file_order",
+    "check_includes",
+    "fail_on_unparsed_fence",
+    "ast_compare",
+    "normalize_line_wrapping",
+    "unicode_normalize",
+    "strict",
+    "missing_paths",
+    "attributes",
+    "sort_use",
+    "placeholder",
+    "allow_placeholder",
+    "signature_only",
+    "cfg",
+    "ignore_attributes",
+    "comment_prefix",
+    "fence_language",
+    "no_symlinks",
+    "allow_glob_paths",
+];
+
+// Settings that can be given as environment variables
+const ENV_VARS: &[(&str, &str)] = &[("code_dir", "CODE_DIR")];
+
+// An mdBook to check
+pub struct Book {
+    // The name shown in the summaries and file names when checking multiple books, which is the
+    // table name for books defined in a `[books.<name>]` config table, or the directory otherwise
+    pub name: String,
+    // The mdBook source directory
+    pub dir: String,
+    // The floresta root directory for this book, if not the default one
+    pub code_dir: Option<String>,
+}
+
+// An invalid setting, told apart from the other errors by its own exit code
+#[derive(Debug)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<String> for ConfigError {
+    fn from(message: String) -> Self {
+        ConfigError(message)
+    }
+}
+
+pub struct Config {
+    pub books: Vec<Book>,
+    // The floresta source archive to read instead of the code directory, if not empty
+    pub code_archive: String,
+    // The bundle whose books are checked against its code, instead of the configured ones, if not
+    // empty
+    pub verify_bundle: String,
+    // The baseline file with the known failing snippets, and the one to write them to
+    pub baseline: String,
+    pub baseline_write: String,
+    // Remove the snippets that don't fail anymore from the baseline
+    pub baseline_prune: bool,
+    // How many snippets can differ from the code, and how many can have a missing path, without
+    // failing the check
+    pub max_diffs: usize,
+    pub max_missing: usize,
+    // Keep checking the files that change after the first check
+    pub watch: bool,
+    // Run `cargo check` for each crate with source files shown in the book
+    pub cargo_check: bool,
+    // Rewrite the snippets that differ from the code, asking before each one if `interactive`
+    pub fix: bool,
+    pub interactive: bool,
+    // Never prompt for the fixes, even with `interactive`, and write the applied ones to the JSON
+    // `fix_report` file if not empty
+    pub assume_yes: bool,
+    pub fix_report: String,
+    // Rewrite the `sha` directives of the snippets that match the code, but not their hash
+    pub update_hashes: bool,
+    // Check a single markdown document read from the standard input, reported with the given
+    // name, instead of the book
+    pub stdin: bool,
+    // The directory where the `annotate` format writes the annotated copies of the markdown files
+    pub output_dir: String,
+    // Print nothing, exiting with the number of failing snippets instead
+    pub count_only: bool,
+    // Print nothing, with the usual exit code
+    pub quiet: bool,
+    // Write the JSON report of the checked snippets, with the changes of each diff, to this file
+    // if not empty
+    pub report: String,
+    pub stdin_name: String,
+    // Append a markdown summary of the check to the GitHub Actions step summary file
+    pub github_summary: bool,
+    // The threads that check the markdown files (0 for one per CPU core), whose output is still
+    // printed in the order of the files
+    pub threads: usize,
+    pub color_theme: ColorTheme,
+    // Only check the markdown files modified in this time window, as given by the `since` setting
+    since: String,
+    // The snippet manifest with the source locations for the `# // Ref:` lines, if not empty
+    manifest: String,
+    // The oldest checker version that the book can be checked with, if not empty
+    min_version: String,
+    pub ctx: CheckContext,
+    // Print the resolved settings instead of checking the book
+    pub print_config: bool,
+    // The config file that was read, if any
+    file: Option<PathBuf>,
+    sources: HashMap<&'static str, Source>,
+}
+
+impl Config {
+    // The default settings, without any book
+    fn new() -> Self {
+        Config {
+            books: Vec::new(),
+            code_archive: String::new(),
+            verify_bundle: String::new(),
+            baseline: String::new(),
+            baseline_write: String::new(),
+            baseline_prune: false,
+            max_diffs: 0,
+            max_missing: 0,
+            watch: false,
+            cargo_check: false,
+            fix: false,
+            interactive: false,
+            assume_yes: false,
+            fix_report: String::new(),
+            update_hashes: false,
+            stdin: false,
+            count_only: false,
+            quiet: false,
+            report: String::new(),
+            github_summary: false,
+            threads: 0,
+            output_dir: String::new(),
+            stdin_name: String::new(),
+            color_theme: ColorTheme::Default,
+            since: String::new(),
+            manifest: String::new(),
+            min_version: String::new(),
+            ctx: CheckContext::new(String::new()),
+            print_config: false,
+            file: None,
+            sources: HashMap::new(),
+        }
+    }
+
+    pub fn load(args: impl Iterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut config = Config::new();
+        let mut config_path = None;
+        let mut flags = Vec::new();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            let (flag, mut inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            // The switches don't take the next argument, only a value given with `=`
+            let switch = switch_value(&flag, inline.as_deref());
+            let mut value = || {
+                inline
+                    .take()
+                    .or_else(|| args.next())
+                    .ok_or(format!("Missing value for {flag}"))
+            };
+
+            match flag.as_str() {
+                "--config" => config_path = Some(PathBuf::from(value()?)),
+                "--print-config" => config.print_config = switch?,
+                "-v" => flags.push(("verbose", Value::Boolean(switch?))),
+                _ => {
+                    let (name, kind) = flag
+                        .strip_prefix("--")
+                        .and_then(|name| setting(&name.replace('-', "_")))
+                        .ok_or(format!("Unknown argument: {flag}"))?;
+
+                    match kind {
+                        Kind::Switch => flags.push((name, Value::Boolean(switch?))),
+                        Kind::Value | Kind::List => flags.push((name, Value::String(value()?))),
+                    }
+                }
+            }
+        }
+
+        // An explicit config file must exist, while the default one is optional
+        let config_path = config_path.or_else(|| {
+            let default = PathBuf::from(CONFIG_FILE);
+            default.is_file().then_some(default)
+        });
+        if let Some(path) = config_path {
+            config.read_file(&path)?;
+        }
+
+        let dotenv = dotenv::read(Path::new(dotenv::DOTENV_FILE))?;
+        for (name, var) in ENV_VARS {
+            let file_value = || dotenv.iter().rev().find(|(key, _)| key == var);
+            if let Ok(value) = env::var(var) {
+                config.set(name, Value::String(value), Source::Env(var))?;
+            } else if let Some((_, value)) = file_value() {
+                config.set(name, Value::String(value.clone()), Source::DotEnv(var))?;
+            }
+        }
+
+        for (name, value) in flags {
+            config.set(name, value, Source::Flag)?;
+        }
+
+        if config.books.is_empty() {
+            match find_book_dir()? {
+                Some((book_dir, book_toml)) => config.set(
+                    "book_dir",
+                    Value::String(book_dir),
+                    Source::BookToml(book_toml),
+                )?,
+                None => config.books.push(Book {
+                    name: MDBOOK_DIR.to_string(),
+                    dir: MDBOOK_DIR.to_string(),
+                    code_dir: None,
+                }),
+            }
+        }
+
+        // The code directories are resolved once, so that they don't depend on the working
+        // directory later. Those set in the config file are relative to its directory
+        let file_dir = config
+            .file
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        if !config.ctx.code_dir.is_empty() {
+            let code_dir = match config.sources.get("code_dir") {
+                Some(Source::File) => file_dir.join(&config.ctx.code_dir),
+                _ => PathBuf::from(&config.ctx.code_dir),
+            };
+            config.ctx.code_dir = absolute_dir(&code_dir)?;
+        }
+        for book in &mut config.books {
+            if let Some(code_dir) = &mut book.code_dir {
+                *code_dir = absolute_dir(&file_dir.join(&*code_dir))?;
+            }
+        }
+
+        // As the config file, an explicit manifest must exist, while the default one is optional
+        if config.manifest.is_empty() && Path::new(manifest::MANIFEST_FILE).is_file() {
+            config.manifest = manifest::MANIFEST_FILE.to_string();
+            config.sources.insert("manifest", Source::Default);
+        }
+        if !config.manifest.is_empty() {
+            let manifest_path = match config.sources.get("manifest") {
+                Some(Source::File) => file_dir.join(&config.manifest),
+                _ => PathBuf::from(&config.manifest),
+            };
+            config.ctx.snippet_refs = manifest::read(&manifest_path)?;
+        }
+        Ok(config)
+    }
+
+    // Checks that the book directories exist, as otherwise there would be nothing to check
+    pub fn check_book_dirs(&self) -> Result<(), ConfigError> {
+        match self
+            .books
+            .iter()
+            .find(|book| !Path::new(&book.dir).is_dir())
+        {
+            Some(book) => Err(ConfigError(format!(
+                "Book directory {} does not exist",
+                book.dir
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn read_file(&mut self, path: &Path) -> Result<(), String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config file {}: {e}", path.display()))?;
+        let entries = toml::parse(&content)
+            .map_err(|e| format!("Invalid config file {}, {e}", path.display()))?;
+
+        for (key, value) in entries {
+            if let Some(book) = key.strip_prefix("books.") {
+                self.set_book(book, value)
+                    .map_err(|e| format!("{e} in config file {}", path.display()))?;
+                continue;
+            }
+            let (name, _) = setting(&key).ok_or(format!(
+                "Unknown setting `{key}` in config file {}",
+                path.display()
+            ))?;
+            self.set(name, value, Source::File)
+                .map_err(|e| format!("{e} in config file {}", path.display()))?;
+        }
+        if let Some(book) = self.books.iter().find(|book| book.dir.is_empty()) {
+            return Err(format!(
+                "Book `{}` has no `book_dir` in config file {}",
+                book.name,
+                path.display()
+            ));
+        }
+        self.file = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    // Sets a `<name>.<key>` setting of the `[books.<name>]` table, which can be `book_dir` or
+    // `code_dir`
+    fn set_book(&mut self, key: &str, value: Value) -> Result<(), String> {
+        let (name, key) = key
+            .rsplit_once('.')
+            .ok_or(format!("Invalid setting `books.{key}`"))?;
+
+        let index = match self.books.iter().position(|book| book.name == name) {
+            Some(index) => index,
+            None => {
+                self.books.push(Book {
+                    name: name.to_string(),
+                    dir: String::new(),
+                    code_dir: None,
+                });
+                self.books.len() - 1
+            }
+        };
+        let book = &mut self.books[index];
+        match key {
+            "book_dir" => book.dir = string(key, value)?,
+            "code_dir" => book.code_dir = Some(string(key, value)?),
+            _ => return Err(format!("Unknown setting `{key}` for book `{name}`")),
+        }
+        self.sources.insert("book_dir", Source::File);
+        Ok(())
+    }
+
+    fn set(&mut self, name: &'static str, value: Value, source: Source) -> Result<(), String> {
+        let ctx = &mut self.ctx;
+        let list = |value| -> Result<Vec<String>, String> {
+            match value {
+                Value::String(string) => Ok(vec![string]),
+                Value::Array(values) => values
+                    .into_iter()
+                    .map(|value| string(name, value))
+                    .collect(),
+                _ => Err(format!("`{name}` must be a string or an array of strings")),
+            }
+        };
+
+        match name {
+            "book_dir" => {
+                for dir in list(value)? {
+                    self.books.push(Book {
+                        name: dir.clone(),
+                        dir,
+                        code_dir: None,
+                    });
+                }
+            }
+            "code_dir" => ctx.code_dir = string(name, value)?,
+            "code_archive" => self.code_archive = string(name, value)?,
+            "verify_bundle" => self.verify_bundle = string(name, value)?,
+            "baseline" => self.baseline = string(name, value)?,
+            "baseline_write" => self.baseline_write = string(name, value)?,
+            "baseline_prune" => self.baseline_prune = boolean(name, value)?,
+            "max_diffs" => self.max_diffs = count(name, value)?,
+            "max_missing" => self.max_missing = count(name, value)?,
+            "watch" => self.watch = boolean(name, value)?,
+            "cargo_check" => self.cargo_check = boolean(name, value)?,
+            "fix" => self.fix = boolean(name, value)?,
+            "interactive" => self.interactive = boolean(name, value)?,
+            "assume_yes" => self.assume_yes = boolean(name, value)?,
+            "fix_report" => self.fix_report = string(name, value)?,
+            "update_hashes" => self.update_hashes = boolean(name, value)?,
+            "output_dir" => self.output_dir = string(name, value)?,
+            "stdin" => self.stdin = boolean(name, value)?,
+            "count_only" => self.count_only = boolean(name, value)?,
+            "quiet" => self.quiet = boolean(name, value)?,
+            "report" => self.report = string(name, value)?,
+            "github_summary" => self.github_summary = boolean(name, value)?,
+            "threads" => self.threads = count(name, value)?,
+            "stdin_name" => self.stdin_name = string(name, value)?,
+            "format" => {
+                let format = string(name, value)?;
+                ctx.format = OutputFormat::parse(&format)
+                    .ok_or(format!("Unknown output format: {format}"))?;
+            }
+            "json_pretty" => ctx.json_pretty = boolean(name, value)?,
+            "color_theme" => {
+                let theme = string(name, value)?;
+                self.color_theme = ColorTheme::parse(&theme).ok_or(format!(
+                    "Unknown color theme: {theme}, expected default, high-contrast or monochrome"
+                ))?;
+            }
+            "include" => ctx.includes.extend(list(value)?),
+            "exclude" => ctx.excludes.extend(list(value)?),
+            "since" => {
+                self.since = string(name, value)?;
+                ctx.modified_since = Some(since_time(&self.since)?);
+            }
+            "verbose" => ctx.verbose = boolean(name, value)?,
+            "require_balanced_braces" => ctx.require_balanced_braces = boolean(name, value)?,
+            "max_snippet_lines" => ctx.max_snippet_lines = count(name, value)?,
+            "max_candidates" => ctx.max_candidates = count(name, value)?,
+            "strip_header_lines" => ctx.strip_header_lines = count(name, value)?,
+            "check_indent" => ctx.check_indent = boolean(name, value)?,
+            "no_strip_comments" => ctx.keep_comments = boolean(name, value)?,
+            "group_by_source" => ctx.group_by_source = boolean(name, value)?,
+            "group_by_crate" => ctx.group_by_crate = boolean(name, value)?,
+            "report_largest_diffs" => ctx.report_largest_diffs = count(name, value)?,
+            "ignore_trailing_whitespace" => ctx.ignore_trailing_whitespace = boolean(name, value)?,
+            "check_global_order" => ctx.check_global_order = boolean(name, value)?,
+            "check_overlaps" => ctx.check_overlaps = boolean(name, value)?,
+            "explain_skips" => ctx.explain_skips = boolean(name, value)?,
+            "check_intra_file_order" => ctx.check_intra_file_order = boolean(name, value)?,
+            "check_includes" => ctx.check_includes = boolean(name, value)?,
+            "fail_on_unparsed_fence" => ctx.fail_on_unparsed_fence = boolean(name, value)?,
+            "diff_only_changed_lines" => ctx.only_changed_lines = boolean(name, value)?,
+            "detect_moves" => ctx.detect_moves = boolean(name, value)?,
+            "show_raw" => ctx.show_raw = boolean(name, value)?,
+            "line_numbers" => ctx.line_numbers = boolean(name, value)?,
+            "rst" => ctx.rst = boolean(name, value)?,
+            "doc_tests" => ctx.doc_tests = boolean(name, value)?,
+            "ast_compare" => ctx.ast_compare = boolean(name, value)?,
+            "normalize_line_wrapping" => ctx.normalize_line_wrapping = boolean(name, value)?,
+            "unicode_normalize" => ctx.unicode_normalize = boolean(name, value)?,
+            "source_context" => ctx.source_context = count(name, value)?,
+            "context" => ctx.diff_context = count(name, value)?,
+            "diff_tool" => *ctx.diff_tool.get_mut().unwrap() = string(name, value)?,
+            "compact_status" => ctx.compact_status = boolean(name, value)?,
+            "strict" => ctx.strict = boolean(name, value)?,
+            "missing_paths" => {
+                let handling = string(name, value)?;
+                ctx.missing_paths = MissingPaths::parse(&handling).ok_or(format!(
+                    "Unknown missing paths handling: {handling}, expected warn, error or skip"
+                ))?;
+            }
+            "attributes" => {
+                let handling = string(name, value)?;
+                ctx.leading_attributes = LeadingAttributes::parse(&handling).ok_or(format!(
+                    "Unknown leading attributes handling: {handling}, expected auto, require or \
+                     ignore"
+                ))?;
+            }
+            "sort_use" => ctx.sort_use = boolean(name, value)?,
+            "placeholder" => ctx.placeholder = string(name, value)?,
+            "allow_placeholder" => ctx.allow_placeholder = boolean(name, value)?,
+            "signature_only" => ctx.signature_only = boolean(name, value)?,
+            "manifest" => self.manifest = string(name, value)?,
+            "min_version" => {
+                self.min_version = string(name, value)?;
+                check_min_version(&self.min_version, env!("CARGO_PKG_VERSION"))?;
+            }
+            "ignore_attributes" => ctx.ignore_attributes.extend(list(value)?),
+            "fence_language" => ctx.fence_languages.extend(list(value)?),
+            "comment_prefix" => {
+                for entry in list(value)? {
+                    ctx.comment_prefixes.push(comment_prefix(&entry)?);
+                }
+            }
+            "cfg" => {
+                for option in list(value)? {
+                    ctx.cfg.push(cfg::normalize_option(&option)?);
+                }
+            }
+            "no_symlinks" => ctx.no_symlinks = boolean(name, value)?,
+            "allow_glob_paths" => ctx.allow_glob_paths = boolean(name, value)?,
+            "only_missing" => ctx.only_missing = boolean(name, value)?,
+            "profile" => ctx.profile = boolean(name, value)?,
+            "freshness" => ctx.freshness = boolean(name, value)?,
+            _ => unreachable!("all the settings are handled"),
+        }
+        self.sources.insert(name, source);
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Value {
+        let ctx = &self.ctx;
+        let list =
+            |values: &[String]| Value::Array(values.iter().cloned().map(Value::String).collect());
+
+        match name {
+            "book_dir" => Value::Array(
+                self.books
+                    .iter()
+                    .map(|book| Value::String(book.dir.clone()))
+                    .collect(),
+            ),
+            "code_dir" => Value::String(ctx.code_dir.clone()),
+            "code_archive" => Value::String(self.code_archive.clone()),
+            "verify_bundle" => Value::String(self.verify_bundle.clone()),
+            "baseline" => Value::String(self.baseline.clone()),
+            "baseline_write" => Value::String(self.baseline_write.clone()),
+            "baseline_prune" => Value::Boolean(self.baseline_prune),
+            "max_diffs" => Value::Integer(self.max_diffs as i64),
+            "max_missing" => Value::Integer(self.max_missing as i64),
+            "watch" => Value::Boolean(self.watch),
+            "cargo_check" => Value::Boolean(self.cargo_check),
+            "fix" => Value::Boolean(self.fix),
+            "interactive" => Value::Boolean(self.interactive),
+            "assume_yes" => Value::Boolean(self.assume_yes),
+            "fix_report" => Value::String(self.fix_report.clone()),
+            "update_hashes" => Value::Boolean(self.update_hashes),
+            "output_dir" => Value::String(self.output_dir.clone()),
+            "stdin" => Value::Boolean(self.stdin),
+            "count_only" => Value::Boolean(self.count_only),
+            "quiet" => Value::Boolean(self.quiet),
+            "report" => Value::String(self.report.clone()),
+            "github_summary" => Value::Boolean(self.github_summary),
+            "threads" => Value::Integer(self.threads as i64),
+            "stdin_name" => Value::String(self.stdin_name.clone()),
+            "format" => Value::String(ctx.format.name().to_string()),
+            "json_pretty" => Value::Boolean(ctx.json_pretty),
+            "color_theme" => Value::String(self.color_theme.name().to_string()),
+            "include" => list(&ctx.includes),
+            "exclude" => list(&ctx.excludes),
+            "since" => Value::String(self.since.clone()),
+            "verbose" => Value::Boolean(ctx.verbose),
+            "require_balanced_braces" => Value::Boolean(ctx.require_balanced_braces),
+            "max_snippet_lines" => Value::Integer(ctx.max_snippet_lines as i64),
+            "max_candidates" => Value::Integer(ctx.max_candidates as i64),
+            "strip_header_lines" => Value::Integer(ctx.strip_header_lines as i64),
+            "check_indent" => Value::Boolean(ctx.check_indent),
+            "no_strip_comments" => Value::Boolean(ctx.keep_comments),
+            "group_by_source" => Value::Boolean(ctx.group_by_source),
+            "group_by_crate" => Value::Boolean(ctx.group_by_crate),
+            "report_largest_diffs" => Value::Integer(ctx.report_largest_diffs as i64),
+            "ignore_trailing_whitespace" => Value::Boolean(ctx.ignore_trailing_whitespace),
+            "check_global_order" => Value::Boolean(ctx.check_global_order),
+            "check_overlaps" => Value::Boolean(ctx.check_overlaps),
+            "explain_skips" => Value::Boolean(ctx.explain_skips),
+            "check_intra_file_order" => Value::Boolean(ctx.check_intra_file_order),
+            "check_includes" => Value::Boolean(ctx.check_includes),
+            "fail_on_unparsed_fence" => Value::Boolean(ctx.fail_on_unparsed_fence),
+            "diff_only_changed_lines" => Value::Boolean(ctx.only_changed_lines),
+            "detect_moves" => Value::Boolean(ctx.detect_moves),
+            "show_raw" => Value::Boolean(ctx.show_raw),
+            "line_numbers" => Value::Boolean(ctx.line_numbers),
+            "rst" => Value::Boolean(ctx.rst),
+            "doc_tests" => Value::Boolean(ctx.doc_tests),
+            "ast_compare" => Value::Boolean(ctx.ast_compare),
+            "normalize_line_wrapping" => Value::Boolean(ctx.normalize_line_wrapping),
+            "unicode_normalize" => Value::Boolean(ctx.unicode_normalize),
+            "source_context" => Value::Integer(ctx.source_context as i64),
+            "context" => Value::Integer(ctx.diff_context as i64),
+            "diff_tool" => Value::String(ctx.diff_tool.lock().unwrap().clone()),
+            "compact_status" => Value::Boolean(ctx.compact_status),
+            "strict" => Value::Boolean(ctx.strict),
+            "missing_paths" => Value::String(ctx.missing_paths.name().to_string()),
+            "attributes" => Value::String(ctx.leading_attributes.name().to_string()),
+            "sort_use" => Value::Boolean(ctx.sort_use),
+            "placeholder" => Value::String(ctx.placeholder.clone()),
+            "allow_placeholder" => Value::Boolean(ctx.allow_placeholder),
+            "signature_only" => Value::Boolean(ctx.signature_only),
+            "manifest" => Value::String(self.manifest.clone()),
+            "min_version" => Value::String(self.min_version.clone()),
+            "cfg" => list(&ctx.cfg),
+            "ignore_attributes" => list(&ctx.ignore_attributes),
+            "comment_prefix" => list(&ctx.comment_prefixes),
+            "fence_language" => list(&ctx.fence_languages),
+            "no_symlinks" => Value::Boolean(ctx.no_symlinks),
+            "allow_glob_paths" => Value::Boolean(ctx.allow_glob_paths),
+            "only_missing" => Value::Boolean(ctx.only_missing),
+            "profile" => Value::Boolean(ctx.profile),
+            "freshness" => Value::Boolean(ctx.freshness),
+            _ => unreachable!("all the settings are handled"),
+        }
+    }
+
+    // Prints every setting in config file syntax, along with where its value comes from
+    pub fn print(&self) {
+        match &self.file {
+            Some(path) => println!("# Config file: {}", path.display()),
+            None => println!("# No config file"),
+        }
+
+        for (name, _) in SETTINGS {
+            let source = match self.sources.get(name).unwrap_or(&Source::Default) {
+                Source::Default => "default".to_string(),
+                Source::File => "config file".to_string(),
+                Source::BookToml(path) => format!("detected from {}", path.display()),
+                Source::Env(var) => format!("environment variable {var}"),
+                Source::DotEnv(var) => format!("{var} in {}", dotenv::DOTENV_FILE),
+                Source::Flag => "command line flag".to_string(),
+            };
+            println!("{} = {}  # {}", name, self.get(name), source);
+        }
+
+        for book in &self.books {
+            if let Some(code_dir) = &book.code_dir {
+                println!(
+                    "books.{}.code_dir = {:?}  # config file",
+                    book.name, code_dir
+                );
+            }
+        }
+    }
+}
+
+// Returns the book source directory given by the closest `book.toml`, in the current directory or
+// its ancestors, along with the path of that `book.toml`
+fn find_book_dir() -> Result<Option<(String, PathBuf)>, String> {
+    let current_dir = env::current_dir().map_err(|e| e.to_string())?;
+    let Some(book_toml) = current_dir
+        .ancestors()
+        .map(|dir| dir.join("book.toml"))
+        .find(|path| path.is_file())
+    else {
+        return Ok(None);
+    };
+
+    let content = fs::read_to_string(&book_toml)
+        .map_err(|e| format!("Could not read {}: {e}", book_toml.display()))?;
+    let entries =
+        toml::parse(&content).map_err(|e| format!("Invalid {}, {e}", book_toml.display()))?;
+
+    // As in mdBook, the source directory is `src` unless set in the `[book]` table
+    let src = match entries.into_iter().find(|(key, _)| key == "book.src") {
+        Some((_, Value::String(src))) => src,
+        Some(_) => {
+            return Err(format!(
+                "Invalid {}, `src` must be a string",
+                book_toml.display()
+            ))
+        }
+        None => "src".to_string(),
+    };
+    let book_dir = book_toml.parent().unwrap().join(src);
+    Ok(Some((book_dir.display().to_string(), book_toml)))
+}
+
+// Sets a check setting of the library interface in the check context, with its value written as in
+// the config file, like `true`, `3` or `["a", "b"]`
+pub fn set_check_setting(
+    ctx: &mut CheckContext,
+    name: &str,
+    value: &str,
+) -> Result<(), ConfigError> {
+    let name = CHECK_SETTINGS
+        .iter()
+        .find(|setting| **setting == name)
+        .ok_or(format!("Unknown check setting `{name}`"))?;
+    let entries = toml::parse(&format!("{name} = {value}"))
+        .map_err(|e| format!("Invalid value for `{name}`, {e}"))?;
+    let [(_, value)] =
+        <[_; 1]>::try_from(entries).map_err(|_| format!("Invalid value for `{name}`: {value}"))?;
+
+    let mut config = Config::new();
+    config.ctx = std::mem::replace(ctx, CheckContext::new(String::new()));
+    let set = config.set(name, value, Source::Flag);
+    *ctx = config.ctx;
+    Ok(set?)
+}
+
+fn setting(name: &str) -> Option<(&'static str, Kind)> {
+    SETTINGS
+        .iter()
+        .find(|(setting, _)| *setting == name)
+        .copied()
+}
+
+// Returns the absolute form of a directory path, relative to the working directory, and without
+// `..` components
+fn absolute_dir(dir: &Path) -> Result<String, String> {
+    let absolute = std::path::absolute(dir)
+        .map_err(|e| format!("Could not resolve directory {}: {e}", dir.display()))?;
+
+    let mut resolved = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    Ok(resolved.display().to_string())
+}
+
+// Returns the start of the time window given by a duration before now, like `30m`, `2h`, `3d` or
+// `1w`, or by a UTC date, like `2024-01-31`
+fn since_time(since: &str) -> Result<SystemTime, String> {
+    let invalid = || {
+        format!("Invalid `since` value: {since}, expected a duration like `2h` or a date like `2024-01-31`")
+    };
+
+    if !since.contains('-') {
+        let split = since
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(since.len());
+        let (number, unit) = since.split_at(split);
+        let unit_seconds = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86400,
+            "w" => 7 * 86400,
+            _ => return Err(invalid()),
+        };
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let seconds = number.checked_mul(unit_seconds).ok_or_else(invalid)?;
+        return SystemTime::now()
+            .checked_sub(Duration::from_secs(seconds))
+            .ok_or_else(invalid);
+    }
+
+    let parts: Vec<u64> = since
+        .split('-')
+        .map(|part| part.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+
+    // Converts the date to days since 1970-01-01, counting from 0000-03-01 so that leap days are
+    // at the end of each year (see http://howardhinnant.github.io/date_algorithms.html)
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(days * 86400))
+}
+
+// Fails if the checker `version` is older than `min_version`, both given as dotted numbers like
+// `1.2` (where the missing parts are 0)
+pub fn check_min_version(min_version: &str, version: &str) -> Result<(), String> {
+    let numbers = |version: &str| -> Option<Vec<u64>> {
+        version.split('.').map(|part| part.parse().ok()).collect()
+    };
+    let mut min = numbers(min_version).ok_or(format!(
+        "Invalid `min_version` value: {min_version}, expected a version like `1.2`"
+    ))?;
+    let mut current = numbers(version).ok_or(format!("Invalid checker version: {version}"))?;
+    let parts = min.len().max(current.len());
+    min.resize(parts, 0);
+    current.resize(parts, 0);
+
+    match current < min {
+        true => Err(format!(
+            "The snippet-checker version {version} is older than the required `min_version` \
+             {min_version}"
+        )),
+        false => Ok(()),
+    }
+}
+
+// Returns whether a switch flag is turned on, as it is unless given `=false`
+fn switch_value(flag: &str, value: Option<&str>) -> Result<bool, String> {
+    match value {
+        None | Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(value) => Err(format!(
+            "Invalid value for {flag}: expected `true` or `false`, found `{value}`"
+        )),
+    }
+}
+
+fn string(name: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(format!("`{name}` must be a string")),
+    }
+}
+
+// A non-negative integer, which is given as a string in the command line flags
+fn count(name: &str, value: Value) -> Result<usize, String> {
+    let count = match value {
+        Value::Integer(integer) => usize::try_from(integer).ok(),
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    };
+    count.ok_or(format!("`{name}` must be a non-negative integer"))
+}
+
+// A comment prefix of a snippet language, given as `lang=prefix`
+fn comment_prefix(entry: &str) -> Result<String, String> {
+    match entry.split_once('=') {
+        Some((lang, prefix)) if !lang.trim().is_empty() && !prefix.trim().is_empty() => {
+            Ok(format!("{}={}", lang.trim(), prefix.trim()))
+        }
+        _ => Err(format!(
+            "Invalid comment prefix: {entry}, expected `lang=prefix`"
+        )),
+    }
+}
+
+fn boolean(name: &str, value: Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(boolean) => Ok(boolean),
+        _ => Err(format!("`{name}` must be a boolean")),
+    }
+}
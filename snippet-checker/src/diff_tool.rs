@@ -0,0 +1,44 @@
+// Opens the snippet diffs in an external diff tool, given as a command that is run with the paths
+// of the snippet and the code, as in `meld` or `code --diff --wait`. Each tool run is waited for
+// before opening the next one, so that a file with many diffs doesn't open many windows at once
+use crate::SnippetDiff;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+// Writes the compared snippet and code to temporary files, and runs the diff tool with them
+pub fn open(command: &str, md_name: &str, diff: &SnippetDiff) -> Result<(), String> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("The diff tool command is empty")?;
+
+    // The file names tell which snippet is shown, as diff tools usually display them
+    let name = md_name.replace(['/', '\\'], "_");
+    let temp_file = |side: &str| -> PathBuf {
+        env::temp_dir().join(format!(
+            "snippet-checker-{}-{}-{}-{side}.rs",
+            process::id(),
+            name.trim_end_matches(".md"),
+            diff.index,
+        ))
+    };
+    let (snippet_file, code_file) = (temp_file("snippet"), temp_file("code"));
+    for (file, content) in [(&snippet_file, &diff.snippet), (&code_file, &diff.block)] {
+        fs::write(file, format!("{content}\n"))
+            .map_err(|e| format!("Could not write {}: {e}", file.display()))?;
+    }
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(&snippet_file)
+        .arg(&code_file)
+        .status();
+    let _ = fs::remove_file(&snippet_file);
+    let _ = fs::remove_file(&code_file);
+
+    // Diff tools usually exit with 1 when the files differ, so only a failed launch is an error
+    status
+        .map(|_| ())
+        .map_err(|e| format!("Could not run the diff tool `{command}`: {e}"))
+}
@@ -0,0 +1,63 @@
+// Support for the doc examples of Rust source files, written in `///` (or `//!`) doc comments. The
+// doc comment text is turned into markdown, keeping the line numbers, so that the examples with a
+// `# // Path:` line are checked as the markdown snippets
+use std::path::Path;
+
+// The code block attributes of rustdoc, which are still Rust code blocks
+const RUSTDOC_ATTRIBUTES: &[&str] = &[
+    "ignore",
+    "no_run",
+    "should_panic",
+    "compile_fail",
+    "test_harness",
+    "standalone_crate",
+];
+
+pub fn is_rust_file(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("rs")
+}
+
+// Returns the doc comment text of the Rust code, with the other lines blanked. The code blocks
+// that rustdoc takes as Rust (those without a language, or with only rustdoc attributes) get a
+// `rust` fence, as in markdown
+pub fn to_markdown(code: &str) -> String {
+    let mut in_block = false;
+    let markdown: Vec<String> = code
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let text = match trimmed.strip_prefix("///").or(trimmed.strip_prefix("//!")) {
+                // Four slashes start a regular comment
+                Some(text) if !trimmed.starts_with("////") => text,
+                _ => return String::new(),
+            };
+            let text = text.strip_prefix(' ').unwrap_or(text);
+
+            let Some(info) = text.trim_start().strip_prefix("```") else {
+                return text.to_string();
+            };
+            if in_block {
+                in_block = false;
+                return text.to_string();
+            }
+            in_block = true;
+            let attributes: Vec<&str> = info.split(',').map(str::trim).collect();
+            let is_rust = attributes.iter().all(|attribute| {
+                attribute.is_empty()
+                    || RUSTDOC_ATTRIBUTES.contains(attribute)
+                    || attribute.starts_with("edition")
+            });
+            match is_rust {
+                true if info.trim().is_empty() => "```rust".to_string(),
+                true => format!("```rust,{}", info.trim()),
+                false => text.to_string(),
+            }
+        })
+        .collect();
+
+    let mut markdown = markdown.join("\n");
+    if code.ends_with('\n') {
+        markdown.push('\n');
+    }
+    markdown
+}
@@ -0,0 +1,68 @@
+// Support for a `.env` file in the current directory, with `KEY=value` lines, so that the
+// environment variable settings (like `CODE_DIR`) of a project can be kept in it. The variables
+// already set in the environment take precedence over the file ones
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+// The file that is read, from the current directory
+pub const DOTENV_FILE: &str = ".env";
+
+// Returns the variables of the file, in order, or none if it doesn't exist. Lines can start with
+// `export`, values can be quoted, and the empty lines and `#` comments are skipped
+pub fn read(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Could not read {}: {e}", path.display())),
+    };
+
+    let mut vars = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("Invalid {} line {}: {message}", path.display(), i + 1);
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `KEY=value`"))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(error(&format!("invalid variable name `{key}`")));
+        }
+        vars.push((
+            key.to_string(),
+            unquote(value.trim()).ok_or_else(|| error("unclosed quote"))?,
+        ));
+    }
+    Ok(vars)
+}
+
+// Returns the value without its quotes, with the escapes of double quoted values replaced, or
+// without a trailing ` #` comment if unquoted
+fn unquote(value: &str) -> Option<String> {
+    if let Some(value) = value.strip_prefix('\'') {
+        let end = value.find('\'')?;
+        return Some(value[..end].to_string());
+    }
+    let Some(value) = value.strip_prefix('"') else {
+        let end = value.find(" #").unwrap_or(value.len());
+        return Some(value[..end].trim_end().to_string());
+    };
+
+    let mut unquoted = String::new();
+    let mut chars = value.chars();
+    loop {
+        match chars.next()? {
+            '"' => return Some(unquoted),
+            '\\' => match chars.next()? {
+                'n' => unquoted.push('\n'),
+                c => unquoted.push(c),
+            },
+            c => unquoted.push(c),
+        }
+    }
+}
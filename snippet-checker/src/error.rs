@@ -0,0 +1,61 @@
+// The errors that stop the check of a markdown file, as opposed to the snippet problems (like a
+// diff or a missing path), which are reported and only fail their snippet
+use crate::checker::Issue;
+use crate::config::ConfigError;
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum CheckError {
+    // A book or source file that could not be read (or written), with the full message
+    Io { path: PathBuf, message: String },
+    // An invalid setting, which has its own exit code
+    Config(ConfigError),
+    // A baseline, bundle or ignore file that was read, but is not valid
+    Invalid { path: PathBuf, message: String },
+    // A source path not found in the code directory, for the commands reading a single one
+    MissingPath(String),
+    // A snippet that could not be read, for the commands showing a single one
+    Snippet(Issue),
+    // A step that failed by itself, like a `cargo check` that could not be run or a self-test
+    Failed(String),
+}
+
+impl CheckError {
+    pub(crate) fn read(path: &Path, error: io::Error) -> Self {
+        CheckError::Io {
+            path: path.to_path_buf(),
+            message: format!("Could not read {}: {error}", path.display()),
+        }
+    }
+
+    pub(crate) fn write(path: &Path, error: io::Error) -> Self {
+        CheckError::Io {
+            path: path.to_path_buf(),
+            message: format!("Could not write {}: {error}", path.display()),
+        }
+    }
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckError::Io { message, .. } => write!(f, "{message}"),
+            CheckError::Config(error) => write!(f, "{error}"),
+            CheckError::Invalid { message, .. } => write!(f, "{message}"),
+            CheckError::MissingPath(path) => write!(f, "{path} is not in the code directory"),
+            CheckError::Snippet(issue) => write!(f, "{}", issue.message),
+            CheckError::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+impl From<ConfigError> for CheckError {
+    fn from(error: ConfigError) -> Self {
+        CheckError::Config(error)
+    }
+}
@@ -0,0 +1,327 @@
+// Rewrites the snippets that differ from the code with the code lines they were compared with, or
+// updates their line range if the same content moved. In the interactive mode, each diff is shown
+// first, asking whether to apply its fix
+use crate::checker::SnippetChecker;
+use crate::error::CheckError;
+use crate::json::Json;
+use crate::theme::Themed;
+use crate::{
+    common_identation, get_md_snippets_diff, print_diff, unhide_line, CheckContext, FileCheck,
+    SnippetDiff,
+};
+use colored::*;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+
+pub struct Fixer {
+    // Ask before applying each fix, which is only possible if stdin is a terminal
+    interactive: bool,
+    // Show the lines that only moved in the diffs as moved, as in the check output
+    detect_moves: bool,
+    // Whether the user answered to apply all the remaining fixes, or to stop fixing
+    apply_all: bool,
+    quit: bool,
+    // The applied fixes of each markdown file, for the fix report
+    applied: Vec<(String, AppliedFix)>,
+}
+
+pub struct AppliedFix {
+    pub index: usize,
+    pub path: String,
+    // The new line range, if only the snippet path was updated
+    pub moved_to: Option<(usize, usize)>,
+}
+
+impl AppliedFix {
+    fn new(diff: &SnippetDiff) -> Self {
+        AppliedFix {
+            index: diff.index,
+            path: diff.path.clone(),
+            moved_to: diff.moved_to,
+        }
+    }
+}
+
+// Returns the markdown document at `md_path` (relative to the book directory) with all the snippets
+// that differ from the code fixed, and the applied fixes, without writing the file nor printing
+// anything. The lines of the other snippets and the text are kept byte for byte
+pub fn compute_fixed_markdown(
+    checker: &SnippetChecker,
+    md_path: impl AsRef<Path>,
+    md: &str,
+) -> Result<(String, Vec<AppliedFix>), CheckError> {
+    fixed_markdown(md, &checker.md_dir(md_path.as_ref()), &checker.ctx)
+}
+
+// The same, with the snippet paths starting with `./` relative to `md_dir`
+pub(crate) fn fixed_markdown(
+    md: &str,
+    md_dir: &Path,
+    ctx: &CheckContext,
+) -> Result<(String, Vec<AppliedFix>), CheckError> {
+    let check = get_md_snippets_diff(md.to_string(), md_dir, ctx)?;
+    let fixes: Vec<&SnippetDiff> = check.diffs.iter().collect();
+    let fixed = apply_fixes(md, &check, &fixes);
+    Ok((fixed, fixes.into_iter().map(AppliedFix::new).collect()))
+}
+
+// Returns the markdown content with the snippets of these diffs rewritten
+fn apply_fixes(content: &str, check: &FileCheck, fixes: &[&SnippetDiff]) -> String {
+    // The lines keep their line endings, so that the other lines are written as they were read
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+
+    // Applied from the last snippet, so that the lines of the previous ones don't move
+    for diff in fixes.iter().rev() {
+        let (open, close) = check.fences[diff.index];
+        fix_snippet(&mut lines, open - 1, close - 1, diff);
+    }
+    lines.concat()
+}
+
+impl Fixer {
+    pub fn new(interactive: bool, detect_moves: bool) -> Self {
+        Fixer {
+            interactive: interactive && io::stdin().is_terminal(),
+            detect_moves,
+            apply_all: false,
+            quit: false,
+            applied: Vec::new(),
+        }
+    }
+
+    // Fixes the snippet diffs of a markdown file, returning the indexes of the fixed snippets
+    pub fn fix_file(
+        &mut self,
+        md_path: &Path,
+        md_name: &str,
+        check: &FileCheck,
+    ) -> Result<Vec<usize>, CheckError> {
+        let mut fixes = Vec::new();
+        for diff in &check.diffs {
+            if self.quit {
+                break;
+            }
+            if self.interactive && !self.apply_all && !self.confirm(md_name, diff)? {
+                continue;
+            }
+            fixes.push(diff);
+        }
+        if fixes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(md_path).map_err(|e| CheckError::read(md_path, e))?;
+        let fixed = apply_fixes(&content, check, &fixes);
+        fs::write(md_path, fixed).map_err(|e| CheckError::write(md_path, e))?;
+
+        self.applied.extend(
+            fixes
+                .iter()
+                .map(|diff| (md_name.to_string(), AppliedFix::new(diff))),
+        );
+        Ok(fixes.iter().map(|diff| diff.index).collect())
+    }
+
+    // Returns the JSON array with the applied fixes, each with the markdown file, the snippet index
+    // and path, and whether it was rewritten or only moved to other lines of the source file
+    pub fn report(&self) -> Json {
+        let fixes = self.applied.iter().map(|(md_name, fix)| {
+            let (action, moved_to) = match fix.moved_to {
+                Some((start, end)) => ("moved", Some(format!("{start}-{end}"))),
+                None => ("rewritten", None),
+            };
+            Json::object([
+                ("md", md_name.as_str().into()),
+                ("index", fix.index.into()),
+                ("path", fix.path.as_str().into()),
+                ("action", action.into()),
+                ("moved_to", moved_to.into()),
+            ])
+        });
+        Json::Array(fixes.collect())
+    }
+
+    // Shows the diff and asks whether to apply its fix, until a valid answer is given
+    fn confirm(&mut self, md_name: &str, diff: &SnippetDiff) -> Result<bool, CheckError> {
+        println!(
+            "{}#{} ({}:{})\n",
+            md_name,
+            diff.index.to_string().bold().warning(),
+            diff.path.bold().warning(),
+            diff.start_line
+        );
+        print_diff(
+            &diff.snippet,
+            &diff.block,
+            self.detect_moves,
+            &diff.source_lines,
+        );
+
+        loop {
+            print!("Apply this fix? [y/n/a/q] ");
+            io::stdout()
+                .flush()
+                .map_err(|e| CheckError::Failed(format!("Could not show the question: {e}")))?;
+            let mut answer = String::new();
+            let read = io::stdin()
+                .lock()
+                .read_line(&mut answer)
+                .map_err(|e| CheckError::Failed(format!("Could not read the answer: {e}")))?;
+
+            match answer.trim() {
+                "y" => return Ok(true),
+                "n" => return Ok(false),
+                "a" => {
+                    self.apply_all = true;
+                    return Ok(true);
+                }
+                "q" => {
+                    self.quit = true;
+                    return Ok(false);
+                }
+                // The end of the input is taken as quitting
+                _ if read == 0 => {
+                    self.quit = true;
+                    return Ok(false);
+                }
+                _ => println!("Please answer y (yes), n (no), a (all the remaining) or q (quit)"),
+            }
+        }
+    }
+}
+
+// Rewrites the `sha` directives of the snippets with a stale hash, with their new hash
+pub fn update_hashes(md_path: &Path, check: &FileCheck) -> Result<(), CheckError> {
+    let content = fs::read_to_string(md_path).map_err(|e| CheckError::read(md_path, e))?;
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+
+    for (index, hash) in &check.stale_hashes {
+        let (open, close) = check.fences[*index];
+        let Some(line) = (open..close - 1).find(|&j| lines[j].contains("# // sha:")) else {
+            continue;
+        };
+        let directive = &lines[line][..lines[line].find("sha:").unwrap()];
+        let newline = match lines[line].ends_with("\r\n") {
+            true => "\r\n",
+            false => "\n",
+        };
+        lines[line] = format!("{directive}sha: {hash}{newline}");
+    }
+    fs::write(md_path, lines.concat()).map_err(|e| CheckError::write(md_path, e))
+}
+
+// Rewrites the snippet with its opening and closing fence at these line indexes, where each line
+// ends with its line ending
+fn fix_snippet(lines: &mut Vec<String>, open: usize, close: usize, diff: &SnippetDiff) {
+    // The lines keep the prefix and line ending of the fence, as in blockquotes and list items
+    let fence = &lines[open];
+    let marker = fence.find("```").or_else(|| fence.find("~~~")).unwrap_or(0);
+    let prefix = fence[..marker].to_string();
+    // Only the Rust fences have hidden lines, as the `#` lines of the others are their comments
+    let hidden_lines = fence[marker + 3..].starts_with("rust");
+    let newline = match fence.ends_with("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    };
+
+    // The path line range is updated if the content moved, and otherwise the content is replaced
+    if let Some((start, end)) = diff.moved_to {
+        // A `lines` directive takes precedence over the path line range
+        let header = open + 2..open + 2 + diff.directives;
+        if let Some(line) = header
+            .into_iter()
+            .find(|&j| lines[j].contains("# // lines:"))
+        {
+            let directive = &lines[line][..lines[line].find("lines:").unwrap()];
+            lines[line] = format!("{directive}lines: {start}-{end}{newline}");
+            return;
+        }
+        // The line ranges of the manifest references are only updated in the manifest
+        let path_line = lines[open + 1].trim_end_matches(['\r', '\n']);
+        if !path_line.contains(" Path: ") {
+            return;
+        }
+        if let Some((path, _)) = path_line.rsplit_once(':') {
+            lines[open + 1] = format!("{path}:{start}-{end}{newline}");
+        }
+        return;
+    }
+    let snippet: Vec<&str> = lines[open + 2 + diff.directives..close]
+        .iter()
+        .map(|line| {
+            let line = line.trim_end_matches(['\r', '\n']);
+            line.strip_prefix(prefix.as_str())
+                .or_else(|| line.strip_prefix(prefix.trim_end()))
+                .unwrap_or(line)
+        })
+        .collect();
+    let code = restore_snippet_lines(
+        &snippet,
+        &crate::dedent(&diff.code),
+        diff.comment_prefix.as_deref(),
+        hidden_lines,
+    );
+    let content = code.iter().map(|line| match line.is_empty() {
+        true => format!("{}{newline}", prefix.trim_end()),
+        false => format!("{prefix}{line}{newline}"),
+    });
+    lines.splice(open + 2 + diff.directives..close, content);
+}
+
+// Returns the code lines with the identation of the snippet lines they replace, keeping the snippet
+// lines that are still in the code as they were, like the hidden lines (if the fence has them, as
+// in Rust), so that the fixed snippet shows the same lines as before. The snippet comments that are
+// not in the code are kept before the code lines that replace them, if the comments were not
+// compared (with this prefix), as they are usually explanations for the book, while the comments
+// of the code are left out, as they were not compared either. The snippet lines are matched to the
+// code lines in order
+fn restore_snippet_lines(
+    snippet: &[&str],
+    code: &str,
+    comment_prefix: Option<&str>,
+    hidden_lines: bool,
+) -> Vec<String> {
+    let unhidden: Vec<String> = snippet
+        .iter()
+        .map(|line| match hidden_lines {
+            true => unhide_line(line),
+            false => line.to_string(),
+        })
+        .collect();
+    let identation = common_identation(&unhidden.join("\n")).to_string();
+    let code: Vec<String> = code
+        .lines()
+        .map(|line| match line.is_empty() {
+            true => String::new(),
+            false => format!("{identation}{line}"),
+        })
+        .collect();
+
+    let old: Vec<&str> = unhidden.iter().map(|line| line.trim()).collect();
+    let new: Vec<&str> = code.iter().map(|line| line.trim()).collect();
+    let is_comment = |line: &str| comment_prefix.is_some_and(|prefix| line.starts_with(prefix));
+    let mut fixed = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old, &new) {
+        match op {
+            DiffOp::Equal { old_index, len, .. } => {
+                fixed.extend((0..len).map(|i| snippet[old_index + i].to_string()))
+            }
+            op => {
+                fixed.extend(
+                    op.old_range()
+                        .filter(|&i| is_comment(old[i]))
+                        .map(|i| snippet[i].to_string()),
+                );
+                fixed.extend(
+                    op.new_range()
+                        .filter(|&i| !is_comment(new[i]))
+                        .map(|i| code[i].clone()),
+                );
+            }
+        }
+    }
+    fixed
+}
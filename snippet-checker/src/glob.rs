@@ -0,0 +1,94 @@
+// Minimal glob matching for the paths given in the command line, where `?` matches any char and
+// `*` any sequence of chars (but not `/`), while `**` matches any sequence of directories
+use crate::error::CheckError;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    matches(&pattern, &path)
+}
+
+fn matches(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` can match no directories at all
+            if let ['/', after_slash @ ..] = rest {
+                if matches(after_slash, path) {
+                    return true;
+                }
+            }
+            (0..=path.len()).any(|i| matches(rest, &path[i..]))
+        }
+        ['*', rest @ ..] => {
+            for i in 0..=path.len() {
+                if matches(rest, &path[i..]) {
+                    return true;
+                }
+                if path.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => match path {
+            [c, path_rest @ ..] if *c != '/' => matches(rest, path_rest),
+            _ => false,
+        },
+        [c, rest @ ..] => match path {
+            [first, path_rest @ ..] if first == c => matches(rest, path_rest),
+            _ => false,
+        },
+    }
+}
+
+// The file of a book directory listing the markdown files to skip, as in a `.gitignore`
+pub const IGNORE_FILE: &str = ".snippetignore";
+
+// Returns the patterns of the ignore file in the book directory, if any, as patterns matched
+// against the paths relative to the book directory. As in a `.gitignore`, the patterns without a
+// `/` (other than a trailing one) match files in any subdirectory, a leading `/` only matches from
+// the book directory, and a trailing `/` matches all the files of a directory. Negated patterns
+// (with `!`) are not supported
+pub fn read_ignore_file(book_dir: &Path) -> Result<Vec<String>, CheckError> {
+    let path = book_dir.join(IGNORE_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(CheckError::read(&path, e)),
+    };
+
+    let mut patterns = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('!') {
+            return Err(CheckError::Invalid {
+                message: format!(
+                    "Negated pattern {line} in {} is not supported",
+                    path.display()
+                ),
+                path,
+            });
+        }
+        let (pattern, directory) = match line.strip_suffix('/') {
+            Some(pattern) => (pattern, true),
+            None => (line, false),
+        };
+        let mut pattern = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if !pattern.contains('/') => format!("**/{pattern}"),
+            None => pattern.to_string(),
+        };
+        if directory {
+            pattern.push_str("/**");
+        }
+        patterns.push(pattern);
+    }
+    Ok(patterns)
+}
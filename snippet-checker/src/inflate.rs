@@ -0,0 +1,229 @@
+// A DEFLATE (RFC 1951) decompressor, for reading the gzip and zip source archives. It favors
+// simplicity over speed, decoding the Huffman codes one bit at a time. Invalid or truncated data
+// is an error, and so is an output larger than `MAX_OUTPUT`, as from a corrupted archive
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// The largest decompressed size of a file, far above the size of the floresta source archives
+const MAX_OUTPUT: usize = 1 << 30;
+
+// The order in which the code length code lengths are stored in dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    // The position of the next bit, in bits
+    position: usize,
+}
+
+impl BitReader<'_> {
+    // Reads `count` bits, least significant first
+    fn bits(&mut self, count: u8) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.position / 8)
+                .ok_or("unexpected end of compressed data")?;
+            value |= (((byte >> (self.position % 8)) & 1) as u32) << i;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    // Skips to the next byte boundary, and returns the byte position
+    fn align(&mut self) -> usize {
+        self.position = self.position.div_ceil(8) * 8;
+        self.position / 8
+    }
+}
+
+// A canonical Huffman code, given by the number of codes of each length and the symbols sorted by
+// code
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols = Vec::new();
+        for length in 1..16 {
+            for (symbol, _) in lengths.iter().enumerate().filter(|(_, l)| **l == length) {
+                symbols.push(symbol as u16);
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        // The first code of the current length, and the index of its symbol
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+// Decompresses a raw DEFLATE stream
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    inflate_limited(data, MAX_OUTPUT)
+}
+
+// The same, failing if the output is larger than `max_output` bytes
+pub(crate) fn inflate_limited(data: &[u8], max_output: usize) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut output = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                let start = reader.align();
+                let header = data
+                    .get(start..start + 4)
+                    .ok_or("unexpected end of compressed data")?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if length != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err("corrupted stored block length".to_string());
+                }
+                let block = data
+                    .get(start + 4..start + 4 + length)
+                    .ok_or("unexpected end of compressed data")?;
+                if output.len() + block.len() > max_output {
+                    return Err(too_large(max_output));
+                }
+                output.extend_from_slice(block);
+                reader.position = (start + 4 + length) * 8;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut output, max_output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, max_output, &literals, &distances)?;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn too_large(max_output: usize) -> String {
+    format!("decompressed data larger than {max_output} bytes")
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    // The literal and distance code lengths are stored together, and repeats can span both
+    let mut lengths = Vec::new();
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or("repeated length with no previous one")?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() != literal_count + distance_count {
+        return Err("too many code lengths".to_string());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    max_output: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        // A symbol writes at most 258 bytes, so the output size is checked before each one
+        if output.len() > max_output {
+            return Err(too_large(max_output));
+        }
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err("invalid length symbol".to_string());
+                }
+                let length = LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i])? as usize;
+
+                let i = distances.decode(reader)? as usize;
+                if i >= DISTANCE_BASE.len() {
+                    return Err("invalid distance symbol".to_string());
+                }
+                let distance = DISTANCE_BASE[i] as usize + reader.bits(DISTANCE_EXTRA[i])? as usize;
+                if distance > output.len() {
+                    return Err("distance too far back".to_string());
+                }
+
+                // The copied bytes can overlap the ones being written
+                let start = output.len() - distance;
+                for j in 0..length {
+                    output.push(output[start + j]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// A minimal JSON value, to write the machine readable output formats
+use std::fmt;
+
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object<const N: usize>(fields: [(&str, Json); N]) -> Self {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<u128> for Json {
+    fn from(value: u128) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Self {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+// Writes the value over multiple lines, indenting each nesting level by two spaces
+fn write_pretty(f: &mut fmt::Formatter, value: &Json, level: usize) -> fmt::Result {
+    let indent = "  ".repeat(level + 1);
+    let separator = |i, len| if i + 1 < len { "," } else { "" };
+    match value {
+        Json::Array(values) if !values.is_empty() => {
+            writeln!(f, "[")?;
+            for (i, value) in values.iter().enumerate() {
+                write!(f, "{indent}")?;
+                write_pretty(f, value, level + 1)?;
+                writeln!(f, "{}", separator(i, values.len()))?;
+            }
+            write!(f, "{}]", "  ".repeat(level))
+        }
+        Json::Object(fields) if !fields.is_empty() => {
+            writeln!(f, "{{")?;
+            for (i, (key, value)) in fields.iter().enumerate() {
+                write!(f, "{indent}")?;
+                write_string(f, key)?;
+                write!(f, ": ")?;
+                write_pretty(f, value, level + 1)?;
+                writeln!(f, "{}", separator(i, fields.len()))?;
+            }
+            write!(f, "{}}}", "  ".repeat(level))
+        }
+        value => write!(f, "{value}"),
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// Writes the compact representation of the value, or the pretty one with `{:#}`
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            return write_pretty(f, self, 0);
+        }
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            Json::Number(value) => write!(f, "{value}"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
@@ -0,0 +1,6119 @@
+use colored::*;
+use regex::Regex; // For matching Rust code blocks in markdown files
+use similar::{ChangeTag, TextDiff}; // For calculating and displaying differences
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::panic;
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir; // For recursively iterating through directories
+
+mod archive;
+mod baseline;
+mod bundle;
+mod cargo_check;
+mod cfg;
+mod checker;
+mod comparator;
+mod config;
+mod diff_tool;
+mod doc_tests;
+mod dotenv;
+mod error;
+mod fix;
+mod glob;
+mod inflate;
+mod json;
+mod manifest;
+mod rst;
+mod selftest;
+mod sha256;
+mod source;
+#[cfg(test)]
+mod tests;
+mod theme;
+mod tokens;
+mod toml;
+mod unicode;
+mod watch;
+
+pub use checker::{
+    DiffLine, Issue, IssueKind, SnippetChecker, SnippetCheckerBuilder, SnippetOutcome,
+    SnippetResult,
+};
+pub use comparator::{Comparator, Identity, StripComments};
+pub use config::ConfigError;
+pub use error::CheckError;
+pub use fix::{compute_fixed_markdown, AppliedFix};
+pub use source::SourceResolver;
+
+use archive::Archive;
+use checker::diff_lines;
+use config::Config;
+use json::Json;
+use source::FsResolver;
+use theme::Themed;
+use watch::WatchedFile;
+
+// The book source directory if no `book.toml` is found, as a sibling from current one
+const MDBOOK_DIR: &str = "../src";
+const DEFAULT_PLACEHOLDER: &str = "/* ... */";
+// The snippet lines that stand for several code lines, with `allow_placeholder`
+const PLACEHOLDER_LINES: &[&str] = &["todo!()", "unimplemented!()", "/* ... */"];
+
+fn bold_red(str: &str) -> ColoredString {
+    str.bold().error()
+}
+
+// Settings that affect how the markdown files are checked
+struct CheckContext {
+    // The floresta root directory, containing the `crates` directory
+    code_dir: String,
+    // The backend the source files are read from instead of `code_dir` if given, like a code
+    // archive
+    source: Option<Box<dyn SourceResolver + Send + Sync>>,
+    // Print every matching snippet along with the code lines it was compared with
+    verbose: bool,
+    format: OutputFormat,
+    // Write the JSON output formats over multiple lines, instead of compact
+    json_pretty: bool,
+    // Report snippets with unclosed (or extra closing) delimiters, as they are likely truncated
+    require_balanced_braces: bool,
+    // Warn about snippets with more cleaned lines than this, as they are hard to read (0 for no limit)
+    max_snippet_lines: usize,
+    // The most occurrences of the first snippet line that are tried when searching for it, before
+    // reporting the line as too ambiguous (0 for no limit)
+    max_candidates: usize,
+    // The license header lines at the start of each source file, which are never shown in the book,
+    // so they are left out of the line ranges starting in them and of the directory snippets
+    strip_header_lines: usize,
+    // Require snippets to not be idented, and only remove one identation level from the code,
+    // instead of removing the common identation of both
+    check_indent: bool,
+    // Compare comments and empty lines too, instead of skipping them in both snippet and code
+    keep_comments: bool,
+    // The code files as they would be written in snippet paths, listed when first needed
+    source_files: OnceLock<Vec<String>>,
+    // Glob patterns selecting the markdown files to check (all of them if empty), and the ones to
+    // skip, which take precedence
+    includes: Vec<String>,
+    excludes: Vec<String>,
+    // Only check the markdown files modified after this time, if given
+    modified_since: Option<SystemTime>,
+    // After checking, list the snippets that reference each source file
+    group_by_source: bool,
+    // After checking, print the snippet counts of each crate
+    group_by_crate: bool,
+    // After checking, print again the diffs with the most changed lines, at most this many
+    report_largest_diffs: usize,
+    // Take lines that only differ in trailing whitespace as equal
+    ignore_trailing_whitespace: bool,
+    // Check that the snippets of each source file show its lines in the book reading order
+    check_global_order: bool,
+    // After checking, report the snippets that show some of the same lines of a source file
+    check_overlaps: bool,
+    // After checking, list the files, snippets and Rust fences that were not checked, and why
+    explain_skips: bool,
+    // Check that the snippets of each source file show its lines in order within each markdown file
+    check_intra_file_order: bool,
+    // Also check the mdBook `{{#include}}` directives
+    check_includes: bool,
+    // Fail on every Rust fence without a snippet header, which is not checked, so that the
+    // illustrative blocks must be written with another language
+    fail_on_unparsed_fence: bool,
+    // Compose the accented letters written with combining marks, in both snippet and code
+    unicode_normalize: bool,
+    // Join the lines of the expressions wrapped over several lines, in both snippet and code
+    normalize_line_wrapping: bool,
+    // Compare the Rust tokens of the snippet and code, ignoring whitespace, comments, line breaks
+    // and trailing commas
+    ast_compare: bool,
+    // Also check the reStructuredText files, with `.. code-block:: rust` snippets
+    rst: bool,
+    // Also check the doc examples of the `.rs` files in the book directories
+    doc_tests: bool,
+    // Only print the changed lines of the diffs, without the equal ones
+    only_changed_lines: bool,
+    // Show the runs of lines that only moved in the diffs as moved, instead of deleted and inserted
+    detect_moves: bool,
+    // Also print the fenced snippet of each diff as written in the markdown file, before cleaning
+    show_raw: bool,
+    // Print the snippet line, and the source file line, of each diff line
+    line_numbers: bool,
+    // The number of source lines before the compared code that are printed with each diff
+    source_context: usize,
+    // The number of equal lines around the changes of the `review-json` diffs
+    diff_context: usize,
+    // The external diff tool command that also shows each diff, if not empty and the output is a
+    // terminal. It is cleared after failing to launch, so that the error is reported only once
+    diff_tool: Mutex<String>,
+    // Print an aligned status line with the snippet counts of each file, with the file names
+    // padded to `status_width`
+    compact_status: bool,
+    status_width: usize,
+    // Fail on the warnings too, instead of only printing them
+    strict: bool,
+    missing_paths: MissingPaths,
+    leading_attributes: LeadingAttributes,
+    // Ignore the order of consecutive `use` lines, which rustfmt may have sorted differently
+    sort_use: bool,
+    // The enabled cfg options, like `feature=x`, to remove the code disabled by `#[cfg(...)]`
+    // attributes before comparing. No attributes are evaluated if empty
+    cfg: Vec<String>,
+    // The attributes (like `doc(hidden)` or `cfg_attr`) whose lines are removed from both the
+    // snippets and code before comparing, matched by the start of the attribute
+    ignore_attributes: Vec<String>,
+    // The comment prefixes of other snippet languages (or replacing the built-in ones), as
+    // `lang=prefix`
+    comment_prefixes: Vec<String>,
+    // The languages of the fences that are also checked, like `toml`, whose snippet header is
+    // written with the comment prefix of the language, as `# Path: tools/Cargo.toml`
+    fence_languages: Vec<String>,
+    // The snippet path of each ID of the snippet manifest, for the `# // Ref: <id>` lines
+    snippet_refs: manifest::Refs,
+    // A token that matches any text of the code line, in the snippet lines that abstract a
+    // literal, or none if empty
+    placeholder: String,
+    // Take the snippet lines that are only `todo!()`, `unimplemented!()` or `/* ... */` as the code
+    // lines they stand for, up to the end of their block
+    allow_placeholder: bool,
+    // Compare the snippets that end with a placeholder body, like `fn new() -> Self { /* ... */ }`,
+    // only with the signature of the code, up to its opening brace
+    signature_only: bool,
+    // Report snippet paths that go through symlinks, which could point outside the code directory
+    no_symlinks: bool,
+    // Resolve the snippet paths with `*` or `?` to the only source file they match, so that a
+    // path like `p2p-*/src/lib.rs` still works after the crate directory is renamed
+    allow_glob_paths: bool,
+    // Only check that the snippet paths exist, without finding nor comparing their code blocks,
+    // and fail for the missing ones
+    only_missing: bool,
+    // Only check the snippet at this index, printing each step of the check
+    explain: Option<usize>,
+    // Only find the code block of each snippet, taking it as verified without comparing, for `grep`
+    extract_only: bool,
+    // The last normalization of the snippets and code blocks before comparing them
+    comparator: Box<dyn Comparator + Send + Sync>,
+    // After checking, print the slowest and largest source file reads to stderr (also printed in
+    // verbose mode)
+    profile: bool,
+    // After checking, print the verified snippets whose source file was modified after their
+    // markdown file to stderr, as the chapter may need to be revisited
+    freshness: bool,
+    // The reads of each source file, to profile them
+    source_reads: Mutex<BTreeMap<PathBuf, SourceReads>>,
+    // The content of the files at these paths, read instead of the filesystem (or the source
+    // backend) so that a check can run from memory, as in tests. The paths are joined to the code
+    // directory or the markdown file directory, as the read paths
+    overlay: HashMap<PathBuf, String>,
+}
+
+// The number of reads of a source file, and the time spent in them
+#[derive(Clone, Copy, Default)]
+struct SourceReads {
+    count: usize,
+    bytes: usize,
+    time: Duration,
+}
+
+impl CheckContext {
+    // Returns the default settings for checking against the code in `code_dir`
+    fn new(code_dir: String) -> Self {
+        CheckContext {
+            code_dir,
+            source: None,
+            verbose: false,
+            format: OutputFormat::Human,
+            json_pretty: false,
+            require_balanced_braces: false,
+            max_snippet_lines: 0,
+            max_candidates: 50,
+            strip_header_lines: 0,
+            check_indent: false,
+            keep_comments: false,
+            source_files: OnceLock::new(),
+            includes: Vec::new(),
+            excludes: Vec::new(),
+            modified_since: None,
+            group_by_source: false,
+            group_by_crate: false,
+            report_largest_diffs: 0,
+            ignore_trailing_whitespace: false,
+            only_changed_lines: false,
+            detect_moves: false,
+            show_raw: false,
+            line_numbers: false,
+            compact_status: false,
+            rst: false,
+            doc_tests: false,
+            ast_compare: false,
+            normalize_line_wrapping: false,
+            unicode_normalize: false,
+            source_context: 0,
+            diff_context: 3,
+            diff_tool: Mutex::new(String::new()),
+            status_width: 0,
+            check_global_order: false,
+            check_overlaps: false,
+            explain_skips: false,
+            check_intra_file_order: false,
+            check_includes: false,
+            fail_on_unparsed_fence: false,
+            strict: false,
+            missing_paths: MissingPaths::Error,
+            leading_attributes: LeadingAttributes::Auto,
+            sort_use: false,
+            cfg: Vec::new(),
+            snippet_refs: manifest::Refs::new(),
+            ignore_attributes: Vec::new(),
+            comment_prefixes: Vec::new(),
+            fence_languages: Vec::new(),
+            placeholder: DEFAULT_PLACEHOLDER.to_string(),
+            allow_placeholder: false,
+            signature_only: false,
+            no_symlinks: false,
+            allow_glob_paths: false,
+            only_missing: false,
+            explain: None,
+            extract_only: false,
+            comparator: Box::new(comparator::Identity),
+            profile: false,
+            freshness: false,
+            source_reads: Mutex::new(BTreeMap::new()),
+            overlay: HashMap::new(),
+        }
+    }
+
+    // Whether the file is checked as a book chapter: a markdown file, or a reStructuredText or Rust
+    // one if enabled
+    fn is_book_file(&self, path: &Path) -> bool {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("md") => true,
+            Some("rst") => self.rst,
+            Some("rs") => self.doc_tests,
+            _ => false,
+        }
+    }
+
+    // Reads a book chapter, as markdown
+    fn read_book_file(&self, path: &Path) -> io::Result<String> {
+        let content = fs::read_to_string(path)?;
+        if rst::is_rst(path) {
+            return Ok(rst::to_markdown(&content));
+        }
+        match doc_tests::is_rust_file(path) {
+            true => Ok(doc_tests::to_markdown(&content)),
+            false => Ok(content),
+        }
+    }
+
+    // Whether to check the markdown file at this path, relative to the book directory, where
+    // `ignored` are the patterns of the book ignore file, excluded as the `excludes`
+    fn is_selected(&self, md_name: &str, ignored: &[String]) -> bool {
+        let included = self.includes.is_empty()
+            || self
+                .includes
+                .iter()
+                .any(|pattern| glob::glob_match(pattern, md_name));
+
+        included
+            && !self
+                .excludes
+                .iter()
+                .chain(ignored)
+                .any(|pattern| glob::glob_match(pattern, md_name))
+    }
+
+    // Calls `f` with the source backend, which reads the code directory if no other is given
+    fn with_source<T>(&self, f: impl FnOnce(&dyn SourceResolver) -> T) -> T {
+        match &self.source {
+            Some(source) => f(source.as_ref()),
+            None => f(&FsResolver::new(&self.code_dir)),
+        }
+    }
+
+    // The directory the source paths are joined to, which is empty for the backends that are not
+    // on the filesystem
+    fn source_root(&self) -> &str {
+        match self.source {
+            Some(_) => "",
+            None => &self.code_dir,
+        }
+    }
+
+    fn source_files(&self) -> &[String] {
+        self.source_files.get_or_init(|| {
+            let overlay_files = self
+                .overlay
+                .keys()
+                .filter_map(|path| path.strip_prefix(self.source_root()).ok())
+                .map(|path| path.display().to_string());
+            self.with_source(|source| source.files())
+                .into_iter()
+                .chain(overlay_files)
+                .map(|path| {
+                    // Files in the `crates` directory are written without the prefix
+                    match path.strip_prefix("crates/") {
+                        Some(path) => path.to_string(),
+                        None => path,
+                    }
+                })
+                .collect()
+        })
+    }
+
+    // Whether the code file exists in the source backend
+    fn is_code_file(&self, path: &Path) -> bool {
+        if self.overlay.contains_key(path) {
+            return true;
+        }
+        let rel = path.strip_prefix(self.source_root()).unwrap_or(path);
+        self.with_source(|source| source.exists(&rel.to_string_lossy())) == Ok(true)
+    }
+
+    fn record_read(&self, path: &Path, bytes: usize, time: Duration) {
+        let mut source_reads = self.source_reads.lock().unwrap();
+        let reads = source_reads.entry(path.to_path_buf()).or_default();
+        reads.count += 1;
+        reads.bytes = bytes;
+        reads.time += time;
+    }
+
+    fn read_code(&self, path: &Path) -> Result<Vec<u8>, CheckError> {
+        if let Some(content) = self.overlay.get(path) {
+            return Ok(content.as_bytes().to_vec());
+        }
+        let rel = path.strip_prefix(self.source_root()).unwrap_or(path);
+        self.with_source(|source| source.read(&rel.to_string_lossy()))
+            .map_err(|e| CheckError::Io {
+                message: format!("Could not read {}: {e}", path.display()),
+                path: path.to_path_buf(),
+            })
+    }
+
+    // Reads a file next to the markdown files, which is never in the source backend
+    fn read_local(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.overlay.get(path) {
+            Some(content) => Ok(content.as_bytes().to_vec()),
+            None => fs::read(path),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat {
+    // The colored per-file status and diffs
+    Human,
+    // A JSON array with the time spent checking each file
+    StatsJson,
+    // A JSON array with the snippet paths that don't exist
+    MissingJson,
+    // A JSON array with the code lines of each matching snippet
+    LinksJson,
+    // A JSON array with the number of snippets of each file, including those with none
+    CoverageJson,
+    // A JSON array of Language Server Protocol diagnostics, located in the markdown document
+    Lsp,
+    // A single line with the snippet counts of all the files, as for a commit status description
+    StatusLine,
+    // A JSON array with the snippet counts of each crate
+    CratesJson,
+    // A JSON array of GitHub pull request review comments, with the unified diff of each snippet
+    ReviewJson,
+    // Copies of the markdown files in `output_dir`, with a comment telling the result of each
+    // checked snippet above its fence
+    Annotate,
+    // A Test Anything Protocol report, with a test for each snippet and the diffs in YAML blocks
+    Tap,
+    // No output, set by `count_only`
+    CountOnly,
+    // No output, set by `quiet`
+    Quiet,
+}
+
+impl OutputFormat {
+    fn parse(format: &str) -> Option<Self> {
+        match format {
+            "human" => Some(OutputFormat::Human),
+            "stats-json" => Some(OutputFormat::StatsJson),
+            "missing-json" => Some(OutputFormat::MissingJson),
+            "links-json" => Some(OutputFormat::LinksJson),
+            "coverage-json" => Some(OutputFormat::CoverageJson),
+            "lsp" => Some(OutputFormat::Lsp),
+            "status-line" => Some(OutputFormat::StatusLine),
+            "crates-json" => Some(OutputFormat::CratesJson),
+            "review-json" => Some(OutputFormat::ReviewJson),
+            "annotate" => Some(OutputFormat::Annotate),
+            "tap" => Some(OutputFormat::Tap),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Human => "human",
+            OutputFormat::StatsJson => "stats-json",
+            OutputFormat::MissingJson => "missing-json",
+            OutputFormat::LinksJson => "links-json",
+            OutputFormat::CoverageJson => "coverage-json",
+            OutputFormat::Lsp => "lsp",
+            OutputFormat::StatusLine => "status-line",
+            OutputFormat::CratesJson => "crates-json",
+            OutputFormat::ReviewJson => "review-json",
+            OutputFormat::Annotate => "annotate",
+            OutputFormat::Tap => "tap",
+            OutputFormat::CountOnly => "count-only",
+            OutputFormat::Quiet => "quiet",
+        }
+    }
+}
+
+// How snippets whose path doesn't exist are handled
+#[derive(Clone, Copy, PartialEq)]
+enum MissingPaths {
+    // Printed as warnings, which only fail the check in the `strict` mode
+    Warn,
+    Error,
+    // Not checked nor reported
+    Skip,
+}
+
+impl MissingPaths {
+    fn parse(handling: &str) -> Option<Self> {
+        match handling {
+            "warn" => Some(MissingPaths::Warn),
+            "error" => Some(MissingPaths::Error),
+            "skip" => Some(MissingPaths::Skip),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            MissingPaths::Warn => "warn",
+            MissingPaths::Error => "error",
+            MissingPaths::Skip => "skip",
+        }
+    }
+}
+
+// How the attributes right before the code of a snippet, like `#[derive(Debug)]`, are compared
+#[derive(Clone, Copy, PartialEq)]
+enum LeadingAttributes {
+    // Compared only if the snippet starts with them, as the snippets found by their first line
+    // don't include the attributes before it
+    Auto,
+    // The snippet must include them
+    Require,
+    // Skipped in both the snippet and the code
+    Ignore,
+}
+
+impl LeadingAttributes {
+    fn parse(handling: &str) -> Option<Self> {
+        match handling {
+            "auto" => Some(LeadingAttributes::Auto),
+            "require" => Some(LeadingAttributes::Require),
+            "ignore" => Some(LeadingAttributes::Ignore),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            LeadingAttributes::Auto => "auto",
+            LeadingAttributes::Require => "require",
+            LeadingAttributes::Ignore => "ignore",
+        }
+    }
+}
+
+// The result of checking the snippets of a markdown file
+struct FileCheck {
+    // `None` if there was no snippet, `Some(false)` if there was a snippet with no difference
+    // with the floresta code, or `Some(true)` if there was a difference
+    diff: Option<bool>,
+    verified: Vec<VerifiedSnippet>,
+    diffs: Vec<SnippetDiff>,
+    // Problems found in the snippets, regardless of whether they match the code
+    issues: Vec<SnippetIssue>,
+    // Snippets whose path doesn't exist, which are not compared
+    missing: Vec<MissingPath>,
+    // Whether the missing paths fail the check, instead of being warnings
+    missing_fails: bool,
+    // Likely problems, which are printed but only fail the check in the `strict` mode
+    warnings: Vec<SnippetIssue>,
+    // Remarks about the snippets that are not problems, only printed in verbose mode
+    notes: Vec<SnippetIssue>,
+    // The number of snippets declared with a `<!-- snippets: N -->` comment, if any, and
+    // the markdown line of the comment
+    expected_snippets: Option<usize>,
+    snippet_count_line: usize,
+    // The mdBook include directives that don't resolve to any content, in the `check_includes` mode
+    broken_includes: Vec<BrokenInclude>,
+    // Rust fences whose first line looks like a snippet path but is not written as one, so they are
+    // not checked, and whether they fail the check (in the `strict` mode) instead of being warnings
+    malformed_headers: Vec<MalformedHeader>,
+    malformed_fails: bool,
+    // The source files read to check the snippets
+    sources: BTreeSet<PathBuf>,
+    // The markdown lines of the opening and closing fence of each snippet, by index
+    fences: Vec<(usize, usize)>,
+    // The path of each snippet as written (or given by the manifest), without the line range or
+    // anchors, by index
+    paths: Vec<String>,
+    // The snippets that match the code, but whose `sha` directive is not the hash of their code
+    // block, with the new hash
+    stale_hashes: Vec<(usize, String)>,
+    // The snippets and Rust fences that were not checked, for `explain_skips`
+    skipped: Vec<Skipped>,
+}
+
+impl FileCheck {
+    fn snippets(&self) -> usize {
+        self.verified.len() + self.diffs.len()
+    }
+
+    fn failed(&self) -> bool {
+        self.diff == Some(true)
+            || !self.issues.is_empty()
+            || self.snippet_count_mismatch()
+            || !self.broken_includes.is_empty()
+            || (self.malformed_fails && !self.malformed_headers.is_empty())
+            || (self.missing_fails && !self.missing.is_empty())
+    }
+
+    // Whether the file doesn't have the declared number of snippets, which usually means some
+    // snippet was removed by accident
+    fn snippet_count_mismatch(&self) -> bool {
+        self.expected_snippets
+            .is_some_and(|expected| expected != self.checked_snippets())
+    }
+
+    // The number of checked snippets, whether they match the code or not, which doesn't depend on
+    // the baseline. The skipped snippets are not counted
+    fn checked_snippets(&self) -> usize {
+        let verified = self.verified.iter().map(|snippet| snippet.index);
+        verified
+            .chain(self.diffs.iter().map(|diff| diff.index))
+            .chain(self.issues.iter().map(|issue| issue.index))
+            .chain(self.missing.iter().map(|missing| missing.index))
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    // The indexes of the snippets that differ from the code or have issues
+    fn failed_snippets(&self) -> BTreeSet<usize> {
+        let diffs = self.diffs.iter().map(|diff| diff.index);
+        diffs
+            .chain(self.issues.iter().map(|issue| issue.index))
+            .chain(
+                self.missing
+                    .iter()
+                    .filter(|_| self.missing_fails)
+                    .map(|missing| missing.index),
+            )
+            .collect()
+    }
+}
+
+struct SnippetIssue {
+    index: usize,
+    kind: IssueKind,
+    message: String,
+}
+
+// An `{{#include}}` directive of the markdown file that mdBook can't resolve to any content
+struct BrokenInclude {
+    // The markdown line of the directive
+    line: usize,
+    directive: String,
+    message: String,
+}
+
+struct MalformedHeader {
+    // The markdown line of the header, after the fence line, or the fence line if it has no header
+    line: usize,
+    header: String,
+    // Whether the fence has no header at all, in the `fail_on_unparsed_fence` mode
+    unparsed: bool,
+}
+
+impl MalformedHeader {
+    fn message(&self) -> String {
+        match self.unparsed {
+            true => concat!(
+                "Rust fence without a snippet header, block not checked (add a `# // Path:` ",
+                "line, or use another language for an illustrative block)"
+            )
+            .to_string(),
+            false => format!(
+                "malformed snippet header, block not checked: {}",
+                self.header
+            ),
+        }
+    }
+}
+
+// A snippet or Rust fence of a markdown file that was not checked
+enum Skipped {
+    // A snippet with a `skip` directive or fence attribute, and its path as written
+    Directive { index: usize, path: String },
+    // A snippet whose path doesn't exist, in the `missing_paths = "skip"` mode
+    MissingPath { index: usize, path: String },
+    // A Rust fence without a snippet header, at this markdown line
+    NoHeader { line: usize },
+}
+
+// A snippet path that was not found in the code directory
+struct MissingPath {
+    index: usize,
+    path: String,
+    // The paths that were tried, relative to the code directory
+    attempted: Vec<String>,
+    // The closest existing path, if it's likely a typo
+    suggestion: Option<String>,
+}
+
+// A snippet that matches the code, and the code lines it was found at
+struct VerifiedSnippet {
+    index: usize,
+    // The markdown line where the snippet fence starts
+    fence_line: usize,
+    // The path as written in the snippet, without the line range or anchors
+    path: String,
+    // The source file it was resolved to, relative to the code directory
+    source: String,
+    start_line: usize,
+    end_line: usize,
+    lines: usize,
+    // The code block of the snippet, only kept in the `extract_only` mode
+    block: String,
+    // The snippet as compared with the code, which is empty in the `extract_only` mode
+    snippet: String,
+}
+
+// A snippet that doesn't match the code, and the (cleaned) code block it was compared with
+struct SnippetDiff {
+    index: usize,
+    // The path as written in the snippet, without the line range or anchors
+    path: String,
+    // The source file it was resolved to, relative to the code directory
+    source: String,
+    start_line: usize,
+    // The lines where the snippet content was found, if the referenced line range is outdated
+    moved_to: Option<(usize, usize)>,
+    // The number of directive lines after the path line
+    directives: usize,
+    // Why the snippet differs, if more can be told than the diff shows, like the first TOML key
+    // whose value differs (as the TOML snippets are compared by their keys)
+    hint: Option<String>,
+    snippet: String,
+    block: String,
+    // The markdown lines of the snippet lines that are not in the code block
+    md_lines: Vec<usize>,
+    // The code lines the snippet was compared with, as written in the source file (including the
+    // comments), to fix the snippet
+    code: String,
+    // The comment prefix of the snippet lines that were not compared, which are kept by the fix
+    comment_prefix: Option<String>,
+    // The source lines before the compared code, with their line numbers, for `source_context`
+    source_context: Vec<(usize, String)>,
+    // The markdown lines of the fenced snippet, before cleaning, for `show_raw`
+    raw: Vec<String>,
+    // The source file line of each line of the compared code block, if found, for `line_numbers`
+    source_lines: Vec<Option<usize>>,
+}
+
+// The exit code when some snippet differs from the code or has problems
+const EXIT_DIFF: u8 = 1;
+// The exit code when the snippets could not be checked, as with unreadable files
+const EXIT_TOOLING_ERROR: u8 = 2;
+// The exit code for invalid settings, including unknown arguments and missing directories
+const EXIT_CONFIG_ERROR: u8 = 3;
+
+// Runs the command line interface, with the arguments of the process, printing the results and
+// returning the exit code. The binary only calls this, and other tools can use `SnippetChecker`
+pub fn cli() -> ExitCode {
+    control::set_override(true); // Force colored output for CI environment
+
+    match run() {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("{} {}", bold_red("Error:"), error);
+            match error {
+                CheckError::Config(_) => ExitCode::from(EXIT_CONFIG_ERROR),
+                _ => ExitCode::from(EXIT_TOOLING_ERROR),
+            }
+        }
+    }
+}
+
+// Returns the exit code for whether a diff (or another problem) was found
+fn exit_code(diff: bool) -> ExitCode {
+    match diff {
+        true => ExitCode::from(EXIT_DIFF),
+        false => ExitCode::SUCCESS,
+    }
+}
+
+// Runs the command given by the arguments, returning the exit code
+fn run() -> Result<ExitCode, CheckError> {
+    // The failing snippets, which are written to the baseline with `baseline_write`, and the
+    // other failures of the run, which can't be
+    let (mut snippet_diff, mut final_diff) = (false, false);
+
+    if env::args().nth(1).as_deref() == Some("selftest") {
+        selftest::run()?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if env::args().nth(1).as_deref() == Some("explain") {
+        return explain(env::args().skip(2)).map(exit_code);
+    }
+
+    if env::args().nth(1).as_deref() == Some("grep") {
+        return grep(env::args().skip(2)).map(exit_code);
+    }
+
+    if env::args().nth(1).as_deref() == Some("rename") {
+        return rename(env::args().skip(2)).map(exit_code);
+    }
+
+    if env::args().nth(1).as_deref() == Some("bundle") {
+        return write_bundle(env::args().skip(2)).map(exit_code);
+    }
+
+    // Not documented in the usage, as it's only meant to write the self-test fixtures
+    if env::args().nth(1).as_deref() == Some("gen-fixture") {
+        gen_fixture(env::args().skip(2))?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut config = Config::load(env::args().skip(1))?;
+    if config.print_config {
+        config.print();
+        return Ok(ExitCode::SUCCESS);
+    }
+    check_settings(&mut config)?;
+    // The books of the bundle are checked instead of the configured ones, against its code. The
+    // extracted book files are removed when returning
+    let _extracted = match config.verify_bundle.as_str() {
+        "" => None,
+        path => {
+            let bundle = bundle::Bundle::open(Path::new(path))?;
+            config.books = bundle
+                .books
+                .into_iter()
+                .map(|(name, dir)| config::Book {
+                    name,
+                    dir: dir.display().to_string(),
+                    code_dir: None,
+                })
+                .collect();
+            config.ctx.source = Some(Box::new(bundle.code));
+            Some(bundle.extracted)
+        }
+    };
+    if !config.stdin {
+        config.check_book_dirs()?;
+    }
+    config.color_theme.apply();
+    // The context is taken out of the config, which is still used for the other settings
+    let mut ctx = std::mem::replace(&mut config.ctx, CheckContext::new(String::new()));
+    open_code(&mut ctx, &config)?;
+    if config.stdin {
+        return check_stdin(&ctx, &config).map(exit_code);
+    }
+    let baseline = match config.baseline.as_str() {
+        "" => BTreeSet::new(),
+        path => baseline::read(Path::new(path))?,
+    };
+    // Every failing snippet, and how many of them are in the baseline
+    let mut failing = BTreeSet::new();
+    let mut known_failures = 0;
+    // The failing snippets not in the baseline that only differ from the code, or only have a
+    // missing path, which are allowed up to `max_diffs` and `max_missing`
+    let (mut new_diffs, mut new_missing) = (0, 0);
+    // The entries of the JSON output formats
+    let mut json_entries = Vec::new();
+    let mut results = Vec::new();
+    // The markdown files that were not checked, and why, for `explain_skips`
+    let mut skipped_files = Vec::new();
+    // Where each checked file is from, to check it again in the `watch` mode
+    let mut watched = Vec::new();
+    // The files that could not be checked, like the unreadable ones, which fail the run once the
+    // other files are checked
+    let mut file_errors = 0;
+    let default_code_dir = ctx.code_dir.clone();
+    // With multiple books, the markdown file names start with the book name
+    let multiple_books = config.books.len() > 1;
+    // With `--assume-yes` all the fixes are applied without prompts, even if `--interactive` is set
+    let mut fixer = fix::Fixer::new(
+        config.interactive && !config.assume_yes,
+        config.ctx.detect_moves,
+    );
+    if ctx.compact_status {
+        ctx.status_width = status_width(&config, &ctx, multiple_books);
+    }
+
+    for book in &config.books {
+        let code_dir = book.code_dir.as_ref().unwrap_or(&default_code_dir);
+        if *code_dir != ctx.code_dir {
+            ctx.code_dir = code_dir.clone();
+            ctx.source_files = OnceLock::new();
+        }
+        if ctx.verbose && ctx.format == OutputFormat::Human && ctx.source.is_none() {
+            println!("Code directory: {}", ctx.code_dir);
+        }
+        let (mut files, mut failed_files) = (0, 0);
+        let ignored = glob::read_ignore_file(Path::new(&book.dir))?;
+
+        // Walk through all files in the mdBook directory recursively. The unreadable entries are
+        // reported, which fails the check in the `strict` mode
+        let (entries, walk_errors) = walk_book(&book.dir);
+        print_walk_errors(&walk_errors);
+        if ctx.strict && !walk_errors.is_empty() {
+            final_diff = true;
+        }
+        // The selected files are read first, and then checked on the `threads`
+        let mut selected = Vec::new();
+        // The file names start with the book name if there are several books
+        let file_name = |md_name: String| match multiple_books {
+            true => format!("{}/{}", book.name, md_name),
+            false => md_name,
+        };
+        for entry in &entries {
+            // Check if the current file has the `.md` extension (or `.rst` and `.rs`, if enabled)
+            if !ctx.is_book_file(entry.path()) {
+                continue;
+            }
+            let md_path = entry.path();
+            let md_name = md_path
+                .strip_prefix(&book.dir)
+                .unwrap()
+                .display()
+                .to_string();
+            if !ctx.is_selected(&md_name, &ignored) {
+                skipped_files.push((file_name(md_name), FileSkip::Excluded));
+                continue;
+            }
+            let modified = watch::modified(md_path);
+            if ctx
+                .modified_since
+                .is_some_and(|since| modified.is_none_or(|modified| modified < since))
+            {
+                skipped_files.push((file_name(md_name), FileSkip::Unmodified));
+                continue;
+            }
+            let md_name = file_name(md_name);
+            let md_content = match ctx.read_book_file(md_path) {
+                Ok(md_content) => md_content,
+                Err(e) => {
+                    file_errors += 1;
+                    print_file_error(&format!("Could not read {}: {e}", md_path.display()));
+                    continue;
+                }
+            };
+            let file = WatchedFile {
+                md_path: md_path.to_path_buf(),
+                md_name: md_name.clone(),
+                code_dir: ctx.code_dir.clone(),
+                modified,
+            };
+            selected.push((md_path, md_name, md_content, file));
+        }
+
+        let contents: Vec<_> = selected
+            .iter()
+            .map(|(md_path, _, md_content, _)| (*md_path, md_content.as_str()))
+            .collect();
+        let checks = check_files(&contents, &ctx, config.threads);
+        for ((md_path, md_name, _, file), checked) in selected.into_iter().zip(checks) {
+            let (mut check, elapsed) = match checked {
+                Ok(checked) => checked,
+                Err(message) => {
+                    file_errors += 1;
+                    print_file_error(&message);
+                    continue;
+                }
+            };
+            watched.push(file);
+            // The hashes are updated first, as they don't move the lines of the other snippets
+            if config.update_hashes && !check.stale_hashes.is_empty() {
+                fix::update_hashes(md_path, &check)?;
+                // The updated hashes are not reported as issues
+                let updated: Vec<usize> = check.stale_hashes.iter().map(|(i, _)| *i).collect();
+                check.issues.retain(|issue| {
+                    !updated.contains(&issue.index) || issue.kind != IssueKind::StaleHash
+                });
+                if ctx.format == OutputFormat::Human {
+                    println!("{} ... updated {} hashes", md_name, updated.len());
+                }
+            }
+
+            // The reStructuredText snippets are not rewritten, as their directives aren't fences
+            if config.fix && !rst::is_rst(md_path) && !doc_tests::is_rust_file(md_path) {
+                // The fixed snippets are not reported as diffs
+                let fixed = fixer.fix_file(md_path, &md_name, &check)?;
+                check.diffs.retain(|diff| !fixed.contains(&diff.index));
+                if check.diff == Some(true) && check.diffs.is_empty() {
+                    check.diff = Some(false);
+                }
+                if !fixed.is_empty() && ctx.format == OutputFormat::Human {
+                    println!("{} ... fixed {} snippets", md_name, fixed.len());
+                }
+            }
+
+            // These are file errors, which can't be in the baseline
+            if check.snippet_count_mismatch()
+                || !check.broken_includes.is_empty()
+                || (check.malformed_fails && !check.malformed_headers.is_empty())
+            {
+                final_diff = true;
+            }
+            for index in check.failed_snippets() {
+                let snippet = (md_name.clone(), index);
+                let has_issue = check.issues.iter().any(|issue| issue.index == index);
+                if baseline.contains(&snippet) {
+                    known_failures += 1;
+                } else if has_issue {
+                    snippet_diff = true;
+                } else if check.diffs.iter().any(|diff| diff.index == index) {
+                    new_diffs += 1;
+                } else {
+                    new_missing += 1;
+                }
+                failing.insert(snippet);
+            }
+
+            match ctx.format {
+                OutputFormat::Human => print_file_check(&md_name, &check, &ctx),
+                // Only the markdown files are copied, as the others are converted to be checked
+                OutputFormat::Annotate
+                    if rst::is_rst(md_path) || doc_tests::is_rust_file(md_path) => {}
+                OutputFormat::Annotate => {
+                    let output_path = Path::new(&config.output_dir).join(&md_name);
+                    annotate(md_path, &output_path, &check)?;
+                }
+                OutputFormat::ReviewJson => {
+                    // The review comments are located by the path from the repository root
+                    let md_path = md_path.display().to_string();
+                    let md_path = md_path.strip_prefix("./").unwrap_or(&md_path);
+                    json_entries.extend(review_comments(md_path, &check, ctx.diff_context));
+                }
+                format => json_entries.extend(file_json_entries(&md_name, &check, elapsed, format)),
+            }
+            files += 1;
+            if check.failed() {
+                failed_files += 1;
+            }
+            results.push((md_name, check));
+        }
+
+        if multiple_books && ctx.format == OutputFormat::Human {
+            println!(
+                "\nBook {}: {} files checked, {}\n",
+                book.name.bold(),
+                files,
+                match failed_files {
+                    0 => "all ok".success(),
+                    _ => format!("{failed_files} with diffs or errors")
+                        .bold()
+                        .error(),
+                }
+            );
+        }
+    }
+
+    if new_diffs > config.max_diffs || new_missing > config.max_missing {
+        snippet_diff = true;
+    }
+    let baseline_message = if !config.baseline_write.is_empty() {
+        baseline::write(Path::new(&config.baseline_write), &failing)?;
+        // All the failing snippets are now in the baseline
+        snippet_diff = false;
+        Some(format!(
+            "Wrote {} failing snippets to the baseline {}",
+            failing.len(),
+            config.baseline_write
+        ))
+    } else if !config.baseline.is_empty() {
+        Some(format!(
+            "{known_failures} failing snippets are in the baseline {}",
+            config.baseline
+        ))
+    } else {
+        None
+    };
+    final_diff |= snippet_diff;
+
+    // The baseline snippets of the checked files that don't fail anymore, which are removed from
+    // the baseline with `baseline_prune`
+    let checked: BTreeSet<_> = results.iter().map(|(md_name, _)| md_name).collect();
+    let mut stale: Vec<_> = baseline
+        .iter()
+        .filter(|snippet| checked.contains(&snippet.0) && !failing.contains(*snippet))
+        .collect();
+    if config.baseline_prune && !stale.is_empty() {
+        let pruned = baseline
+            .iter()
+            .filter(|snippet| !stale.contains(snippet))
+            .cloned()
+            .collect();
+        baseline::write(Path::new(&config.baseline), &pruned)?;
+        if ctx.format == OutputFormat::Human {
+            println!(
+                "\nRemoved {} snippets that don't fail anymore from the baseline {}",
+                stale.len(),
+                config.baseline
+            );
+        }
+        stale.clear();
+    }
+    if ctx.strict && !stale.is_empty() && config.baseline_write.is_empty() {
+        final_diff = true;
+    }
+
+    let reports = BookReports::new(&results, &ctx);
+    if reports.fails(ctx.strict) {
+        final_diff = true;
+    }
+
+    // Each crate is checked once, for all the books with the same code directory
+    let mut crate_checks = Vec::new();
+    if config.cargo_check {
+        let code_dirs: BTreeSet<_> = config
+            .books
+            .iter()
+            .map(|book| book.code_dir.as_ref().unwrap_or(&default_code_dir))
+            .collect();
+        let sources: BTreeSet<_> = results
+            .iter()
+            .flat_map(|(_, check)| &check.sources)
+            .collect();
+        for code_dir in code_dirs {
+            let checks = cargo_check::check_crates(
+                Path::new(code_dir),
+                sources.iter().map(|source| source.as_path()),
+            )?;
+            crate_checks.extend(checks);
+        }
+    }
+    if crate_checks.iter().any(|check| check.error.is_some()) {
+        final_diff = true;
+    }
+
+    if !config.fix_report.is_empty() {
+        let path = Path::new(&config.fix_report);
+        fs::write(path, format!("{:#}\n", fixer.report())).map_err(|e| CheckError::Io {
+            path: path.to_path_buf(),
+            message: format!("Could not write the fix report {}: {e}", config.fix_report),
+        })?;
+    }
+    if !config.report.is_empty() {
+        let path = Path::new(&config.report);
+        fs::write(path, format!("{:#}\n", snippets_report(&results))).map_err(|e| {
+            CheckError::Io {
+                path: path.to_path_buf(),
+                message: format!("Could not write the report {}: {e}", config.report),
+            }
+        })?;
+    }
+
+    // The step summary file may have been written by the previous commands of the step
+    if config.github_summary {
+        let summary_path = env::var("GITHUB_STEP_SUMMARY").unwrap_or_default();
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&summary_path)
+            .and_then(|mut file| file.write_all(github_summary(&results, final_diff).as_bytes()))
+            .map_err(|e| CheckError::Io {
+                path: PathBuf::from(&summary_path),
+                message: format!("Could not write the GitHub step summary {summary_path}: {e}"),
+            })?;
+    }
+
+    // The files that could not be checked fail the run as a tooling error, once the others are
+    // reported
+    let exit = |code| match file_errors {
+        0 => code,
+        _ => ExitCode::from(EXIT_TOOLING_ERROR),
+    };
+    if ctx.format == OutputFormat::CountOnly {
+        let failing = failing.len() - known_failures;
+        return Ok(exit(count_exit_code(final_diff, failing)));
+    }
+    if ctx.format == OutputFormat::Quiet {
+        return Ok(exit(exit_code(final_diff)));
+    }
+    if ctx.profile || ctx.verbose {
+        print_read_profile(&ctx);
+    }
+    // The files of the other source backends have no modification time of their own
+    if ctx.freshness && ctx.source.is_none() {
+        print_freshness(&watched, &results);
+    }
+
+    if ctx.format != OutputFormat::Human {
+        print_output(&results, json_entries, &ctx, &config.output_dir);
+        return Ok(exit(exit_code(final_diff)));
+    }
+
+    print_summaries(&results, &skipped_files, &ctx);
+    reports.print(ctx.strict);
+    print_crate_checks(&crate_checks);
+    if let Some(message) = baseline_message {
+        println!("\n{message}");
+    }
+    if !stale.is_empty() && config.baseline_write.is_empty() {
+        let label = format!("{} baseline snippets don't fail anymore:", stale.len());
+        match ctx.strict {
+            true => println!("{}", bold_red(&label)),
+            false => println!("{}", label.bold().warning()),
+        }
+        for (md_name, index) in &stale {
+            println!("  {md_name}#{index}");
+        }
+        println!("Pass `--baseline-prune` to remove them from the baseline");
+    }
+    if config.max_diffs > 0 || config.max_missing > 0 {
+        println!(
+            "\n{new_diffs} diffs (at most {} allowed) and {new_missing} missing paths (at most {} \
+             allowed)",
+            config.max_diffs, config.max_missing
+        );
+    }
+
+    if ctx.only_missing {
+        let snippets: usize = results.iter().map(|(_, check)| check.fences.len()).sum();
+        let missing: usize = results.iter().map(|(_, check)| check.missing.len()).sum();
+        println!("\nOnly the snippet paths were checked: {missing} of {snippets} snippets have a missing path");
+    }
+    if file_errors > 0 {
+        println!("\n{file_errors} files could not be checked");
+        println!("\nFinal status: {}", "ERROR".bold().error());
+    } else if final_diff {
+        println!("\nFinal status: {}", "DIFF FOUND".bold().error());
+    } else {
+        println!("\nFinal status: {}", "OK".success());
+    }
+
+    if config.watch {
+        let checks = results.into_iter().map(|(_, check)| check);
+        watch::run(&mut ctx, watched.into_iter().zip(checks).collect())?;
+    }
+    Ok(exit(exit_code(final_diff)))
+}
+
+// Checks the settings that can't be used together, or that need another one, and sets the output
+// format of the `count_only` and `quiet` modes
+fn check_settings(config: &mut Config) -> Result<(), ConfigError> {
+    if config.count_only {
+        if config.ctx.format != OutputFormat::Human {
+            return Err(ConfigError(
+                "`--count-only` can't be used with another output format".to_string(),
+            ));
+        }
+        if config.watch || config.fix || config.stdin {
+            return Err(ConfigError(
+                "`--count-only` can't be used with `--watch`, `--fix` or `--stdin`".to_string(),
+            ));
+        }
+        config.ctx.format = OutputFormat::CountOnly;
+    }
+    if config.quiet {
+        if config.ctx.format != OutputFormat::Human {
+            return Err(ConfigError(
+                "`--quiet` can't be used with another output format or `--count-only`".to_string(),
+            ));
+        }
+        if config.watch || config.interactive || config.stdin {
+            return Err(ConfigError(
+                "`--quiet` can't be used with `--watch`, `--interactive` or `--stdin`".to_string(),
+            ));
+        }
+        config.ctx.format = OutputFormat::Quiet;
+    }
+    if !config.verify_bundle.is_empty()
+        && (!config.code_archive.is_empty()
+            || config.cargo_check
+            || config.fix
+            || config.update_hashes
+            || config.watch
+            || config.stdin)
+    {
+        return Err(ConfigError(
+            "`--verify-bundle` can't be used with `--code-archive`, `--cargo-check`, `--fix`, \
+             `--update-hashes`, `--watch` or `--stdin`"
+                .to_string(),
+        ));
+    }
+    if config.baseline_prune && config.baseline.is_empty() {
+        return Err(ConfigError(
+            "`--baseline-prune` needs a `--baseline` file".to_string(),
+        ));
+    }
+    if config.cargo_check && !config.code_archive.is_empty() {
+        return Err(ConfigError(
+            "`--cargo-check` needs a code directory, instead of a code archive".to_string(),
+        ));
+    }
+    if config.interactive && !config.fix {
+        return Err(ConfigError(
+            "`--interactive` can only be used with `--fix`".to_string(),
+        ));
+    }
+    check_fence_languages(&config.ctx)?;
+    if config.assume_yes && !config.fix {
+        return Err(ConfigError(
+            "`--assume-yes` can only be used with `--fix`".to_string(),
+        ));
+    }
+    if !config.fix_report.is_empty() && !config.fix {
+        return Err(ConfigError(
+            "`--fix-report` can only be used with `--fix`".to_string(),
+        ));
+    }
+    if config.fix && config.stdin {
+        return Err(ConfigError(
+            "`--fix` can't be used with `--stdin`".to_string(),
+        ));
+    }
+    if config.update_hashes && (config.stdin || config.watch) {
+        return Err(ConfigError(
+            "`--update-hashes` can't be used with `--stdin` or `--watch`".to_string(),
+        ));
+    }
+    if config.github_summary
+        && env::var_os("GITHUB_STEP_SUMMARY").is_none_or(|path| path.is_empty())
+    {
+        return Err(ConfigError(
+            "`--github-summary` needs the `GITHUB_STEP_SUMMARY` environment variable, which is \
+             set in GitHub Actions"
+                .to_string(),
+        ));
+    }
+    if config.ctx.only_missing && (config.fix || config.update_hashes) {
+        return Err(ConfigError(
+            "`--only-missing` can't be used with `--fix` or `--update-hashes`, as the snippets \
+             are not compared"
+                .to_string(),
+        ));
+    }
+    if config.watch && config.stdin {
+        return Err(ConfigError(
+            "`--watch` can't be used with `--stdin`".to_string(),
+        ));
+    }
+    if config.ctx.format == OutputFormat::Lsp && !config.stdin {
+        return Err(ConfigError(
+            "The `lsp` output format locates the diagnostics in a single document, so it can only \
+             be used with `--stdin`"
+                .to_string(),
+        ));
+    }
+    if (config.ctx.format == OutputFormat::Annotate) == config.output_dir.is_empty() {
+        return Err(ConfigError(
+            "The `annotate` output format needs an `--output-dir`, which is only used by it"
+                .to_string(),
+        ));
+    }
+    if config.ctx.format == OutputFormat::Annotate && (config.stdin || config.fix) {
+        return Err(ConfigError(
+            "The `annotate` output format can't be used with `--stdin` or `--fix`".to_string(),
+        ));
+    }
+    if config.ctx.format == OutputFormat::Annotate {
+        check_output_dir(config)?;
+    }
+    if config.watch && config.ctx.format != OutputFormat::Human {
+        return Err(ConfigError(
+            "`--watch` can only be used with the human output format".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Prints the output of the formats other than the human one, once all the files are checked
+fn print_output(
+    results: &[(String, FileCheck)],
+    json_entries: Vec<Json>,
+    ctx: &CheckContext,
+    output_dir: &str,
+) {
+    match ctx.format {
+        OutputFormat::StatusLine => {
+            println!("{}", status_summary(results.iter().map(|(_, check)| check)));
+        }
+        OutputFormat::Annotate => println!("Annotated markdown files written to {output_dir}"),
+        OutputFormat::Tap => {
+            let files = results
+                .iter()
+                .map(|(md_name, check)| (md_name.as_str(), check));
+            for line in tap_report(files, ctx.diff_context) {
+                println!("{line}");
+            }
+        }
+        format => {
+            let json_entries = match format {
+                OutputFormat::CratesJson => {
+                    crate_json_entries(results.iter().map(|(_, check)| check))
+                }
+                _ => json_entries,
+            };
+            match ctx.json_pretty {
+                true => println!("{:#}", Json::Array(json_entries)),
+                false => println!("{}", Json::Array(json_entries)),
+            }
+        }
+    }
+}
+
+// The problems found across the checked files of the books
+struct BookReports {
+    // Snippets with the same content but different paths, which likely have a wrong path
+    conflicting_paths: Vec<(String, Vec<String>)>,
+    order_regressions: Vec<String>,
+    // Snippets showing the same source lines, which may be redundant
+    overlaps: Vec<String>,
+}
+
+impl BookReports {
+    fn new(results: &[(String, FileCheck)], ctx: &CheckContext) -> Self {
+        BookReports {
+            conflicting_paths: conflicting_snippet_paths(results),
+            order_regressions: match ctx.check_global_order {
+                true => source_order_regressions(results),
+                false => Vec::new(),
+            },
+            overlaps: match ctx.check_overlaps {
+                true => overlapping_snippets(results),
+                false => Vec::new(),
+            },
+        }
+    }
+
+    // Whether the check fails, as the order regressions always fail it, and the others only in the
+    // `strict` mode
+    fn fails(&self, strict: bool) -> bool {
+        !self.order_regressions.is_empty()
+            || (strict && !(self.conflicting_paths.is_empty() && self.overlaps.is_empty()))
+    }
+
+    fn print(&self, strict: bool) {
+        if !self.order_regressions.is_empty() {
+            println!("\n{}", bold_red("Snippets out of reading order:"));
+            for regression in &self.order_regressions {
+                println!("  {regression}");
+            }
+        }
+        if !self.overlaps.is_empty() {
+            let label = "Snippets that show the same source lines:";
+            match strict {
+                true => println!("\n{}", bold_red(label)),
+                false => println!("\n{}", label.bold().warning()),
+            }
+            for overlap in &self.overlaps {
+                println!("  {overlap}");
+            }
+        }
+        if !self.conflicting_paths.is_empty() {
+            let label = "Snippets with the same content but different paths:";
+            match strict {
+                true => println!("\n{}", bold_red(label)),
+                false => println!("\n{}", label.bold().warning()),
+            }
+            for (first_line, snippets) in &self.conflicting_paths {
+                println!("  `{first_line}`");
+                for snippet in snippets {
+                    println!("    {snippet}");
+                }
+            }
+        }
+    }
+}
+
+// Prints the summaries of the human output that are enabled, after the checked files
+fn print_summaries(
+    results: &[(String, FileCheck)],
+    skipped_files: &[(String, FileSkip)],
+    ctx: &CheckContext,
+) {
+    if ctx.group_by_source {
+        print_by_source(results);
+    }
+    if ctx.group_by_crate {
+        print_by_crate(results.iter().map(|(_, check)| check));
+    }
+    if ctx.report_largest_diffs > 0 {
+        print_largest_diffs(results, ctx);
+    }
+    if ctx.explain_skips {
+        println!("\nNot checked:");
+        let report = skips_report(skipped_files, results);
+        if report.is_empty() {
+            println!("  nothing, all the files and snippets were checked");
+        }
+        for (reason, skipped) in report {
+            println!("  {} ({}):", reason.bold().warning(), skipped.len());
+            for skipped in skipped {
+                println!("    {skipped}");
+            }
+        }
+    }
+}
+
+// Prints whether each crate with source files in the book compiles
+fn print_crate_checks(crate_checks: &[cargo_check::CrateCheck]) {
+    if crate_checks.is_empty() {
+        return;
+    }
+    println!("\nCrates with source files in the book:");
+    for check in crate_checks {
+        match &check.error {
+            None => println!(
+                "  {} ({} files) ... {}",
+                check.name,
+                check.sources,
+                "ok".success()
+            ),
+            Some(error) => {
+                println!(
+                    "  {} ({} files) ... {}",
+                    check.name,
+                    check.sources,
+                    "DOES NOT COMPILE".bold().error()
+                );
+                for line in error.lines() {
+                    println!("    {line}");
+                }
+            }
+        }
+    }
+}
+
+// The `count_only` exit code, which is the number of failing snippets not in the baseline if the
+// check failed (at least 1, for the file errors), capped at 255
+fn count_exit_code(diff: bool, failing: usize) -> ExitCode {
+    match diff {
+        true => ExitCode::from(failing.clamp(1, u8::MAX as usize) as u8),
+        false => ExitCode::SUCCESS,
+    }
+}
+
+// Returns the entries of a JSON output format for a checked markdown file
+fn file_json_entries(
+    md_name: &str,
+    check: &FileCheck,
+    elapsed: Duration,
+    format: OutputFormat,
+) -> Vec<Json> {
+    match format {
+        OutputFormat::Human
+        | OutputFormat::StatusLine
+        | OutputFormat::CratesJson
+        | OutputFormat::ReviewJson
+        | OutputFormat::Annotate
+        | OutputFormat::Tap
+        | OutputFormat::CountOnly
+        | OutputFormat::Quiet => Vec::new(),
+        OutputFormat::StatsJson => vec![Json::object([
+            ("file", md_name.into()),
+            ("millis", elapsed.as_millis().into()),
+            ("snippets", check.snippets().into()),
+        ])],
+        OutputFormat::MissingJson => check
+            .missing
+            .iter()
+            .map(|missing| {
+                Json::object([
+                    ("md", md_name.into()),
+                    ("index", missing.index.into()),
+                    ("path", missing.path.as_str().into()),
+                ])
+            })
+            .collect(),
+        OutputFormat::LinksJson => check
+            .verified
+            .iter()
+            .map(|snippet| {
+                Json::object([
+                    ("md", md_name.into()),
+                    ("fence_line", snippet.fence_line.into()),
+                    ("source", snippet.path.as_str().into()),
+                    ("start", snippet.start_line.into()),
+                    ("end", snippet.end_line.into()),
+                ])
+            })
+            .collect(),
+        OutputFormat::CoverageJson => vec![Json::object([
+            ("file", md_name.into()),
+            ("snippets", check.snippets().into()),
+            ("verified", check.verified.len().into()),
+        ])],
+        OutputFormat::Lsp => lsp_diagnostics(check),
+    }
+}
+
+// Returns the JSON report of the checked snippets, in the order of the files and then of the
+// snippets, each with its status, resolved source location and message, and the changes from the
+// snippet to the code for the diffs. The files without snippets get a single `no_snippets` entry
+fn snippets_report(results: &[(String, FileCheck)]) -> Json {
+    let mut entries = Vec::new();
+    for (md_name, check) in results {
+        let entry = |index: Option<usize>,
+                     source: Option<&str>,
+                     start: Option<usize>,
+                     status: &str,
+                     message: Option<String>| {
+            Json::object([
+                ("md", md_name.as_str().into()),
+                ("index", index.into()),
+                (
+                    "path",
+                    index.map(|index| check.paths[index].as_str()).into(),
+                ),
+                ("source", source.into()),
+                ("block_start_line", start.into()),
+                ("status", status.into()),
+                ("message", message.into()),
+            ])
+        };
+        if check.diff.is_none() {
+            entries.push(entry(None, None, None, "no_snippets", None));
+            continue;
+        }
+        let mut snippets = BTreeMap::new();
+        for snippet in &check.verified {
+            let source = Some(snippet.source.as_str());
+            let ok = entry(
+                Some(snippet.index),
+                source,
+                Some(snippet.start_line),
+                "ok",
+                None,
+            );
+            snippets.insert(snippet.index, ok);
+        }
+        for diff in &check.diffs {
+            let changes = diff_lines(&diff.snippet, &diff.block)
+                .into_iter()
+                .map(|line| {
+                    let (tag, line) = match line {
+                        DiffLine::Equal(line) => ("equal", line),
+                        DiffLine::Delete(line) => ("delete", line),
+                        DiffLine::Insert(line) => ("insert", line),
+                    };
+                    Json::object([("tag", tag.into()), ("line", line.into())])
+                })
+                .collect();
+            let source = Some(diff.source.as_str());
+            let start = Some(diff.start_line);
+            let mut entry = entry(Some(diff.index), source, start, "diff", diff.hint.clone());
+            if let Json::Object(fields) = &mut entry {
+                fields.push(("changes".to_string(), Json::Array(changes)));
+            }
+            snippets.insert(diff.index, entry);
+        }
+        for missing in &check.missing {
+            let message = format!(
+                "file path does not exist, tried {}",
+                missing.attempted.join(", ")
+            );
+            let index = missing.index;
+            snippets.insert(
+                index,
+                entry(Some(index), None, None, "missing", Some(message)),
+            );
+        }
+        // A snippet with issues fails even if it matches the code, so its issues are reported with
+        // the location of the code it was compared with, if any
+        let mut issues: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+        for issue in &check.issues {
+            issues.entry(issue.index).or_default().push(&issue.message);
+        }
+        for (index, messages) in issues {
+            let compared = check
+                .verified
+                .iter()
+                .map(|snippet| (snippet.index, &snippet.source, snippet.start_line))
+                .chain(
+                    check
+                        .diffs
+                        .iter()
+                        .map(|diff| (diff.index, &diff.source, diff.start_line)),
+                )
+                .find(|(compared, ..)| *compared == index);
+            let source = compared.map(|(_, source, _)| source.as_str());
+            let start = compared.map(|(.., start)| start);
+            let message = Some(messages.join("; "));
+            snippets.insert(index, entry(Some(index), source, start, "issue", message));
+        }
+        entries.extend(snippets.into_values());
+    }
+    Json::Array(entries)
+}
+
+// The LSP diagnostic severities
+const LSP_ERROR: usize = 1;
+const LSP_WARNING: usize = 2;
+
+// Returns the LSP diagnostics of a checked markdown document, covering the lines of each problem
+fn lsp_diagnostics(check: &FileCheck) -> Vec<Json> {
+    let mut diagnostics = Vec::new();
+    let fence = |index: usize| check.fences[index];
+
+    if let Some(expected) = check
+        .expected_snippets
+        .filter(|_| check.snippet_count_mismatch())
+    {
+        let message = format!(
+            "expected {expected} snippets, found {}",
+            check.checked_snippets()
+        );
+        let line = check.snippet_count_line;
+        diagnostics.push(lsp_diagnostic(line, line, LSP_ERROR, message));
+    }
+    for issue in &check.issues {
+        let (start, end) = fence(issue.index);
+        diagnostics.push(lsp_diagnostic(start, end, LSP_ERROR, issue.message.clone()));
+    }
+    for warning in &check.warnings {
+        let (start, end) = fence(warning.index);
+        diagnostics.push(lsp_diagnostic(
+            start,
+            end,
+            LSP_WARNING,
+            warning.message.clone(),
+        ));
+    }
+    for include in &check.broken_includes {
+        let message = format!("{}: {}", include.directive, include.message);
+        diagnostics.push(lsp_diagnostic(
+            include.line,
+            include.line,
+            LSP_ERROR,
+            message,
+        ));
+    }
+    for malformed in &check.malformed_headers {
+        let message = malformed.message();
+        let severity = match check.malformed_fails {
+            true => LSP_ERROR,
+            false => LSP_WARNING,
+        };
+        diagnostics.push(lsp_diagnostic(
+            malformed.line,
+            malformed.line,
+            severity,
+            message,
+        ));
+    }
+    for missing in &check.missing {
+        let mut message = format!("file path does not exist - {}", missing.path);
+        if let Some(suggestion) = &missing.suggestion {
+            message.push_str(&format!(", did you mean `{suggestion}`?"));
+        }
+        let severity = match check.missing_fails {
+            true => LSP_ERROR,
+            false => LSP_WARNING,
+        };
+        // Reported at the path line, after the fence line
+        let line = fence(missing.index).0 + 1;
+        diagnostics.push(lsp_diagnostic(line, line, severity, message));
+    }
+    for diff in &check.diffs {
+        let mut message = format!("differs from {}:{}", diff.path, diff.start_line);
+        if let Some((start, end)) = diff.moved_to {
+            message.push_str(&format!(
+                ", range outdated, content moved to L{start}-L{end}"
+            ));
+        }
+        if let Some(hint) = &diff.hint {
+            message.push_str(&format!(", {hint}"));
+        }
+        // Each run of consecutive differing lines gets a diagnostic, or the whole snippet if they
+        // were not found (as when the snippet only lacks some code lines)
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for &line in &diff.md_lines {
+            match runs.last_mut() {
+                Some((_, end)) if *end + 1 == line => *end = line,
+                _ => runs.push((line, line)),
+            }
+        }
+        if runs.is_empty() {
+            runs.push(fence(diff.index));
+        }
+        for (start, end) in runs {
+            diagnostics.push(lsp_diagnostic(start, end, LSP_ERROR, message.clone()));
+        }
+    }
+    diagnostics
+}
+
+// A diagnostic covering the markdown lines from `start` to `end`, which are counted from 1 while
+// the LSP positions are counted from 0
+fn lsp_diagnostic(start: usize, end: usize, severity: usize, message: String) -> Json {
+    let position =
+        |line: usize| Json::object([("line", line.into()), ("character", 0usize.into())]);
+    Json::object([
+        (
+            "range",
+            Json::object([("start", position(start - 1)), ("end", position(end))]),
+        ),
+        ("severity", severity.into()),
+        ("message", message.into()),
+        ("source", "snippet-checker".into()),
+    ])
+}
+
+// Writes a copy of the markdown file to `output_path`, with a comment above the fence of each checked
+// snippet, like `<!-- snippet ok: floresta-wire/src/p2p_wire/node.rs:120 -->` or
+// `<!-- snippet DIFF -->`. The skipped snippets are not annotated
+fn annotate(md_path: &Path, output_path: &Path, check: &FileCheck) -> Result<(), CheckError> {
+    let content = fs::read_to_string(md_path).map_err(|e| CheckError::read(md_path, e))?;
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| CheckError::Io {
+            path: parent.to_path_buf(),
+            message: format!("Could not create {}: {e}", parent.display()),
+        })?;
+    }
+    fs::write(output_path, annotated_markdown(&content, check))
+        .map_err(|e| CheckError::write(output_path, e))
+}
+
+// Returns the markdown content with the comment of each checked snippet above its fence
+fn annotated_markdown(content: &str, check: &FileCheck) -> String {
+    let mut annotations: BTreeMap<usize, String> = BTreeMap::new();
+    for snippet in &check.verified {
+        let annotation = format!("ok: {}:{}", snippet.path, snippet.start_line);
+        annotations.insert(snippet.index, annotation);
+    }
+    for diff in &check.diffs {
+        annotations.insert(diff.index, "DIFF".to_string());
+    }
+    let errors = check.issues.iter().map(|issue| issue.index);
+    for index in errors.chain(check.missing.iter().map(|missing| missing.index)) {
+        annotations.insert(index, "ERROR".to_string());
+    }
+
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+    // Inserted from the last fence, so that the lines of the previous ones don't move
+    for (index, annotation) in annotations.iter().rev() {
+        let open = check.fences[*index].0 - 1;
+        let fence = &lines[open];
+        // The comment keeps the prefix and line ending of the fence, as in blockquotes
+        let marker = fence.find("```").or_else(|| fence.find("~~~")).unwrap_or(0);
+        let newline = match fence.ends_with("\r\n") {
+            true => "\r\n",
+            false => "\n",
+        };
+        let comment = format!("{}<!-- snippet {annotation} -->{newline}", &fence[..marker]);
+        lines.insert(open, comment);
+    }
+    lines.concat()
+}
+
+// Checks that the annotated copies are not written over the book sources
+fn check_output_dir(config: &Config) -> Result<(), ConfigError> {
+    let output_dir = Path::new(&config.output_dir);
+    // The output directory may not exist yet
+    let output_dir = fs::canonicalize(output_dir)
+        .or_else(|_| std::path::absolute(output_dir))
+        .unwrap_or(output_dir.to_path_buf());
+    for book in &config.books {
+        let book_dir = fs::canonicalize(&book.dir).unwrap_or(PathBuf::from(&book.dir));
+        if output_dir.starts_with(&book_dir) || book_dir.starts_with(&output_dir) {
+            return Err(ConfigError(format!(
+                "The output directory {} can't be inside the book directory {}, nor contain it",
+                config.output_dir, book.dir
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Returns the GitHub review comments of a checked markdown file, each at the opening fence line of
+// a failing snippet. The diffs are unified, with `context` equal lines around each change
+fn review_comments(md_path: &str, check: &FileCheck, context: usize) -> Vec<Json> {
+    let comment = |index: usize, body: String| {
+        Json::object([
+            ("path", md_path.into()),
+            ("line", check.fences[index].0.into()),
+            ("body", body.into()),
+        ])
+    };
+    let mut comments = Vec::new();
+
+    for issue in &check.issues {
+        comments.push(comment(
+            issue.index,
+            format!("Snippet error: {}", issue.message),
+        ));
+    }
+    for missing in check.missing.iter().filter(|_| check.missing_fails) {
+        let mut body = format!("Snippet path does not exist: {}", missing.path);
+        if let Some(suggestion) = &missing.suggestion {
+            body.push_str(&format!(", did you mean `{suggestion}`?"));
+        }
+        comments.push(comment(missing.index, body));
+    }
+    for diff in &check.diffs {
+        let unified = TextDiff::from_lines(&diff.snippet, &diff.block)
+            .unified_diff()
+            .context_radius(context)
+            .missing_newline_hint(false)
+            .to_string();
+        let mut body = format!("Snippet out of sync with {}", diff.path);
+        if let Some((start, end)) = diff.moved_to {
+            body.push_str(&format!(", the content moved to lines {start}-{end}"));
+        }
+        if let Some(hint) = &diff.hint {
+            body.push_str(&format!(", {hint}"));
+        }
+        body.push_str(&format!(":\n```diff\n{}\n```", unified.trim_end()));
+        comments.push(comment(diff.index, body));
+    }
+    // In the order of the snippets, as a reviewer reads them
+    comments.sort_by_key(|comment| match comment {
+        Json::Object(fields) => match fields[1].1 {
+            Json::Number(line) => line as usize,
+            _ => 0,
+        },
+        _ => 0,
+    });
+    comments
+}
+
+// Returns the lines of a Test Anything Protocol report of the checked markdown files, with a test
+// for each snippet, described by its markdown file, index and path, and one for each file error.
+// The failing tests have a YAML block with the error messages, and the unified diff (with `context`
+// equal lines around each change) of the snippets that differ from the code
+fn tap_report<'a>(
+    files: impl Iterator<Item = (&'a str, &'a FileCheck)>,
+    context: usize,
+) -> Vec<String> {
+    let mut tests = Vec::new();
+    for (md_name, check) in files {
+        // The path, whether the snippet fails, the messages and the diff of each snippet
+        let mut snippets: BTreeMap<usize, (&str, bool, Vec<String>, String)> = BTreeMap::new();
+        for snippet in &check.verified {
+            snippets.insert(
+                snippet.index,
+                (&snippet.path, false, Vec::new(), String::new()),
+            );
+        }
+        for diff in &check.diffs {
+            let mut message = format!("differs from {}:{}", diff.path, diff.start_line);
+            if let Some((start, end)) = diff.moved_to {
+                message.push_str(&format!(", the content moved to lines {start}-{end}"));
+            }
+            if let Some(hint) = &diff.hint {
+                message.push_str(&format!(", {hint}"));
+            }
+            let unified = TextDiff::from_lines(&diff.snippet, &diff.block)
+                .unified_diff()
+                .context_radius(context)
+                .missing_newline_hint(false)
+                .to_string();
+            snippets.insert(diff.index, (&diff.path, true, vec![message], unified));
+        }
+        for missing in &check.missing {
+            let message = format!("file path does not exist - {}", missing.path);
+            snippets.insert(
+                missing.index,
+                (
+                    &missing.path,
+                    check.missing_fails,
+                    vec![message],
+                    String::new(),
+                ),
+            );
+        }
+        for issue in &check.issues {
+            let snippet = snippets.entry(issue.index).or_default();
+            snippet.1 = true;
+            snippet.2.push(issue.message.clone());
+        }
+
+        for (index, (path, failed, messages, diff)) in snippets {
+            // Without `#`, which starts a directive in TAP
+            let mut description = format!("{md_name} snippet {index}");
+            if !path.is_empty() {
+                description.push_str(&format!(" ({path})"));
+            }
+            tests.push(match failed {
+                // A missing path that doesn't fail the check is skipped
+                false if !messages.is_empty() => {
+                    (format!("{description} # SKIP {}", messages[0]), None)
+                }
+                false => (description, None),
+                true => (description, Some((messages, diff))),
+            });
+        }
+
+        let mut file_errors = Vec::new();
+        if let (true, Some(expected)) = (check.snippet_count_mismatch(), check.expected_snippets) {
+            file_errors.push(format!(
+                "expected {expected} snippets, found {}",
+                check.checked_snippets()
+            ));
+        }
+        for include in &check.broken_includes {
+            file_errors.push(format!(
+                "line {}: {}: {}",
+                include.line, include.directive, include.message
+            ));
+        }
+        for malformed in check
+            .malformed_headers
+            .iter()
+            .filter(|_| check.malformed_fails)
+        {
+            file_errors.push(format!("line {}: {}", malformed.line, malformed.message()));
+        }
+        if !file_errors.is_empty() {
+            tests.push((md_name.to_string(), Some((file_errors, String::new()))));
+        }
+    }
+
+    let mut lines = vec!["TAP version 13".to_string(), format!("1..{}", tests.len())];
+    for (i, (description, failure)) in tests.into_iter().enumerate() {
+        let Some((messages, diff)) = failure else {
+            lines.push(format!("ok {} - {description}", i + 1));
+            continue;
+        };
+        lines.push(format!("not ok {} - {description}", i + 1));
+        lines.push("  ---".to_string());
+        // The messages are written as JSON strings, which are also YAML strings
+        lines.push(format!("  message: {}", Json::from(messages.join("; "))));
+        if !diff.is_empty() {
+            lines.push("  diff: |".to_string());
+            lines.extend(diff.lines().map(|line| format!("    {line}")));
+        }
+        lines.push("  ...".to_string());
+    }
+    lines
+}
+
+// Checks a single markdown document read from the standard input, as an editor buffer that was
+// not saved. Snippet paths starting with `./` are resolved from the current directory
+fn check_stdin(ctx: &CheckContext, config: &Config) -> Result<bool, CheckError> {
+    let mut md_content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut md_content)
+        .map_err(|e| CheckError::Io {
+            path: PathBuf::from("<stdin>"),
+            message: format!("Could not read the standard input: {e}"),
+        })?;
+    let md_name = match config.stdin_name.as_str() {
+        "" => "<stdin>",
+        name => name,
+    };
+
+    let start = Instant::now();
+    let check = get_md_snippets_diff(md_content, Path::new(""), ctx)?;
+    let elapsed = start.elapsed();
+
+    if ctx.format == OutputFormat::StatusLine {
+        println!("{}", status_summary([&check].into_iter()));
+        return Ok(check.failed());
+    }
+    if ctx.format == OutputFormat::Tap {
+        for line in tap_report([(md_name, &check)].into_iter(), ctx.diff_context) {
+            println!("{line}");
+        }
+        return Ok(check.failed());
+    }
+    if ctx.format != OutputFormat::Human {
+        let entries = match ctx.format {
+            OutputFormat::CratesJson => crate_json_entries([&check].into_iter()),
+            OutputFormat::ReviewJson => review_comments(md_name, &check, ctx.diff_context),
+            format => file_json_entries(md_name, &check, elapsed, format),
+        };
+        let entries = Json::Array(entries);
+        match ctx.json_pretty {
+            true => println!("{entries:#}"),
+            false => println!("{entries}"),
+        }
+        return Ok(check.failed());
+    }
+
+    print_file_check(md_name, &check, ctx);
+    match check.failed() {
+        true => println!("\nFinal status: {}", "DIFF FOUND".bold().error()),
+        false => println!("\nFinal status: {}", "OK".success()),
+    }
+    Ok(check.failed())
+}
+
+// Returns the entries of a book directory, recursively and in file name order, and the errors of
+// those that could not be read (like an unreadable subdirectory), whose files are not checked
+fn walk_book(dir: &str) -> (Vec<walkdir::DirEntry>, Vec<walkdir::Error>) {
+    let (mut entries, mut errors) = (Vec::new(), Vec::new());
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(error) => errors.push(error),
+        }
+    }
+    (entries, errors)
+}
+
+fn print_walk_errors(errors: &[walkdir::Error]) {
+    for error in errors {
+        let path = error
+            .path()
+            .map_or(String::new(), |path| path.display().to_string());
+        let reason = error
+            .io_error()
+            .map_or(error.to_string(), |e| e.to_string());
+        eprintln!(
+            "{} could not read {path}, so its files were not checked: {reason}",
+            bold_red("Warning:")
+        );
+    }
+}
+
+// Prints the error of a file that could not be checked, which doesn't stop the check of the others
+fn print_file_error(message: &str) {
+    eprintln!("{} {message}", bold_red("Error:"));
+}
+
+// Returns the length of the longest selected markdown file name, to align the status lines
+fn status_width(config: &Config, ctx: &CheckContext, multiple_books: bool) -> usize {
+    let mut width = 0;
+    for book in &config.books {
+        // An unreadable ignore file is reported when checking the book
+        let ignored = glob::read_ignore_file(Path::new(&book.dir)).unwrap_or_default();
+        // The unreadable entries are reported when checking the book
+        for entry in walk_book(&book.dir).0 {
+            let path = entry.path();
+            if !ctx.is_book_file(path) {
+                continue;
+            }
+            let md_name = path.strip_prefix(&book.dir).unwrap().display().to_string();
+            if !ctx.is_selected(&md_name, &ignored) {
+                continue;
+            }
+            let book_prefix = match multiple_books {
+                true => book.name.chars().count() + 1,
+                false => 0,
+            };
+            width = width.max(book_prefix + md_name.chars().count());
+        }
+    }
+    width
+}
+
+// Opens the code archive if given, or otherwise checks that there is a code directory for every
+// book
+fn open_code(ctx: &mut CheckContext, config: &Config) -> Result<(), CheckError> {
+    let needs_code_dir = config.books.iter().any(|book| book.code_dir.is_none());
+    if !config.code_archive.is_empty() {
+        let path = Path::new(&config.code_archive);
+        let archive = Archive::open(path).map_err(|message| CheckError::Io {
+            path: path.to_path_buf(),
+            message,
+        })?;
+        ctx.source = Some(Box::new(archive));
+    } else if ctx.source.is_none() && ctx.code_dir.is_empty() && needs_code_dir {
+        let message = concat!(
+            "No code directory configured. Pass `--code-dir <path>`, set the `CODE_DIR` ",
+            "environment variable, or set `code_dir` in snippet-checker.toml, to the path of ",
+            "your Floresta clone (or pass `--code-archive <file>` with a source archive)"
+        );
+        return Err(ConfigError(message.to_string()).into());
+    }
+    Ok(())
+}
+
+// Checks that the snippet headers of every fence language can be read, as they are comments
+fn check_fence_languages(ctx: &CheckContext) -> Result<(), ConfigError> {
+    for language in &ctx.fence_languages {
+        if snippet_comment_prefix(language, &ctx.comment_prefixes).is_err() {
+            return Err(ConfigError(format!(
+                "the fence language `{language}` has no comment prefix for its snippet header, \
+                 add one with `--comment-prefix {language}=<prefix>`"
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Prints a fixture chapter for the lines of a source file, given as a snippet path with a line
+// range (followed by the usual flags). It has the snippet as it would be written in the book, and a
+// broken variant of it, which differs from the code in one line
+fn gen_fixture(mut args: impl Iterator<Item = String>) -> Result<(), CheckError> {
+    let usage = || ConfigError("Usage: snippet_checker gen-fixture <path>:<start>-<end>".into());
+    let snippet_path = args.next().ok_or_else(usage)?;
+    let (path, selector) = parse_snippet_path(&snippet_path).map_err(ConfigError)?;
+    let BlockSelector::Lines(start, end) = selector else {
+        return Err(usage().into());
+    };
+
+    let mut config = Config::load(args)?;
+    let mut ctx = std::mem::replace(&mut config.ctx, CheckContext::new(String::new()));
+    open_code(&mut ctx, &config)?;
+    let code_path =
+        validate_file_path(&ctx, path).map_err(|_| CheckError::MissingPath(path.to_string()))?;
+    let code = String::from_utf8(ctx.read_code(&code_path)?).map_err(|_| {
+        CheckError::Snippet(Issue {
+            kind: IssueKind::UnreadableSource,
+            message: format!("source file is not valid UTF-8: {path}"),
+        })
+    })?;
+    let block = extract_range_block(strip_bom(&code), start, end, Comments::Keep)
+        .filter(|_| start <= end && end <= code.lines().count())
+        .ok_or_else(|| {
+            CheckError::Snippet(Issue {
+                kind: IssueKind::BlockNotFound,
+                message: format!("{path} has no lines {start}-{end}"),
+            })
+        })?;
+    let snippet = dedent(&block);
+
+    // The broken variant misses the second to last line, or changes the only one
+    let mut broken: Vec<String> = snippet.lines().map(str::to_string).collect();
+    match broken.len() {
+        1 => broken[0].push_str(" // broken"),
+        len => {
+            broken.remove(len - 2);
+        }
+    }
+    let broken = broken.join("\n");
+
+    println!("# Fixture for {path}\n");
+    println!("The lines {start}-{end} of the file:\n");
+    println!("```rust\n# // Path: {snippet_path}\n{snippet}\n```\n");
+    println!("The same snippet, which now differs from the code:\n");
+    println!("```rust\n# // Path: {snippet_path}\n{broken}\n```");
+    Ok(())
+}
+
+// Lists the snippets whose path or code block contains the query, given before the usual flags,
+// without comparing them with the code. Returns whether no snippet was found, as in grep
+fn grep(args: impl Iterator<Item = String>) -> Result<bool, CheckError> {
+    let usage = || ConfigError("Usage: snippet_checker grep <query> [--regex] [flags]".into());
+    let mut args: Vec<String> = args.collect();
+    let is_regex = match args.iter().position(|arg| arg == "--regex") {
+        Some(position) => {
+            args.remove(position);
+            true
+        }
+        None => false,
+    };
+    if args.is_empty() {
+        return Err(usage().into());
+    }
+    let query = args.remove(0);
+    let source = match is_regex {
+        true => query.clone(),
+        false => regex::escape(&query),
+    };
+    let pattern =
+        Regex::new(&source).map_err(|e| ConfigError(format!("Invalid regex `{query}`: {e}")))?;
+
+    let mut config = Config::load(args.into_iter())?;
+    config.check_book_dirs()?;
+    config.color_theme.apply();
+    // The context is taken out of the config, which is still used for the other settings
+    let mut ctx = std::mem::replace(&mut config.ctx, CheckContext::new(String::new()));
+    open_code(&mut ctx, &config)?;
+    ctx.extract_only = true;
+
+    let default_code_dir = ctx.code_dir.clone();
+    let multiple_books = config.books.len() > 1;
+    let mut found = 0;
+    for book in &config.books {
+        let code_dir = book.code_dir.as_ref().unwrap_or(&default_code_dir);
+        if *code_dir != ctx.code_dir {
+            ctx.code_dir = code_dir.clone();
+            ctx.source_files = OnceLock::new();
+        }
+        let ignored = glob::read_ignore_file(Path::new(&book.dir))?;
+        let (entries, walk_errors) = walk_book(&book.dir);
+        print_walk_errors(&walk_errors);
+        for entry in entries {
+            let md_path = entry.path();
+            let md_name = md_path
+                .strip_prefix(&book.dir)
+                .unwrap()
+                .display()
+                .to_string();
+            if !ctx.is_book_file(md_path) || !ctx.is_selected(&md_name, &ignored) {
+                continue;
+            }
+            let md_name = match multiple_books {
+                true => format!("{}/{}", book.name, md_name),
+                false => md_name,
+            };
+            let md_content = ctx
+                .read_book_file(md_path)
+                .map_err(|e| CheckError::read(md_path, e))?;
+
+            let md_dir = md_path.parent().unwrap_or(Path::new(""));
+            let check = get_md_snippets_diff(md_content, md_dir, &ctx)?;
+            for snippet in &check.verified {
+                if !pattern.is_match(&snippet.path) && !pattern.is_match(&snippet.block) {
+                    continue;
+                }
+                found += 1;
+                println!(
+                    "{}:{} {}:{}-{}",
+                    md_name,
+                    snippet.fence_line,
+                    snippet.path.bold().warning(),
+                    snippet.start_line,
+                    snippet.end_line
+                );
+            }
+        }
+    }
+    Ok(found == 0)
+}
+
+// Writes a bundle with the book files, and the source files read to check them, followed by the
+// usual flags. Returns whether some snippet failed, as the bundle is written anyway
+fn write_bundle(args: impl Iterator<Item = String>) -> Result<bool, CheckError> {
+    let usage = || ConfigError("Usage: snippet_checker bundle --output <file.tar> [flags]".into());
+    let mut args: Vec<String> = args.collect();
+    let position = args
+        .iter()
+        .position(|arg| arg == "--output")
+        .filter(|position| position + 1 < args.len())
+        .ok_or_else(usage)?;
+    let output = args.remove(position + 1);
+    args.remove(position);
+    if !output.ends_with(".tar") {
+        return Err(usage().into());
+    }
+
+    let mut config = Config::load(args.into_iter())?;
+    config.check_book_dirs()?;
+    config.color_theme.apply();
+    let mut ctx = std::mem::replace(&mut config.ctx, CheckContext::new(String::new()));
+    open_code(&mut ctx, &config)?;
+    if config.books.iter().any(|book| {
+        book.code_dir
+            .as_ref()
+            .is_some_and(|dir| *dir != ctx.code_dir)
+    }) {
+        return Err(ConfigError(
+            "`bundle` needs all the books to use the same code directory".into(),
+        )
+        .into());
+    }
+
+    let mut writer = bundle::BundleWriter::default();
+    let mut failed = false;
+    for book in &config.books {
+        // The book directory name is used if it's not the name of a `[books.<name>]` table
+        let name = Path::new(&book.name)
+            .file_name()
+            .map_or("book".to_string(), |name| {
+                name.to_string_lossy().to_string()
+            });
+        writer.add_book(&name);
+        let ignored = glob::read_ignore_file(Path::new(&book.dir))?;
+        let (entries, walk_errors) = walk_book(&book.dir);
+        print_walk_errors(&walk_errors);
+        for entry in entries {
+            let md_path = entry.path();
+            let md_name = md_path
+                .strip_prefix(&book.dir)
+                .unwrap()
+                .display()
+                .to_string();
+            if !ctx.is_book_file(md_path) || !ctx.is_selected(&md_name, &ignored) {
+                continue;
+            }
+            let read_error = |e| CheckError::read(md_path, e);
+            writer.add_book_file(&name, &md_name, fs::read(md_path).map_err(read_error)?);
+
+            let md_content = ctx.read_book_file(md_path).map_err(read_error)?;
+            let md_dir = md_path.parent().unwrap_or(Path::new(""));
+            let check = get_md_snippets_diff(md_content, md_dir, &ctx)?;
+            failed |= check.failed();
+            for source in &check.sources {
+                // The files next to the markdown files are kept in the book
+                if let Ok(local) = source.strip_prefix(&book.dir) {
+                    let content = ctx
+                        .read_local(source)
+                        .map_err(|e| CheckError::read(source, e))?;
+                    writer.add_book_file(&name, &local.display().to_string(), content);
+                    continue;
+                }
+                let rel = source.strip_prefix(ctx.source_root()).unwrap_or(source);
+                writer.add_source_file(&rel.display().to_string(), ctx.read_code(source)?);
+            }
+        }
+    }
+
+    let (book_files, source_files) = writer.counts();
+    writer.write(Path::new(&output))?;
+    println!("Bundled {book_files} book files and {source_files} source files in {output}");
+    Ok(failed)
+}
+
+// Checks a single snippet, given by the markdown file and snippet index followed by the usual
+// flags, printing each step of the check. Returns whether the snippet failed
+// Updates the snippet paths of a moved source file (or directory, if ending with `/`) in all the book
+// files, keeping their line range or anchors. Only the changes are printed, unless `--write` is set
+fn rename(args: impl Iterator<Item = String>) -> Result<bool, CheckError> {
+    let usage = || {
+        ConfigError("Usage: snippet_checker rename <old path> <new path> [--write] [flags]".into())
+    };
+    let mut args: Vec<String> = args.collect();
+    let write = match args.iter().position(|arg| arg == "--write") {
+        Some(position) => {
+            args.remove(position);
+            true
+        }
+        None => false,
+    };
+    if args.len() < 2 || args[0].starts_with("--") || args[1].starts_with("--") {
+        return Err(usage().into());
+    }
+    let (old, new) = (args.remove(0), args.remove(0));
+    if old.ends_with('/') != new.ends_with('/') {
+        return Err(ConfigError(
+            "Both paths must be directories (ending with `/`) or files".into(),
+        )
+        .into());
+    }
+
+    let config = Config::load(args.into_iter())?;
+    config.check_book_dirs()?;
+    config.color_theme.apply();
+    let ctx = &config.ctx;
+
+    let multiple_books = config.books.len() > 1;
+    let (mut references, mut files) = (0, 0);
+    for book in &config.books {
+        let ignored = glob::read_ignore_file(Path::new(&book.dir))?;
+        let (entries, walk_errors) = walk_book(&book.dir);
+        print_walk_errors(&walk_errors);
+        for entry in entries {
+            let md_path = entry.path();
+            let md_name = md_path
+                .strip_prefix(&book.dir)
+                .unwrap()
+                .display()
+                .to_string();
+            if !ctx.is_book_file(md_path) || !ctx.is_selected(&md_name, &ignored) {
+                continue;
+            }
+            let md_name = match multiple_books {
+                true => format!("{}/{}", book.name, md_name),
+                false => md_name,
+            };
+            // The file is rewritten as it was read, as with `--fix`, so it's not converted
+            let content = fs::read_to_string(md_path).map_err(|e| CheckError::read(md_path, e))?;
+
+            let mut renamed = 0;
+            let mut lines: Vec<String> = Vec::new();
+            for (i, line) in content.split_inclusive('\n').enumerate() {
+                match renamed_path_line(line, &old, &new) {
+                    Some(new_line) => {
+                        renamed += 1;
+                        println!(
+                            "{}:{} {} -> {}",
+                            md_name,
+                            i + 1,
+                            line.trim().error(),
+                            new_line.trim().success()
+                        );
+                        lines.push(new_line);
+                    }
+                    None => lines.push(line.to_string()),
+                }
+            }
+            if renamed == 0 {
+                continue;
+            }
+            if write {
+                fs::write(md_path, lines.concat()).map_err(|e| CheckError::write(md_path, e))?;
+            }
+            references += renamed;
+            files += 1;
+        }
+    }
+
+    match write {
+        true => println!("\nUpdated {references} references in {files} files"),
+        false if references > 0 => println!(
+            "\nWould update {references} references in {files} files, pass --write to apply"
+        ),
+        false => println!("\nNo snippet path references {old}"),
+    }
+    Ok(references == 0)
+}
+
+// Returns the line with the snippet path renamed, if it's a `# // Path:` line of the old path (or a
+// path in the old directory), keeping the line range or anchors and the line ending
+fn renamed_path_line(line: &str, old: &str, new: &str) -> Option<String> {
+    let header = line.find("# // Path: ")? + "# // Path: ".len();
+    let snippet_path = line[header..].trim_end();
+    let (path, _) = parse_snippet_path(snippet_path).ok()?;
+    let renamed = match old.ends_with('/') {
+        true => format!("{new}{}", path.strip_prefix(old)?),
+        false if path == old => new.to_string(),
+        false => return None,
+    };
+    Some(format!(
+        "{}{renamed}{}",
+        &line[..header],
+        &line[header + path.len()..]
+    ))
+}
+
+fn explain(mut args: impl Iterator<Item = String>) -> Result<bool, CheckError> {
+    let usage = || {
+        ConfigError("Usage: snippet_checker explain <markdown file> <snippet index> [flags]".into())
+    };
+    let md_name = args.next().ok_or_else(usage)?;
+    let index: usize = args.next().and_then(|i| i.parse().ok()).ok_or_else(usage)?;
+
+    let mut config = Config::load(args)?;
+    config.check_book_dirs()?;
+    config.color_theme.apply();
+    // The context is taken out of the config, which is still used for the other settings
+    let mut ctx = std::mem::replace(&mut config.ctx, CheckContext::new(String::new()));
+    open_code(&mut ctx, &config)?;
+    ctx.explain = Some(index);
+
+    // The file can be given relative to a book directory, or to the current one
+    let in_book = config
+        .books
+        .iter()
+        .map(|book| (book, Path::new(&book.dir).join(&md_name)))
+        .find(|(_, path)| path.is_file());
+    let md_path = match in_book {
+        Some((book, path)) => {
+            if let Some(code_dir) = &book.code_dir {
+                ctx.code_dir = code_dir.clone();
+            }
+            path
+        }
+        None => PathBuf::from(&md_name),
+    };
+    let md_content = ctx
+        .read_book_file(&md_path)
+        .map_err(|e| CheckError::read(&md_path, e))?;
+
+    let md_dir = md_path.parent().unwrap_or(Path::new(""));
+    let check = get_md_snippets_diff(md_content, md_dir, &ctx)?;
+    if check.diff.is_none() {
+        return Err(CheckError::Failed(format!("{md_name} has no snippets")));
+    }
+    if check.snippets() == 0 && !check.failed() {
+        return Err(CheckError::Failed(format!(
+            "{md_name} has no snippet {index}"
+        )));
+    }
+    print_file_check(&md_name, &check, &ctx);
+    Ok(check.failed())
+}
+
+// The number of source files listed in each part of the read profile
+const PROFILE_FILES: usize = 5;
+
+// Prints the source files with the most read time, and the largest ones, to stderr so that the
+// output formats are not affected
+fn print_read_profile(ctx: &CheckContext) {
+    let source_reads = ctx.source_reads.lock().unwrap();
+    let mut reads: Vec<_> = source_reads.iter().collect();
+    let root_dir = ctx.source_root();
+    let print = |reads: &[(&PathBuf, &SourceReads)]| {
+        for (path, reads) in reads.iter().take(PROFILE_FILES) {
+            eprintln!(
+                "  {} - {} reads, {:.1} KiB, {:.2} ms",
+                relative_path(root_dir, path),
+                reads.count,
+                reads.bytes as f64 / 1024.0,
+                reads.time.as_secs_f64() * 1000.0
+            );
+        }
+    };
+
+    reads.sort_by_key(|(_, reads)| std::cmp::Reverse(reads.time));
+    eprintln!("\nSlowest source files to read:");
+    print(&reads);
+    reads.sort_by_key(|(_, reads)| std::cmp::Reverse(reads.bytes));
+    eprintln!("Largest source files:");
+    print(&reads);
+}
+
+// Prints the verified snippets whose source file was modified after their markdown file, from the
+// largest time gap, as the time each file was last modified. The snippets next to the chapters are
+// left out, as they are usually edited along with them
+fn print_freshness(files: &[WatchedFile], results: &[(String, FileCheck)]) {
+    let mut stale = Vec::new();
+    for (file, (md_name, check)) in files.iter().zip(results) {
+        let Some(md_modified) = file.modified else {
+            continue;
+        };
+        for snippet in &check.verified {
+            let code_dir = Path::new(&file.code_dir);
+            let source_modified = [code_dir.join("crates"), code_dir.to_path_buf()]
+                .iter()
+                .find_map(|dir| watch::modified(&dir.join(&snippet.path)));
+            let gap = source_modified.and_then(|time| time.duration_since(md_modified).ok());
+            if let Some(gap) = gap.filter(|_| !snippet.path.starts_with("./")) {
+                stale.push((gap, md_name, snippet.index, &snippet.path));
+            }
+        }
+    }
+    if stale.is_empty() {
+        return;
+    }
+
+    stale.sort_by_key(|(gap, ..)| std::cmp::Reverse(*gap));
+    eprintln!("\nSnippets whose source file was modified after the chapter:");
+    for (gap, md_name, index, path) in stale {
+        let days = gap.as_secs_f64() / 86400.0;
+        eprintln!("  {md_name}#{index} ⇄ {path} - {days:.1} days after");
+    }
+}
+
+// Prints the file name padded to `width`, followed by the number of matching snippets and the
+// number of each kind of problem
+fn print_status_line(md_name: &str, check: &FileCheck, width: usize) {
+    let name = format!("{md_name:<width$}");
+    if check.diff.is_none() && !check.failed() {
+        println!("{name}  {}", "no snippets".warning());
+        return;
+    }
+
+    let mut status = format!("{name} {:>4} {}", check.verified.len(), "ok".success());
+    let mut errors = check.issues.len()
+        + check.broken_includes.len()
+        + usize::from(check.snippet_count_mismatch());
+    let mut warnings = check.warnings.len();
+    match check.malformed_fails {
+        true => errors += check.malformed_headers.len(),
+        false => warnings += check.malformed_headers.len(),
+    }
+    let counts = [
+        (check.diffs.len(), "diff", true),
+        (errors, "error", true),
+        (check.missing.len(), "missing", check.missing_fails),
+        (warnings, "warning", false),
+    ];
+    for (count, kind, fails) in counts.into_iter().filter(|(count, _, _)| *count > 0) {
+        let count = format!("{count:>3} {kind}");
+        match fails {
+            true => status.push_str(&format!(" {}", count.bold().error())),
+            false => status.push_str(&format!(" {}", count.warning())),
+        }
+    }
+    println!("{status}");
+}
+
+// Returns the `status-line` summary, like `snippet-check: 115/118 ok, 2 diff, 1 missing`. The
+// snippets with errors are only counted if there are some
+fn status_summary<'a>(checks: impl Iterator<Item = &'a FileCheck>) -> String {
+    let (mut ok, mut diffs, mut missing, mut errors) = (0, 0, 0, 0);
+    for check in checks {
+        ok += check.verified.len();
+        diffs += check.diffs.len();
+        missing += check.missing.len();
+        errors += check.issues.len();
+    }
+    let total = ok + diffs + missing + errors;
+    let mut summary = format!("snippet-check: {ok}/{total} ok, {diffs} diff, {missing} missing");
+    if errors > 0 {
+        summary.push_str(&format!(", {errors} error"));
+    }
+    summary
+}
+
+// Checks the markdown files, given with their content, on this many threads (0 for one per CPU
+// core), returning their checks and how long each took. Nothing is printed while checking, so the
+// caller prints the output of each file in their order, without interleaving it
+fn check_files(
+    files: &[(&Path, &str)],
+    ctx: &CheckContext,
+    threads: usize,
+) -> Vec<Result<(FileCheck, Duration), String>> {
+    // A panic (which would be a bug) only fails its file, with an error telling which one
+    let check = |(md_path, md_content): &(&Path, &str)| {
+        let start = Instant::now();
+        let md_dir = md_path.parent().unwrap_or(Path::new(""));
+        let checked = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            get_md_snippets_diff(md_content.to_string(), md_dir, ctx)
+        }));
+        match checked {
+            Ok(checked) => checked
+                .map(|check| (check, start.elapsed()))
+                .map_err(|e| e.to_string()),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(format!(
+                    "Could not check {}, as the checker panicked: {message}",
+                    md_path.display()
+                ))
+            }
+        }
+    };
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    };
+    if threads <= 1 || files.len() <= 1 {
+        return files.iter().map(check).collect();
+    }
+
+    // Each thread takes the next unchecked file, and the checks are then sorted back in order
+    let next = AtomicUsize::new(0);
+    let mut checked: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(files.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut checked = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(index) else {
+                            return checked;
+                        };
+                        checked.push((index, check(file)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+    checked.sort_by_key(|(index, _)| *index);
+    checked.into_iter().map(|(_, check)| check).collect()
+}
+
+// Returns the markdown summary of the check for GitHub Actions, with the status and a table of the
+// snippets of each file. The files without snippets are left out, unless they failed
+fn github_summary(results: &[(String, FileCheck)], diff: bool) -> String {
+    let status = match diff {
+        true => "DIFF FOUND",
+        false => "OK",
+    };
+    let mut lines = vec![
+        format!("## Snippet check: {status}"),
+        String::new(),
+        "| File | Snippets | OK | Diff | Missing |".to_string(),
+        "| --- | ---: | ---: | ---: | ---: |".to_string(),
+    ];
+    let mut totals = [0; 4];
+    for (md_name, check) in results {
+        let counts = [
+            check.snippets() + check.missing.len(),
+            check.verified.len(),
+            check.diffs.len(),
+            check.missing.len(),
+        ];
+        if counts[0] == 0 && !check.failed() {
+            continue;
+        }
+        for (total, count) in totals.iter_mut().zip(counts) {
+            *total += count;
+        }
+        let [snippets, ok, diffs, missing] = counts;
+        let md_name = md_name.replace('|', "\\|");
+        lines.push(format!(
+            "| {md_name} | {snippets} | {ok} | {diffs} | {missing} |"
+        ));
+    }
+    let [snippets, ok, diffs, missing] = totals;
+    lines.push(format!(
+        "| **Total** | {snippets} | {ok} | {diffs} | {missing} |"
+    ));
+    lines.join("\n") + "\n"
+}
+
+// Returns a message for each snippet that shows earlier lines of a source file than the snippet
+// before it, in the book reading order (the order of the markdown files, then of their snippets)
+fn source_order_regressions(results: &[(String, FileCheck)]) -> Vec<String> {
+    // The last snippet of each source file, and its first line
+    let mut previous: BTreeMap<&str, (&str, usize, usize)> = BTreeMap::new();
+    let mut regressions = Vec::new();
+
+    for (md_name, check) in results {
+        let mut snippets: Vec<_> = check
+            .verified
+            .iter()
+            .map(|snippet| (snippet.index, snippet.path.as_str(), snippet.start_line))
+            .chain(
+                check
+                    .diffs
+                    .iter()
+                    .map(|diff| (diff.index, diff.path.as_str(), diff.start_line)),
+            )
+            .collect();
+        snippets.sort_unstable();
+
+        for (index, path, start_line) in snippets {
+            if let Some((previous_md, previous_index, previous_line)) = previous.get(path) {
+                if start_line < *previous_line {
+                    regressions.push(format!(
+                        "{md_name}#{index} shows {path}:{start_line}, before line \
+                         {previous_line} shown by {previous_md}#{previous_index}"
+                    ));
+                }
+            }
+            previous.insert(path, (md_name, index, start_line));
+        }
+    }
+    regressions
+}
+
+// Why a markdown file of the book was not checked
+#[derive(Clone, Copy, PartialEq)]
+enum FileSkip {
+    // Not selected by the `include` and `exclude` patterns, or ignored by the book ignore file
+    Excluded,
+    // Not modified since the `--since` time
+    Unmodified,
+}
+
+// Returns what was not checked by category, each with its files, snippets or Rust fences, leaving
+// out the empty categories
+fn skips_report(
+    skipped_files: &[(String, FileSkip)],
+    results: &[(String, FileCheck)],
+) -> Vec<(&'static str, Vec<String>)> {
+    let files = |skip| {
+        skipped_files
+            .iter()
+            .filter(|(_, file_skip)| *file_skip == skip)
+            .map(|(md_name, _)| md_name.clone())
+            .collect()
+    };
+    let snippets = |f: fn(&Skipped) -> Option<String>| {
+        results
+            .iter()
+            .flat_map(|(md_name, check)| {
+                check
+                    .skipped
+                    .iter()
+                    .filter_map(f)
+                    .map(move |skipped| format!("{md_name}{skipped}"))
+            })
+            .collect()
+    };
+
+    let report: [(_, Vec<String>); 6] = [
+        (
+            "Excluded by the include, exclude or ignore file patterns",
+            files(FileSkip::Excluded),
+        ),
+        (
+            "Not modified since the --since time",
+            files(FileSkip::Unmodified),
+        ),
+        (
+            "Files without snippets",
+            results
+                .iter()
+                .filter(|(_, check)| check.diff.is_none())
+                .map(|(md_name, _)| md_name.clone())
+                .collect(),
+        ),
+        (
+            "Skipped by a `skip` directive or fence attribute",
+            snippets(|skipped| match skipped {
+                Skipped::Directive { index, path } => Some(format!("#{index} ({path})")),
+                _ => None,
+            }),
+        ),
+        (
+            "Missing paths skipped with --missing-paths=skip",
+            snippets(|skipped| match skipped {
+                Skipped::MissingPath { index, path } => Some(format!("#{index} ({path})")),
+                _ => None,
+            }),
+        ),
+        (
+            "Rust fences without a snippet header",
+            snippets(|skipped| match skipped {
+                Skipped::NoHeader { line } => Some(format!(":{line}")),
+                _ => None,
+            }),
+        ),
+    ];
+    report
+        .into_iter()
+        .filter(|(_, skipped)| !skipped.is_empty())
+        .collect()
+}
+
+// Returns a message for each pair of matching snippets whose line ranges of the same source file
+// overlap, by source file and then first line. The snippets of a directory are not compared, as
+// their lines are of several files
+fn overlapping_snippets(results: &[(String, FileCheck)]) -> Vec<String> {
+    let mut snippets: Vec<_> = results
+        .iter()
+        .flat_map(|(md_name, check)| {
+            check.verified.iter().map(move |snippet| {
+                (
+                    snippet.path.as_str(),
+                    snippet.start_line,
+                    snippet.end_line,
+                    md_name.as_str(),
+                    snippet.index,
+                )
+            })
+        })
+        .filter(|(path, ..)| !path.ends_with('/'))
+        .collect();
+    snippets.sort_unstable();
+
+    let mut overlaps = Vec::new();
+    for (i, (path, start, end, md_name, index)) in snippets.iter().enumerate() {
+        let later = snippets[i + 1..]
+            .iter()
+            .take_while(|(other_path, other_start, ..)| other_path == path && other_start <= end);
+        for (_, other_start, other_end, other_md, other_index) in later {
+            overlaps.push(format!(
+                "{path}: {md_name}#{index} (lines {start}-{end}) and {other_md}#{other_index} \
+                 (lines {other_start}-{other_end})"
+            ));
+        }
+    }
+    overlaps
+}
+
+// Returns an issue for each snippet of a markdown file that shows earlier lines of a source file
+// than the previous snippet of that file, as the snippets of a split function should be in order
+fn intra_file_order_issues(
+    verified: &[VerifiedSnippet],
+    diffs: &[SnippetDiff],
+) -> Vec<SnippetIssue> {
+    let mut snippets: Vec<_> = verified
+        .iter()
+        .map(|snippet| (snippet.index, snippet.path.as_str(), snippet.start_line))
+        .chain(
+            diffs
+                .iter()
+                .map(|diff| (diff.index, diff.path.as_str(), diff.start_line)),
+        )
+        .collect();
+    snippets.sort_unstable();
+
+    // The last snippet of each source file, and its first line
+    let mut previous: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut issues = Vec::new();
+    for (index, path, start_line) in snippets {
+        if let Some((previous_index, previous_line)) = previous.get(path) {
+            if start_line < *previous_line {
+                let message = format!(
+                    "shows {path}:{start_line}, before line {previous_line} shown by snippet \
+                     {previous_index}, so the snippets are out of the source order"
+                );
+                issues.push(SnippetIssue {
+                    index,
+                    kind: IssueKind::Other,
+                    message,
+                });
+            }
+        }
+        previous.insert(path, (index, start_line));
+    }
+    issues
+}
+
+// Returns the groups of snippets with the same content (as compared with the code) that reference
+// more than one source path, as one of them is likely wrong. Each group has the first snippet line,
+// and each of its snippets with the path it shows, in the book reading order
+fn conflicting_snippet_paths(results: &[(String, FileCheck)]) -> Vec<(String, Vec<String>)> {
+    let mut groups: BTreeMap<&str, Vec<(&str, usize, &str)>> = BTreeMap::new();
+    for (md_name, check) in results {
+        let snippets = check
+            .verified
+            .iter()
+            .map(|snippet| {
+                (
+                    snippet.index,
+                    snippet.path.as_str(),
+                    snippet.snippet.as_str(),
+                )
+            })
+            .chain(
+                check
+                    .diffs
+                    .iter()
+                    .map(|diff| (diff.index, diff.path.as_str(), diff.snippet.as_str())),
+            );
+        for (index, path, snippet) in snippets {
+            if !snippet.trim().is_empty() {
+                groups
+                    .entry(snippet)
+                    .or_default()
+                    .push((md_name, index, path));
+            }
+        }
+    }
+
+    let mut conflicts: Vec<_> = groups
+        .into_iter()
+        .filter(|(_, snippets)| snippets.iter().any(|(_, _, path)| *path != snippets[0].2))
+        .map(|(snippet, mut snippets)| {
+            snippets.sort_unstable();
+            let first_line = snippet.lines().find(|line| !line.trim().is_empty());
+            let snippets: Vec<String> = snippets
+                .into_iter()
+                .map(|(md_name, index, path)| format!("{md_name}#{index} shows {path}"))
+                .collect();
+            (first_line.unwrap_or_default().trim().to_string(), snippets)
+        })
+        .collect();
+    conflicts.sort_by(|a, b| a.1.cmp(&b.1));
+    conflicts
+}
+
+// Prints, for each referenced source file, how many snippets and chapters show it, and which of
+// those snippets are outdated
+// Returns the crate of a snippet path, which is its first directory (after `crates/`, if written).
+// The paths next to the chapters have none
+fn snippet_crate(path: &str) -> Option<&str> {
+    if path.starts_with("./") {
+        return None;
+    }
+    let path = path.strip_prefix("crates/").unwrap_or(path);
+    path.split_once('/').map(|(name, _)| name)
+}
+
+// Returns the verified, differing and missing snippets of each crate
+fn crate_counts<'a>(checks: impl Iterator<Item = &'a FileCheck>) -> BTreeMap<&'a str, [usize; 3]> {
+    let mut counts: BTreeMap<&str, [usize; 3]> = BTreeMap::new();
+    for check in checks {
+        let paths = [
+            check
+                .verified
+                .iter()
+                .map(|s| s.path.as_str())
+                .collect::<Vec<_>>(),
+            check.diffs.iter().map(|diff| diff.path.as_str()).collect(),
+            check.missing.iter().map(|m| m.path.as_str()).collect(),
+        ];
+        for (kind, paths) in paths.iter().enumerate() {
+            for name in paths.iter().filter_map(|path| snippet_crate(path)) {
+                counts.entry(name).or_default()[kind] += 1;
+            }
+        }
+    }
+    counts
+}
+
+fn crate_json_entries<'a>(checks: impl Iterator<Item = &'a FileCheck>) -> Vec<Json> {
+    crate_counts(checks)
+        .into_iter()
+        .map(|(name, [ok, diffs, missing])| {
+            Json::object([
+                ("crate", name.into()),
+                ("ok", ok.into()),
+                ("diff", diffs.into()),
+                ("missing", missing.into()),
+            ])
+        })
+        .collect()
+}
+
+// Prints the snippet counts of each crate, to see which parts of the code drifted from the book
+fn print_by_crate<'a>(checks: impl Iterator<Item = &'a FileCheck>) {
+    println!("\nSnippets by crate:");
+    for (name, [ok, diffs, missing]) in crate_counts(checks) {
+        let mut counts = format!("{ok} ok").success().to_string();
+        for (count, kind) in [(diffs, "diff"), (missing, "missing")] {
+            if count > 0 {
+                counts.push_str(&format!(", {}", format!("{count} {kind}").error()));
+            }
+        }
+        println!("  {}: {counts}", name.bold().warning());
+    }
+}
+
+// Returns the diffs with the most changed (inserted or deleted) lines, at most `count` of them,
+// with their markdown file and changed lines. The diffs with as many changes keep the book order
+fn largest_diffs(
+    results: &[(String, FileCheck)],
+    count: usize,
+) -> Vec<(&str, &SnippetDiff, usize)> {
+    let mut diffs: Vec<_> = results
+        .iter()
+        .flat_map(|(md_name, check)| check.diffs.iter().map(move |diff| (md_name.as_str(), diff)))
+        .map(|(md_name, diff)| {
+            let changed = TextDiff::from_lines(&diff.snippet, &diff.block)
+                .iter_all_changes()
+                .filter(|change| change.tag() != ChangeTag::Equal)
+                .count();
+            (md_name, diff, changed)
+        })
+        .collect();
+    diffs.sort_by_key(|(_, _, changed)| std::cmp::Reverse(*changed));
+    diffs.truncate(count);
+    diffs
+}
+
+fn print_largest_diffs(results: &[(String, FileCheck)], ctx: &CheckContext) {
+    let diffs = largest_diffs(results, ctx.report_largest_diffs);
+    if diffs.is_empty() {
+        return;
+    }
+    println!("\nLargest diffs:");
+    for (rank, (md_name, diff, changed)) in diffs.into_iter().enumerate() {
+        println!(
+            "\n{}. {md_name}#{} ({}:{}), {changed} changed lines\n",
+            rank + 1,
+            diff.index.to_string().bold().warning(),
+            diff.path.bold().warning(),
+            diff.start_line
+        );
+        print_diff(
+            &diff.snippet,
+            &diff.block,
+            ctx.detect_moves,
+            &diff.source_lines,
+        );
+    }
+}
+
+fn print_by_source(results: &[(String, FileCheck)]) {
+    // The (markdown file, snippet index, outdated) references to each source file
+    let mut references: BTreeMap<&str, Vec<(&str, usize, bool)>> = BTreeMap::new();
+
+    for (md_name, check) in results {
+        for snippet in &check.verified {
+            references
+                .entry(&snippet.path)
+                .or_default()
+                .push((md_name, snippet.index, false));
+        }
+        for diff in &check.diffs {
+            references
+                .entry(&diff.path)
+                .or_default()
+                .push((md_name, diff.index, true));
+        }
+    }
+
+    println!("\nSnippets by source file:");
+    for (path, refs) in references {
+        let chapters: BTreeSet<_> = refs.iter().map(|(md_name, _, _)| md_name).collect();
+        let stale: Vec<_> = refs
+            .iter()
+            .filter(|(_, _, outdated)| *outdated)
+            .map(|(md_name, index, _)| format!("{md_name}#{index}"))
+            .collect();
+
+        let status = if stale.is_empty() {
+            "all ok".success()
+        } else {
+            format!("{} stale: {}", stale.len(), stale.join(", ")).error()
+        };
+        println!(
+            "  {}: {} snippets in {} chapters, {}",
+            path.bold().warning(),
+            refs.len(),
+            chapters.len(),
+            status,
+        );
+    }
+}
+
+// Shows the diff in the external diff tool, if any, when the diffs are printed to a terminal
+fn open_diff_tool(md_name: &str, diff: &SnippetDiff, ctx: &CheckContext) {
+    let mut diff_tool = ctx.diff_tool.lock().unwrap();
+    if diff_tool.is_empty() || !io::stdout().is_terminal() {
+        return;
+    }
+    if let Err(e) = diff_tool::open(&diff_tool, md_name, diff) {
+        eprintln!("{}", bold_red(&e));
+        diff_tool.clear();
+    }
+}
+
+// Prints the status of a checked markdown file, followed by the diff of each outdated snippet
+fn print_file_check(md_name: &str, check: &FileCheck, ctx: &CheckContext) {
+    if ctx.compact_status {
+        print_status_line(md_name, check, ctx.status_width);
+        if check.failed() || !check.warnings.is_empty() || !check.missing.is_empty() {
+            println!();
+        }
+    }
+    match check.diff {
+        _ if ctx.compact_status => {}
+        Some(true) => print!("{} ... {}\n\n", md_name, "DIFF".bold().error()),
+        _ if check.failed() => print!("{} ... {}\n\n", md_name, "ERROR".bold().error()),
+        Some(false) => println!("{} ... {}", md_name, "ok".success()),
+        None => println!("{} ... {}", md_name, "no snippets".warning()),
+    }
+
+    if let Some(expected) = check
+        .expected_snippets
+        .filter(|_| check.snippet_count_mismatch())
+    {
+        println!(
+            "{} expected {} snippets, found {}\n",
+            bold_red("Snippet count:"),
+            expected,
+            check.checked_snippets()
+        );
+    }
+
+    for issue in &check.issues {
+        println!(
+            "{} {}\n",
+            bold_red(&format!("Snippet {}:", issue.index)),
+            issue.message
+        );
+    }
+
+    for warning in &check.warnings {
+        println!(
+            "{} {}\n",
+            format!("Snippet {} warning:", warning.index)
+                .bold()
+                .warning(),
+            warning.message
+        );
+    }
+
+    for include in &check.broken_includes {
+        println!(
+            "{} {}: {}\n",
+            bold_red(&format!("Line {}:", include.line)),
+            include.directive,
+            include.message
+        );
+    }
+
+    for malformed in &check.malformed_headers {
+        let label = match check.malformed_fails {
+            true => bold_red(&format!("Line {}:", malformed.line)),
+            false => format!("Line {} warning:", malformed.line).bold().warning(),
+        };
+        println!("{label} {}\n", malformed.message());
+    }
+
+    for missing in &check.missing {
+        let label = match check.missing_fails {
+            true => bold_red(&format!("Snippet {}:", missing.index)),
+            false => format!("Snippet {} warning:", missing.index)
+                .bold()
+                .warning(),
+        };
+        println!(
+            "{} file path does not exist - {}\nTried: {}",
+            label,
+            missing.path,
+            missing.attempted.join(", "),
+        );
+        if let Some(suggestion) = &missing.suggestion {
+            println!("Did you mean `{suggestion}`?");
+        }
+        println!();
+    }
+
+    for diff in &check.diffs {
+        println!("Snippet index: {}", diff.index.to_string().bold().warning());
+        if ctx.only_changed_lines {
+            print_changed_lines(diff);
+            continue;
+        }
+        println!(
+            "Code: {}:{}",
+            diff.path.bold().warning(),
+            diff.start_line.to_string().bold()
+        );
+
+        if let Some((start, end)) = diff.moved_to {
+            println!(
+                "{}",
+                format!("Hint: range outdated, content moved to L{start}-L{end}").warning()
+            );
+        }
+        if let Some(hint) = &diff.hint {
+            println!("{}", format!("Hint: {hint}").warning());
+        }
+
+        // The source lines before the code show where the block was found in the file
+        if !diff.source_context.is_empty() {
+            let width = diff.start_line.to_string().len();
+            println!();
+            for (line_number, line) in &diff.source_context {
+                println!("{}", format!("{line_number:>width$} | {line}").plain());
+            }
+        }
+
+        println!();
+        print_diff(
+            &diff.snippet,
+            &diff.block,
+            ctx.detect_moves,
+            &diff.source_lines,
+        );
+        if !diff.raw.is_empty() {
+            println!("{}", format!("Raw snippet in {md_name}:").warning());
+            for line in &diff.raw {
+                println!("{}", line.plain());
+            }
+            println!();
+        }
+        open_diff_tool(md_name, diff, ctx);
+    }
+
+    if ctx.verbose {
+        for snippet in &check.verified {
+            println!(
+                "  ok: {}#{} ⇄ {}:{}-{} ({} lines)",
+                md_name,
+                snippet.index,
+                snippet.path,
+                snippet.start_line,
+                snippet.end_line,
+                snippet.lines,
+            );
+        }
+        for note in &check.notes {
+            println!("  note: {}#{}: {}", md_name, note.index, note.message);
+        }
+    }
+}
+
+// Compares each markdown snippet with the floresta code, collecting the differences found. Snippet
+// paths starting with `./` are relative to `md_dir`, the markdown file directory, instead
+fn get_md_snippets_diff(
+    md_file: String,
+    md_dir: &Path,
+    ctx: &CheckContext,
+) -> Result<FileCheck, CheckError> {
+    // Fences can use backticks or tildes, and each alternative captures the fence info string after
+    // `rust`, the snippet path (or manifest reference) line and the snippet
+    let rust_code_regex = Regex::new(concat!(
+        r"(?s)```rust((?:[ ,][^\n]*)?)\n# // ((?:Path|Ref): .*?)\n(.*?)\n```",
+        r"|~~~rust((?:[ ,][^\n]*)?)\n# // ((?:Path|Ref): .*?)\n(.*?)\n~~~",
+    ))
+    .unwrap();
+
+    // Only checked when all the snippets are, and compared
+    let snippet_count = match ctx.explain {
+        _ if ctx.only_missing => None,
+        Some(_) => None,
+        None => Regex::new(r"<!--\s*snippets:\s*(\d+)\s*-->")
+            .unwrap()
+            .captures(&md_file),
+    };
+    let expected_snippets = snippet_count.as_ref().and_then(|caps| caps[1].parse().ok());
+    let snippet_count_line =
+        snippet_count.map_or(1, |caps| fence_line(&md_file, caps.get(0).unwrap().start()));
+
+    let broken_includes = match ctx.check_includes && ctx.explain.is_none() {
+        true => check_includes(&md_file, md_dir, ctx),
+        false => Vec::new(),
+    };
+
+    // Track if there is any difference between the code and the book snippets
+    let mut diff = None;
+    let mut verified = Vec::new();
+    let mut diffs = Vec::new();
+    let mut issues = Vec::new();
+    let mut missing = Vec::new();
+    let mut warnings = Vec::new();
+    let mut notes = Vec::new();
+    let mut sources = BTreeSet::new();
+    let mut fences = Vec::new();
+    let mut paths = Vec::new();
+    let mut stale_hashes = Vec::new();
+    let mut skipped = Vec::new();
+
+    // The lines as written, as the fences are found once the prefixes below are removed
+    let raw_lines: Vec<&str> = md_file.lines().collect();
+
+    // Strip '> ' prefix from content, as some snippets are inside blockquotes (where the empty lines
+    // are usually a bare `>`), and the indentation of the fences inside list items
+    let md_file = strip_bom(&md_file)
+        .lines()
+        .map(|line| match line {
+            ">" => "",
+            line => line.strip_prefix("> ").unwrap_or(line),
+        })
+        .collect::<Vec<_>>();
+    let md_file = unindent_fences(&md_file);
+    let md_file = match ctx.fence_languages.is_empty() {
+        true => md_file,
+        false => language_fences(&md_file, ctx),
+    };
+    // The fences without a header are only skips if they don't fail the check
+    if ctx.explain_skips && !ctx.fail_on_unparsed_fence {
+        skipped.extend(
+            malformed_headers(&md_file, true)
+                .into_iter()
+                .filter(|header| header.unparsed)
+                .map(|header| Skipped::NoHeader { line: header.line }),
+        );
+    }
+    let malformed_headers = match ctx.explain {
+        Some(_) => Vec::new(),
+        None => malformed_headers(&md_file, ctx.fail_on_unparsed_fence),
+    };
+
+    for (i, caps) in rust_code_regex.captures_iter(&md_file).enumerate() {
+        let group = |i| caps.get(i).or_else(|| caps.get(i + 3)).unwrap().as_str();
+        let fence = caps.get(0).unwrap();
+        fences.push((
+            fence_line(&md_file, fence.start()),
+            fence_line(&md_file, fence.end()),
+        ));
+        let written_path = match group(2).split_once(": ") {
+            Some(("Ref", id)) => ctx
+                .snippet_refs
+                .get(id.trim())
+                .map_or(id.trim(), String::as_str),
+            _ => &group(2)["Path: ".len()..],
+        };
+        let written_path = parse_snippet_path(written_path).map_or(written_path, |(path, _)| path);
+        paths.push(written_path.to_string());
+        if i == 0 {
+            diff = Some(false);
+        }
+        if ctx.explain.is_some_and(|index| index != i) {
+            continue;
+        }
+        let attributes = match snippet_attributes(group(1)) {
+            Ok(attributes) => attributes,
+            Err(message) => {
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::MalformedHeader,
+                    message,
+                });
+                continue;
+            }
+        };
+        // The directive lines after the path line, which take precedence over the fence attributes
+        // and the path line range or anchors
+        let directives = match snippet_directives(group(3)) {
+            Ok(directives) => directives,
+            Err(message) => {
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::MalformedHeader,
+                    message,
+                });
+                continue;
+            }
+        };
+        if directives.skip.unwrap_or(attributes.skip) {
+            let path = group(2).split_once(": ").map_or(group(2), |(_, path)| path);
+            skipped.push(Skipped::Directive {
+                index: i,
+                path: path.to_string(),
+            });
+            continue;
+        }
+        let content = match directives.count {
+            0 => group(3),
+            count => group(3).splitn(count + 1, '\n').nth(count).unwrap_or(""),
+        };
+        let explain = ctx.explain.is_some();
+        if explain {
+            explain_step("Fence content", caps.get(0).unwrap().as_str());
+        }
+
+        // A `Ref:` line gives the snippet path by its ID in the snippet manifest
+        let snippet_path = match group(2).split_once(": ") {
+            Some(("Ref", id)) => match ctx.snippet_refs.get(id.trim()) {
+                Some(snippet_path) => snippet_path.as_str(),
+                None => {
+                    let message =
+                        format!("unknown snippet ID `{}`, not in the manifest", id.trim());
+                    issues.push(SnippetIssue {
+                        index: i,
+                        kind: IssueKind::MalformedHeader,
+                        message,
+                    });
+                    continue;
+                }
+            },
+            _ => &group(2)["Path: ".len()..],
+        };
+        let (path, selector) = match parse_snippet_path(snippet_path) {
+            Ok(parsed) => parsed,
+            Err(message) => {
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::MalformedHeader,
+                    message,
+                });
+                continue;
+            }
+        };
+        let selector = match (directives.lines, directives.anchors) {
+            (Some((start, end)), _) => BlockSelector::Lines(start.max(1), end),
+            (None, Some(anchors)) => BlockSelector::Anchors(anchors),
+            (None, None) => selector,
+        };
+        if directives.occurrence.is_some() && !matches!(selector, BlockSelector::Search) {
+            let message = "the `occurrence` directive only applies to snippets found by their \
+                           first line, not by a line range, anchors or a module"
+                .to_string();
+            issues.push(SnippetIssue {
+                index: i,
+                kind: IssueKind::MalformedHeader,
+                message,
+            });
+            continue;
+        }
+
+        let language = directives
+            .lang
+            .unwrap_or_else(|| snippet_language(group(1)));
+        let keep_comments = match directives.skip_comments {
+            Some(skip_comments) => !skip_comments,
+            None => ctx.keep_comments || attributes.keep_comments,
+        };
+        let comments = match snippet_comment_prefix(language, &ctx.comment_prefixes) {
+            _ if keep_comments => Comments::Keep,
+            Ok(prefix) => Comments::Strip(prefix),
+            Err(message) => {
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::MalformedHeader,
+                    message,
+                });
+                continue;
+            }
+        };
+
+        if escapes_code_dir(path) {
+            issues.push(SnippetIssue {
+                index: i,
+                kind: IssueKind::UnsafePath,
+                message: format!(
+                    "refusing to read {path}, as snippet paths must stay inside the code directory"
+                ),
+            });
+            continue;
+        }
+
+        let local = path.starts_with("./");
+        let directory = path.ends_with('/');
+        let path = match ctx.allow_glob_paths && !local && !directory {
+            true => match resolve_glob_path(ctx, path) {
+                Ok(resolved) => resolved.unwrap_or(path),
+                Err(message) => {
+                    issues.push(SnippetIssue {
+                        index: i,
+                        kind: IssueKind::Ambiguous,
+                        message,
+                    });
+                    continue;
+                }
+            },
+            false => path,
+        };
+
+        // Check that the path retrieved from the mdbook snippet exists
+        let (code_path, code_content) =
+            match resolve_source(ctx, md_dir, i, path, &mut sources, &mut notes)? {
+                SnippetSource::Found(code_path, code_content) => (code_path, code_content),
+                SnippetSource::Exists => continue,
+                SnippetSource::Missing(path) => {
+                    missing.push(path);
+                    continue;
+                }
+                SnippetSource::Skipped(skip) => {
+                    skipped.push(skip);
+                    continue;
+                }
+                SnippetSource::Issue(kind, message) => {
+                    issues.push(SnippetIssue {
+                        index: i,
+                        kind,
+                        message,
+                    });
+                    continue;
+                }
+            };
+        let source = match local {
+            true => code_path.display().to_string(),
+            false => relative_path(ctx.source_root(), &code_path),
+        };
+        let selector = match directory {
+            true => BlockSelector::Lines(1, code_content.lines().count()),
+            false => selector,
+        };
+        // The trailing comments are removed from each line, keeping the lines where they were
+        let trailing_prefix =
+            snippet_comment_prefix(language, &ctx.comment_prefixes).unwrap_or("//");
+        let code_content = normalize_code(
+            code_content,
+            ctx,
+            attributes
+                .strip_trailing_comments
+                .then_some(trailing_prefix),
+        );
+        let code_content = strip_bom(&code_content);
+        let snippet = match attributes.strip_trailing_comments {
+            true => strip_trailing_comments(content, trailing_prefix),
+            false => content.to_string(),
+        };
+        let snippet = snippet.as_str();
+        let cleaned_snippet = match comments {
+            Comments::Keep => unhide_lines(snippet),
+            Comments::Strip(prefix) => strip_comments(snippet, prefix),
+        };
+        let cleaned_snippet = match ctx.ignore_attributes.is_empty() {
+            true => cleaned_snippet,
+            false => cleaned_snippet
+                .lines()
+                .filter(|line| !is_ignored_attribute(line, &ctx.ignore_attributes))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        let cleaned_snippet = match ctx.normalize_line_wrapping {
+            true => join_wrapped_lines(&cleaned_snippet, false),
+            false => cleaned_snippet,
+        };
+        let cleaned_snippet = match ctx.unicode_normalize {
+            true => unicode::compose(&cleaned_snippet),
+            false => cleaned_snippet,
+        };
+        // The skipped leading attributes of the snippet, which are not searched for either
+        let (cleaned_snippet, skipped_attributes) = match ctx.leading_attributes {
+            LeadingAttributes::Ignore => skip_leading_attributes(&cleaned_snippet),
+            _ => (cleaned_snippet, 0),
+        };
+        // A signature-only snippet is compared without its placeholder body, up to its brace
+        let signature = match directives.signature_only.unwrap_or(ctx.signature_only) {
+            true => signature_snippet(&cleaned_snippet),
+            false => None,
+        };
+        let (cleaned_snippet, body) = match signature {
+            Some((signature, body)) => (signature, Some(body)),
+            None => (cleaned_snippet, None),
+        };
+        if explain {
+            explain_step("Source path", &code_path.display().to_string());
+            explain_step(
+                "Snippet with hidden lines, without comments",
+                &cleaned_snippet,
+            );
+        }
+        if ctx.require_balanced_braces {
+            if let Some(unbalanced) = unbalanced_delimiters(&cleaned_snippet) {
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::Other,
+                    message: format!("unbalanced delimiters ({unbalanced}), likely truncated"),
+                });
+            }
+        }
+        let snippet_lines = cleaned_snippet.lines().count();
+        if ctx.max_snippet_lines > 0 && snippet_lines > ctx.max_snippet_lines {
+            let message = format!(
+                "has {snippet_lines} lines, more than the {} allowed; split it or show only an anchor",
+                ctx.max_snippet_lines
+            );
+            match ctx.strict {
+                true => issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::Other,
+                    message,
+                }),
+                false => warnings.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::Other,
+                    message,
+                }),
+            }
+        }
+        if ctx.check_indent && remove_identation(&cleaned_snippet).is_some() {
+            let message = "snippet has leading indentation; dedent it".to_string();
+            issues.push(SnippetIssue {
+                index: i,
+                kind: IssueKind::Other,
+                message,
+            });
+            continue;
+        }
+
+        // Get the matching code content, and the lines where it is found. If the snippet path has a
+        // line range or anchors we take the block from there, instead of searching for the first line.
+        // The `use` lines are sorted first, as the code ones are likely sorted by rustfmt
+        let indent = match ctx.check_indent || attributes.no_dedent {
+            true => Indent::Keep,
+            false => Indent::Remove,
+        };
+        let searched_snippet = match ctx.sort_use {
+            true => sort_use_lines(&cleaned_snippet),
+            false => cleaned_snippet.clone(),
+        };
+        // The code lines of each anchor, in the order of the snippet path
+        let mut regions = Vec::new();
+        let found = match find_block(
+            code_content,
+            path,
+            &selector,
+            directory,
+            &searched_snippet,
+            comments,
+            indent,
+            directives.occurrence,
+            ctx,
+            &mut regions,
+        ) {
+            Ok(found) => found,
+            // The snippets found by their first line only fail when it's ambiguous
+            Err(message) => {
+                let kind = match selector {
+                    BlockSelector::Search => IssueKind::Ambiguous,
+                    _ => IssueKind::BlockNotFound,
+                };
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind,
+                    message,
+                });
+                continue;
+            }
+        };
+        if found.is_none() {
+            if let Some(Occurrence::Nth(n)) = directives.occurrence {
+                let message = format!("no occurrence {n} of the first snippet line in {path}");
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::BlockNotFound,
+                    message,
+                });
+                continue;
+            }
+        }
+        let Some((block_start_line, block_end_line, block)) = found else {
+            let message = format!(
+                "could not find the code block in {path}, as no code lines match the snippet"
+            );
+            issues.push(SnippetIssue {
+                index: i,
+                kind: IssueKind::BlockNotFound,
+                message,
+            });
+            continue;
+        };
+        // The code block of a signature-only snippet is the code signature, found by matching its
+        // opening brace from the first block line, and compared as a single region
+        let (block_end_line, block) = match body {
+            Some(_) => match code_signature(code_content, block_start_line, comments) {
+                Some(signature) => {
+                    regions.clear();
+                    signature
+                }
+                None => (block_end_line, block),
+            },
+            None => (block_end_line, block),
+        };
+        // The placeholder lines are replaced by the code lines they match. A block found by its
+        // first line is taken up to the end of the file, and then up to the last matched line
+        let has_placeholders = ctx.allow_placeholder
+            && cleaned_snippet
+                .lines()
+                .any(|line| PLACEHOLDER_LINES.contains(&line.trim()));
+        let (cleaned_snippet, searched_snippet, block_end_line, block) = match has_placeholders {
+            false => (cleaned_snippet, searched_snippet, block_end_line, block),
+            true => {
+                let search = matches!(selector, BlockSelector::Search);
+                let file_lines = code_content.lines().count();
+                let block = match search {
+                    true => {
+                        extract_range_block(code_content, block_start_line, file_lines, comments)
+                            .unwrap_or(block)
+                    }
+                    false => block,
+                };
+                let (expanded, matched, empty) = expand_placeholders(&cleaned_snippet, &block);
+                for line in empty {
+                    let message = format!(
+                        "the placeholder at snippet line {line} matches no code lines, which is \
+                         likely a mistake"
+                    );
+                    match ctx.strict {
+                        true => issues.push(SnippetIssue {
+                            index: i,
+                            kind: IssueKind::Other,
+                            message,
+                        }),
+                        false => warnings.push(SnippetIssue {
+                            index: i,
+                            kind: IssueKind::Other,
+                            message,
+                        }),
+                    }
+                }
+                let (block_end_line, block) = match search {
+                    true => {
+                        let end = code_lines(code_content, comments)
+                            .skip_while(|(line, _)| *line + 1 < block_start_line)
+                            .nth(matched.saturating_sub(1))
+                            .map_or(block_end_line, |(line, _)| line + 1);
+                        let block = block.lines().take(matched).collect::<Vec<_>>().join("\n");
+                        let block = match indent {
+                            Indent::Remove => dedent(&block),
+                            Indent::Keep => block,
+                        };
+                        (end, block)
+                    }
+                    false => (block_end_line, block),
+                };
+                let searched = match ctx.sort_use {
+                    true => sort_use_lines(&expanded),
+                    false => expanded.clone(),
+                };
+                (expanded, searched, block_end_line, block)
+            }
+        };
+        // A snippet found by its first line near the end of the file may have more lines than
+        // the rest of the code, which would only be a misleading diff
+        let (expected, captured) = (searched_snippet.lines().count(), block.lines().count());
+        if matches!(selector, BlockSelector::Search) && body.is_none() && captured < expected {
+            let message = format!(
+                "captured {captured} of {expected} expected lines, as {path} has no more code \
+                 after line {block_end_line}"
+            );
+            issues.push(SnippetIssue {
+                index: i,
+                kind: IssueKind::Other,
+                message,
+            });
+            continue;
+        }
+
+        let block = match ctx.leading_attributes {
+            LeadingAttributes::Ignore => skip_leading_attributes(&block).0,
+            _ => block,
+        };
+        if ctx.leading_attributes == LeadingAttributes::Require {
+            let before = attributes_before(code_content, block_start_line, comments);
+            if let Some(attribute) = before.first() {
+                let message = format!(
+                    "omits the attribute `{}` before the code at line {block_start_line}, and \
+                     `--attributes=require` is set",
+                    attribute.trim()
+                );
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::Other,
+                    message,
+                });
+            }
+        }
+
+        // A short snippet compared with all the code of a long file (usually most of it comments)
+        // was likely meant to show only a part of it
+        if is_whole_file_block(code_content, block_start_line, block_end_line, comments)
+            && cleaned_snippet.lines().count() * WHOLE_FILE_RATIO <= code_content.lines().count()
+        {
+            let message = format!(
+                "compared with all the code of {path}, which is much longer than the snippet"
+            );
+            match ctx.strict {
+                true => issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::Other,
+                    message,
+                }),
+                false => warnings.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::Other,
+                    message,
+                }),
+            }
+        }
+
+        let verified_snippet = VerifiedSnippet {
+            index: i,
+            fence_line: fences[i].0,
+            path: path.to_string(),
+            source: source.clone(),
+            start_line: block_start_line,
+            end_line: block_end_line,
+            lines: cleaned_snippet.lines().count(),
+            block: match ctx.extract_only {
+                true => block.clone(),
+                false => String::new(),
+            },
+            snippet: String::new(),
+        };
+
+        if explain {
+            let selected_by = match &selector {
+                BlockSelector::Search => "searching for the first snippet line",
+                BlockSelector::Lines(..) => "the line range",
+                BlockSelector::Anchors(..) => "the anchors",
+                BlockSelector::Module(..) => "the module",
+            };
+            let title = format!(
+                "Code block at lines {block_start_line}-{block_end_line}, found by {selected_by}"
+            );
+            explain_step(&title, &block);
+        }
+        if ctx.extract_only {
+            verified.push(verified_snippet);
+            continue;
+        }
+
+        // The hash of the code block is taken before any normalization, with a line feed after
+        // each line
+        let block_hash = directives.sha.map(|_| {
+            let hashed: String = block.lines().map(|line| format!("{line}\n")).collect();
+            sha256::hex(hashed.as_bytes())
+        });
+
+        // Both are compared without their identation, which is also how the diff is displayed
+        let snippet = ctx.comparator.normalize(&normalize_identation(
+            &cleaned_snippet,
+            ctx,
+            !attributes.no_dedent,
+        ));
+        let block =
+            ctx.comparator
+                .normalize(&normalize_identation(&block, ctx, !attributes.no_dedent));
+        // The snippet lines with placeholders are taken as the code lines they match, so that only
+        // the other differences are compared and shown
+        let snippet = fill_placeholders(&snippet, &block, &ctx.placeholder);
+        if explain {
+            let indentation = match ctx.check_indent {
+                _ if attributes.no_dedent => "keeping the indentation",
+                true => "removing one indentation level",
+                false => "removing the common indentation",
+            };
+            explain_step(&format!("Compared snippet, after {indentation}"), &snippet);
+            explain_step(&format!("Compared code block, after {indentation}"), &block);
+        }
+
+        // The TOML snippets are compared by their keys and values, whatever their order and format,
+        // and in the `ast_compare` mode only the tokens are compared, unless some can't be read
+        let mut hint = None;
+        let equal = match ctx.ast_compare {
+            _ if language == "toml" => match toml::difference(&snippet, &block) {
+                Ok(difference) => {
+                    hint = difference.map(|(key, snippet_value, code_value)| {
+                        let value = |value: Option<toml::Value>| match value {
+                            Some(value) => value.to_string(),
+                            None => "missing".to_string(),
+                        };
+                        format!(
+                            "key `{key}` differs, {} in the snippet and {} in the code",
+                            value(snippet_value),
+                            value(code_value)
+                        )
+                    });
+                    hint.is_none()
+                }
+                Err(e) => {
+                    let message = format!("compared as text, as the TOML could not be read ({e})");
+                    notes.push(SnippetIssue {
+                        index: i,
+                        kind: IssueKind::Other,
+                        message,
+                    });
+                    snippet == block
+                }
+            },
+            true => match (tokens::tokenize(&snippet), tokens::tokenize(&block)) {
+                (Ok(snippet_tokens), Ok(block_tokens)) => snippet_tokens == block_tokens,
+                (Err(e), _) | (_, Err(e)) => {
+                    let message =
+                        format!("compared as text, as the tokens could not be read ({e})");
+                    notes.push(SnippetIssue {
+                        index: i,
+                        kind: IssueKind::Other,
+                        message,
+                    });
+                    snippet == block
+                }
+            },
+            false => snippet == block,
+        };
+        // A snippet with a `similarity` directive passes if enough of its lines are equal
+        let similar = match directives.similarity {
+            Some(threshold) if !equal => {
+                let ratio = TextDiff::from_lines(&snippet, &block).ratio();
+                let passes = ratio >= threshold;
+                match passes {
+                    true => {
+                        let message = format!(
+                            "passed with a line similarity of {ratio:.2}, at least {threshold}"
+                        );
+                        notes.push(SnippetIssue {
+                            index: i,
+                            kind: IssueKind::Other,
+                            message,
+                        });
+                    }
+                    false => {
+                        hint = Some(format!(
+                            "line similarity of {ratio:.2}, below the {threshold} of the \
+                             `similarity` directive"
+                        ))
+                    }
+                }
+                passes
+            }
+            _ => false,
+        };
+        // The `sha` directive and the content comparison have to agree
+        if let (Some(sha), Some(block_hash)) = (directives.sha, &block_hash) {
+            let hash_matches = block_hash.starts_with(sha);
+            if hash_matches && !(equal || similar) && hint.is_none() {
+                hint = Some(
+                    "the `sha` directive still matches the code block, so the snippet was \
+                     changed instead of the code"
+                        .to_string(),
+                );
+            }
+            if !hash_matches && (equal || similar) {
+                let new_hash = block_hash[..sha.len()].to_string();
+                let message = format!(
+                    "matches the code, but its `sha` directive {sha} is not the hash of the code \
+                     block, {new_hash}; pass `--update-hashes` to update it"
+                );
+                issues.push(SnippetIssue {
+                    index: i,
+                    kind: IssueKind::StaleHash,
+                    message,
+                });
+                stale_hashes.push((i, new_hash));
+            }
+        }
+        if equal || similar {
+            verified.push(VerifiedSnippet {
+                snippet,
+                ..verified_snippet
+            });
+        } else {
+            // The content may still be in the file, but the referenced lines have moved
+            let moved_to = match selector {
+                BlockSelector::Lines(..) if !directory => extract_clean_block(
+                    code_content,
+                    &searched_snippet,
+                    comments,
+                    indent,
+                    &ctx.placeholder,
+                    None,
+                    ctx.max_candidates,
+                )
+                .ok()
+                .flatten()
+                .filter(|(start, end, moved_block)| {
+                    (*start, *end) != (block_start_line, block_end_line)
+                        && ctx.comparator.normalize(&normalize_identation(
+                            moved_block,
+                            ctx,
+                            !attributes.no_dedent,
+                        )) == snippet
+                })
+                .map(|(start, end, _)| (start, end)),
+                _ => None,
+            };
+
+            // The snippet lines start after the fence, path and directive lines
+            let mut compared = compared_lines(content, comments, &ctx.ignore_attributes);
+            compared.drain(..skipped_attributes.min(compared.len()));
+            let first_line = fences[i].0 + 2 + directives.count;
+            let md_lines = differing_md_lines(&compared, first_line, &snippet, &block);
+            if regions.is_empty() {
+                regions.push((block_start_line, block_end_line));
+            }
+            let code = regions
+                .iter()
+                .flat_map(|(start, end)| code_content.lines().take(*end).skip(start - 1))
+                .collect::<Vec<_>>()
+                .join("\n");
+            // The fixed signature-only snippet keeps its placeholder body after the brace
+            let last_line = code.rfind('\n').map_or(0, |newline| newline + 1);
+            let code = match (body, code[last_line..].find('{')) {
+                (Some(body), Some(brace)) => {
+                    format!("{} {body} }}", &code[..=last_line + brace])
+                }
+                _ => code,
+            };
+            let source_lines = match ctx.line_numbers {
+                true => block_source_lines(&block, code_content, &regions),
+                false => Vec::new(),
+            };
+            let context_start = (block_start_line - 1).saturating_sub(ctx.source_context);
+            let source_context = code_content
+                .lines()
+                .enumerate()
+                .take(block_start_line - 1)
+                .skip(context_start)
+                .map(|(i, line)| (i + 1, line.to_string()))
+                .collect();
+            let raw = match ctx.show_raw {
+                true => raw_lines[fences[i].0 - 1..fences[i].1.min(raw_lines.len())]
+                    .iter()
+                    .map(|line| line.to_string())
+                    .collect(),
+                false => Vec::new(),
+            };
+            diff = Some(true);
+            diffs.push(SnippetDiff {
+                index: i,
+                path: path.to_string(),
+                source,
+                start_line: block_start_line,
+                moved_to,
+                directives: directives.count,
+                hint,
+                snippet,
+                block,
+                md_lines,
+                code,
+                comment_prefix: match comments {
+                    Comments::Strip(prefix) => Some(prefix.to_string()),
+                    Comments::Keep => None,
+                },
+                source_context,
+                raw,
+                source_lines,
+            });
+        }
+    }
+
+    if ctx.check_intra_file_order {
+        issues.extend(intra_file_order_issues(&verified, &diffs));
+    }
+
+    Ok(FileCheck {
+        diff,
+        verified,
+        diffs,
+        issues,
+        missing,
+        missing_fails: ctx.missing_paths == MissingPaths::Error || ctx.strict || ctx.only_missing,
+        warnings,
+        notes,
+        expected_snippets,
+        snippet_count_line,
+        broken_includes,
+        malformed_headers,
+        malformed_fails: ctx.strict || ctx.fail_on_unparsed_fence,
+        sources,
+        fences,
+        paths,
+        stale_hashes,
+        skipped,
+    })
+}
+
+// Returns the source code as compared with the snippets: with the `cfg` options applied, the
+// ignored attribute lines blanked (so that they are skipped as the blank ones), the trailing
+// comments of this prefix removed, and the wrapped lines joined in the first one if enabled
+fn normalize_code(code: String, ctx: &CheckContext, trailing_prefix: Option<&str>) -> String {
+    let code = match ctx.cfg.is_empty() {
+        true => code,
+        false => cfg::apply(&code, &ctx.cfg),
+    };
+    let code = match ctx.ignore_attributes.is_empty() {
+        true => code,
+        false => code
+            .lines()
+            .map(
+                |line| match is_ignored_attribute(line, &ctx.ignore_attributes) {
+                    true => "",
+                    false => line,
+                },
+            )
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    let code = match trailing_prefix {
+        Some(prefix) => strip_trailing_comments(&code, prefix),
+        None => code,
+    };
+    let code = match ctx.normalize_line_wrapping {
+        true => join_wrapped_lines(&code, true),
+        false => code,
+    };
+    match ctx.unicode_normalize {
+        true => unicode::compose(&code),
+        false => code,
+    }
+}
+
+// Finds the code block of a snippet with its selector, and returns its first and last lines and the
+// block itself, or none if no code lines match the snippet. The lines of each anchor are added to
+// the regions, in the order of the snippet path
+#[allow(clippy::too_many_arguments)]
+fn find_block(
+    code_content: &str,
+    path: &str,
+    selector: &BlockSelector,
+    directory: bool,
+    searched_snippet: &str,
+    comments: Comments,
+    indent: Indent,
+    occurrence: Option<Occurrence>,
+    ctx: &CheckContext,
+    regions: &mut Vec<(usize, usize)>,
+) -> Result<Option<(usize, usize, String)>, String> {
+    match selector {
+        BlockSelector::Search => extract_clean_block(
+            code_content,
+            searched_snippet,
+            comments,
+            indent,
+            &ctx.placeholder,
+            occurrence,
+            ctx.max_candidates,
+        ),
+        BlockSelector::Lines(range_start, end) => {
+            let (start, end) = (*range_start, *end);
+            let file_lines = code_content.lines().count();
+            if start > end || end > file_lines {
+                return Err(format!(
+                    "could not find the code block in {path}, as its line range {start}-{end} \
+                     is out of bounds (the file has {file_lines} lines)"
+                ));
+            }
+            // The directory files were read without their header lines
+            let header = ctx.strip_header_lines;
+            let start = match directory {
+                true => start,
+                false => start.max(header + 1),
+            };
+            if start > end {
+                return Err(format!(
+                    "its line range {}-{end} only has header lines of {path} (the first {header})",
+                    *range_start
+                ));
+            }
+            Ok(extract_range_block(code_content, start, end, comments)
+                .map(|block| (start, end, block)))
+        }
+        BlockSelector::Anchors(anchors) => {
+            for anchor in anchors {
+                let Some(region) = find_item_lines(code_content, anchor) else {
+                    return Err(format!(
+                        "could not find the code block in {path}, as it has no item named by the \
+                         anchor `{anchor}`"
+                    ));
+                };
+                regions.push(region);
+            }
+            Ok(extract_regions_block(code_content, regions, comments))
+        }
+        BlockSelector::Module(name) => {
+            let (start, end) = find_module_lines(code_content, path, name)?;
+            Ok(extract_range_block(code_content, start, end, comments)
+                .map(|block| (start, end, block)))
+        }
+    }
+}
+
+// The source of a snippet path, once resolved in the code directory (or the markdown directory)
+enum SnippetSource {
+    // The source file, or directory, and its content
+    Found(PathBuf, String),
+    // The source file exists, and was not read in the `only_missing` mode
+    Exists,
+    Missing(MissingPath),
+    Skipped(Skipped),
+    Issue(IssueKind, String),
+}
+
+// Resolves a snippet path to its source file and reads it. A directory path compares the snippet
+// with all the Rust files of the directory. The read files are added to the sources, and the
+// symlink notes to the notes
+fn resolve_source(
+    ctx: &CheckContext,
+    md_dir: &Path,
+    index: usize,
+    path: &str,
+    sources: &mut BTreeSet<PathBuf>,
+    notes: &mut Vec<SnippetIssue>,
+) -> Result<SnippetSource, CheckError> {
+    let local = path.starts_with("./");
+    let directory = path.ends_with('/');
+    if directory {
+        return Ok(match read_directory(ctx, md_dir, path) {
+            Ok((files, content)) => {
+                sources.extend(files);
+                SnippetSource::Found(PathBuf::from(path), content)
+            }
+            Err(message) => SnippetSource::Issue(IssueKind::BlockNotFound, message),
+        });
+    }
+
+    let root_dir = match local {
+        true => md_dir.display().to_string(),
+        false => ctx.code_dir.clone(),
+    };
+    let validated = match local {
+        true => validate_local_path(ctx, md_dir, path),
+        false => validate_file_path(ctx, path),
+    };
+    let code_path = match validated {
+        Ok(code_path) => code_path,
+        Err(_) if ctx.missing_paths == MissingPaths::Skip && !ctx.only_missing => {
+            return Ok(SnippetSource::Skipped(Skipped::MissingPath {
+                index,
+                path: path.to_string(),
+            }));
+        }
+        Err(attempted) => {
+            return Ok(SnippetSource::Missing(MissingPath {
+                index,
+                path: path.to_string(),
+                attempted: attempted
+                    .iter()
+                    .map(|p| relative_path(&root_dir, p))
+                    .collect(),
+                suggestion: match local {
+                    true => None,
+                    false => suggest_path(path, ctx.source_files()).map(str::to_string),
+                },
+            }));
+        }
+    };
+
+    if ctx.source.is_none() || local {
+        // The path may still escape once resolved, through a symlink
+        if let Some(target) = resolved_outside(&root_dir, &code_path) {
+            let message = format!(
+                "refusing to read {path}, as it resolves to {} outside of the code \
+                 directory",
+                target.display()
+            );
+            return Ok(SnippetSource::Issue(IssueKind::UnsafePath, message));
+        }
+        if let Some(target) = symlink_target(&root_dir, &code_path) {
+            let message = format!("{path} resolves through a symlink to {}", target.display());
+            if ctx.no_symlinks {
+                return Ok(SnippetSource::Issue(IssueKind::UnsafePath, message));
+            }
+            notes.push(SnippetIssue {
+                index,
+                kind: IssueKind::UnsafePath,
+                message,
+            });
+        }
+    }
+    if ctx.only_missing {
+        sources.insert(code_path);
+        return Ok(SnippetSource::Exists);
+    }
+
+    let read_start = Instant::now();
+    let code_bytes = match local {
+        true => ctx.read_local(&code_path).map_err(|e| CheckError::Io {
+            message: format!("Could not read {}: {e}", code_path.display()),
+            path: code_path.clone(),
+        })?,
+        false => ctx.read_code(&code_path)?,
+    };
+    ctx.record_read(&code_path, code_bytes.len(), read_start.elapsed());
+    sources.insert(code_path.clone());
+    // A source file that is not text only fails its snippets, and not the whole run
+    let Ok(code_content) = String::from_utf8(code_bytes) else {
+        let message = format!("source file is not valid UTF-8: {path}");
+        return Ok(SnippetSource::Issue(IssueKind::UnreadableSource, message));
+    };
+    Ok(SnippetSource::Found(code_path, code_content))
+}
+
+// Returns the markdown lines of the compared snippet lines that are not in the code block, given
+// the fence content lines that were compared and the markdown line of the first one. They can't be
+// located if the normalization changed the number of lines
+fn differing_md_lines(
+    compared: &[usize],
+    first_line: usize,
+    snippet: &str,
+    block: &str,
+) -> Vec<usize> {
+    if snippet.lines().count() != compared.len() {
+        return Vec::new();
+    }
+    TextDiff::from_lines(snippet, block)
+        .iter_all_changes()
+        .filter(|change| change.tag() == ChangeTag::Delete)
+        .filter_map(|change| change.old_index())
+        .map(|i| first_line + compared[i])
+        .collect()
+}
+
+// The indexes of the fence content lines that are compared with the code, skipping the comments
+// and blank lines, or only the surrounding blank lines if comments are kept
+fn compared_lines(content: &str, comments: Comments, ignored: &[String]) -> Vec<usize> {
+    let unhidden = content.lines().map(unhide_line).collect::<Vec<_>>();
+    let compared = match comments {
+        Comments::Strip(_) => unhidden
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| is_code_line(line, comments))
+            .map(|(i, _)| i)
+            .collect(),
+        Comments::Keep => {
+            let first = unhidden.iter().position(|line| !line.trim().is_empty());
+            let last = unhidden.iter().rposition(|line| !line.trim().is_empty());
+            match (first, last) {
+                (Some(first), Some(last)) => (first..=last).collect(),
+                _ => Vec::new(),
+            }
+        }
+    };
+    compared
+        .into_iter()
+        .filter(|i| !is_ignored_attribute(&unhidden[*i], ignored))
+        .collect()
+}
+
+// Returns the code without its leading attribute lines, and the number of removed lines
+fn skip_leading_attributes(code: &str) -> (String, usize) {
+    let skipped = code
+        .lines()
+        .take_while(|line| line.trim_start().starts_with("#["))
+        .count();
+    let rest = code.lines().skip(skipped).collect::<Vec<_>>().join("\n");
+    (rest, skipped)
+}
+
+// Returns the attribute lines right before the `start` line of the code, from the closest one. The
+// comments and empty lines between them (like the lines of the ignored attributes) are skipped
+fn attributes_before<'a>(code: &'a str, start: usize, comments: Comments) -> Vec<&'a str> {
+    let comments = match comments {
+        Comments::Keep => Comments::Strip("//"),
+        strip => strip,
+    };
+    code.lines()
+        .take(start - 1)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .filter(|line| is_code_line(line, comments))
+        .take_while(|line| line.trim_start().starts_with("#["))
+        .collect()
+}
+
+// Whether the line is an attribute starting with one of the ignored ones, like `#[doc(hidden)]`
+// for `doc(hidden)`. A name only matches whole attribute names, so `cfg` doesn't match `cfg_attr`
+fn is_ignored_attribute(line: &str, ignored: &[String]) -> bool {
+    let Some(attribute) = line.trim().strip_prefix("#[") else {
+        return false;
+    };
+    ignored.iter().any(|name| {
+        attribute
+            .strip_prefix(name.as_str())
+            .is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+    })
+}
+
+// The minimum number of lines of a file, and of times it's longer than a snippet, for a snippet
+// compared with all its code to be taken as an accidental whole file match
+const WHOLE_FILE_LINES: usize = 30;
+const WHOLE_FILE_RATIO: usize = 4;
+
+// Whether the `start` and `end` lines include all the code lines of a long enough file
+fn is_whole_file_block(file_content: &str, start: usize, end: usize, comments: Comments) -> bool {
+    let mut code_lines = file_content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| is_code_line(line, comments))
+        .map(|(i, _)| i + 1);
+    let first = code_lines.next();
+    let last = code_lines.last().or(first);
+
+    file_content.lines().count() >= WHOLE_FILE_LINES
+        && first.is_some_and(|first| start <= first)
+        && last.is_some_and(|last| end >= last)
+}
+
+// Checks that each `{{#include path}}` (or `{{#rustdoc_include path}}`) directive references an
+// existing file, relative to the markdown file directory as in mdBook, and that the referenced
+// lines or anchor exist and are not empty. Directives escaped with a backslash are skipped
+fn check_includes(md_file: &str, md_dir: &Path, ctx: &CheckContext) -> Vec<BrokenInclude> {
+    let include_regex = Regex::new(r"\{\{#(?:rustdoc_)?include\s+([^}\s]+)\s*\}\}").unwrap();
+    let mut broken = Vec::new();
+
+    for caps in include_regex.captures_iter(md_file) {
+        let directive = caps.get(0).unwrap();
+        if md_file[..directive.start()].ends_with('\\') {
+            continue;
+        }
+        let (path, selection) = caps[1].split_once(':').unwrap_or((&caps[1], ""));
+
+        let content = ctx.read_local(&md_dir.join(path)).and_then(|bytes| {
+            String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        });
+        let message = match content {
+            Ok(content) => included_content(&content, selection)
+                .err()
+                .map(|e| format!("{e} in {path}")),
+            Err(e) => Some(format!("could not read {path}: {e}")),
+        };
+        if let Some(message) = message {
+            broken.push(BrokenInclude {
+                line: fence_line(md_file, directive.start()),
+                directive: directive.as_str().to_string(),
+                message,
+            });
+        }
+    }
+    broken
+}
+
+// Finds the Rust fences whose first line looks like a snippet path (or manifest reference) line
+// with a typo, like `# // path:` or `# //Path:`, which would otherwise be skipped silently. With
+// `unparsed`, the Rust fences without any header are found too, except those of mdBook includes
+fn malformed_headers(md_file: &str, unparsed: bool) -> Vec<MalformedHeader> {
+    let header_regex = Regex::new(r"(?i)^#?\s*//+\s*(path|ref)\s*:").unwrap();
+    let mut malformed = Vec::new();
+
+    let lines: Vec<&str> = md_file.lines().collect();
+    for (i, pair) in lines.windows(2).enumerate() {
+        let info = pair[0]
+            .strip_prefix("```rust")
+            .or_else(|| pair[0].strip_prefix("~~~rust"));
+        if !info.is_some_and(|info| info.is_empty() || info.starts_with([' ', ','])) {
+            continue;
+        }
+        let header = pair[1].trim_end();
+        let well_formed = header.starts_with("# // Path: ") || header.starts_with("# // Ref: ");
+        if header_regex.is_match(header.trim_start()) && !well_formed {
+            malformed.push(MalformedHeader {
+                line: i + 2,
+                header: header.to_string(),
+                unparsed: false,
+            });
+        } else if unparsed && !well_formed && !header.trim_start().starts_with("{{#") {
+            malformed.push(MalformedHeader {
+                line: i + 1,
+                header: header.to_string(),
+                unparsed: true,
+            });
+        }
+    }
+    malformed
+}
+
+// Returns the lines of an included file selected by the part of the directive after the path,
+// which can be empty (the whole file), a line (`10`), a line range (`10:20`, `10:` or `:20`) or
+// an anchor name, given by `ANCHOR: name` and `ANCHOR_END: name` comments
+fn included_content(content: &str, selection: &str) -> Result<String, String> {
+    let lines: Vec<_> = content.lines().collect();
+    let (start, end) = match selection.split_once(':') {
+        _ if selection.is_empty() => (1, lines.len()),
+        None if selection.parse::<usize>().is_ok() => {
+            let line = selection.parse().unwrap();
+            (line, line)
+        }
+        None => {
+            let anchor = |marker: &str| {
+                lines
+                    .iter()
+                    .position(|line| line.trim_end().ends_with(&format!("{marker}: {selection}")))
+            };
+            let start = anchor("ANCHOR").ok_or(format!("anchor `{selection}` not found"))?;
+            let end = anchor("ANCHOR_END").ok_or(format!("anchor `{selection}` has no end"))?;
+            (start + 2, end)
+        }
+        Some((start, end)) => {
+            let number = |value: &str, default| match value {
+                "" => Ok(default),
+                value => value
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid line range `{selection}`")),
+            };
+            (number(start, 1)?, number(end, lines.len())?)
+        }
+    };
+
+    if start == 0 || start > end || end > lines.len() {
+        return Err(format!(
+            "lines {start}-{end} out of bounds ({} lines)",
+            lines.len()
+        ));
+    }
+    let included = lines[start - 1..end].join("\n");
+    if included.trim().is_empty() {
+        return Err(format!("lines {start}-{end} are empty"));
+    }
+    Ok(included)
+}
+
+// Returns the line number of the byte offset in the markdown file
+fn fence_line(md_file: &str, offset: usize) -> usize {
+    md_file[..offset].matches('\n').count() + 1
+}
+
+// Rewrites the fences of the `fence_languages` that start with a snippet header, written with the
+// comment prefix of the language (like `# Path:` in a `toml` fence), as `rust` fences with their
+// `snippetlang` attribute, so that they are checked as those. The lines starting with `#` are
+// escaped as `##`, as they are not hidden lines as in Rust. The lines are kept in place
+fn language_fences(md_file: &str, ctx: &CheckContext) -> String {
+    let mut lines: Vec<String> = md_file.lines().map(str::to_string).collect();
+    let mut i = 0;
+    while i + 1 < lines.len() {
+        let Some(marker) = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| lines[i].starts_with(marker))
+        else {
+            i += 1;
+            continue;
+        };
+        let info = lines[i][marker.len()..].to_string();
+        let language_end = info.find([' ', ',']).unwrap_or(info.len());
+        let (language, attributes) = info.split_at(language_end);
+        let close = lines[i + 1..]
+            .iter()
+            .position(|line| line.trim_end() == marker)
+            .map_or(lines.len(), |close| i + 1 + close);
+
+        let prefix = match ctx.fence_languages.iter().any(|name| name == language) {
+            true => snippet_comment_prefix(language, &ctx.comment_prefixes).ok(),
+            false => None,
+        };
+        let header = prefix.and_then(|prefix| {
+            let header = lines[i + 1].strip_prefix(prefix)?.strip_prefix(' ')?;
+            (header.starts_with("Path: ") || header.starts_with("Ref: "))
+                .then(|| header.to_string())
+        });
+        if let Some(header) = header {
+            lines[i + 1] = format!("# // {header}");
+            lines[i] = format!("{marker}rust,snippetlang={language}{attributes}");
+            for line in &mut lines[i + 2..close] {
+                if line.trim_start().starts_with('#') {
+                    line.insert(line.len() - line.trim_start().len(), '#');
+                }
+            }
+        }
+        i = close + 1;
+    }
+    lines.join("\n")
+}
+
+// Joins the markdown lines, removing the indentation of the indented fences (as in list items)
+// from the fence lines and their content, so that they are found like the other fences
+fn unindent_fences(lines: &[&str]) -> String {
+    // The indentation and fence marker of the current indented fence
+    let mut fence: Option<(usize, &str)> = None;
+    let mut unindented = Vec::new();
+
+    for line in lines {
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+        match fence {
+            None => {
+                let marker = ["```", "~~~"]
+                    .into_iter()
+                    .find(|marker| trimmed.starts_with(marker));
+                match marker {
+                    Some(marker) if indent > 0 => {
+                        fence = Some((indent, marker));
+                        unindented.push(trimmed);
+                    }
+                    _ => unindented.push(line),
+                }
+            }
+            Some((fence_indent, marker)) => {
+                // Content lines can be less indented, like the blank ones
+                let line = &line[indent.min(fence_indent)..];
+                // The closing fence only has the fence characters
+                if line.trim_end().starts_with(marker)
+                    && line.trim_end().trim_matches(['`', '~']).is_empty()
+                {
+                    fence = None;
+                }
+                unindented.push(line);
+            }
+        }
+    }
+    unindented.join("\n")
+}
+
+// Prints a step of the `explain` trace
+fn explain_step(title: &str, content: &str) {
+    println!("{}", format!("== {title}").bold().accent());
+    println!("{content}\n");
+}
+
+// Files saved with a UTF-8 byte order mark would otherwise have it as part of the first line
+fn strip_bom(content: &str) -> &str {
+    content.strip_prefix('\u{FEFF}').unwrap_or(content)
+}
+
+fn remove_identation(block: &str) -> Option<String> {
+    let mut no_ident = String::new();
+    let to_remove = " ".repeat(4); // Identation is 4 spaces
+
+    for (i, line) in block.lines().enumerate() {
+        if let Some(trimmed) = line.strip_prefix(&to_remove) {
+            if i != 0 {
+                // End previous line
+                no_ident.push('\n');
+            }
+            no_ident.push_str(trimmed);
+        } else {
+            // The block doesn't have identation in all the lines
+            return None;
+        }
+    }
+    Some(no_ident)
+}
+
+// Returns the leading whitespace that all the (non-empty) lines have in common
+fn common_identation(block: &str) -> &str {
+    block
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .reduce(|common, ident| {
+            let len = common
+                .chars()
+                .zip(ident.chars())
+                .take_while(|(a, b)| a == b)
+                .count();
+            &common[..len]
+        })
+        .unwrap_or("")
+}
+
+// Removes the leading whitespace that all the (non-empty) lines have in common
+fn dedent(block: &str) -> String {
+    let common = common_identation(block);
+    block
+        .lines()
+        .map(|line| line.strip_prefix(common).unwrap_or(line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Returns the code as compared with other code: without the common identation, or without one
+// identation level in the `check_indent` mode, unless not dedented. Trailing whitespace is also
+// removed if ignored, and the `use` lines are sorted in the `sort_use` mode
+fn normalize_identation(code: &str, ctx: &CheckContext, dedented: bool) -> String {
+    let code = if !dedented {
+        code.to_string()
+    } else if ctx.check_indent {
+        remove_identation(code).unwrap_or_else(|| code.to_string())
+    } else {
+        dedent(code)
+    };
+
+    let code = if ctx.ignore_trailing_whitespace {
+        code.lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        code
+    };
+
+    if ctx.sort_use {
+        sort_use_lines(&code)
+    } else {
+        code
+    }
+}
+
+// Sorts each run of consecutive single line `use` declarations, so that their order doesn't
+// matter. The other lines are kept in place
+// Replaces each snippet line with placeholders by the code line at the same position, if the rest
+// of the line matches it
+fn fill_placeholders(snippet: &str, block: &str, placeholder: &str) -> String {
+    if placeholder.is_empty() || !snippet.contains(placeholder) {
+        return snippet.to_string();
+    }
+    let mut block_lines = block.lines();
+    snippet
+        .lines()
+        .map(|line| match block_lines.next() {
+            Some(code_line) if line_matches(line, code_line, placeholder) => code_line,
+            _ => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Replaces each placeholder line of the snippet by the code lines it stands for: those from the
+// block line at the same position, up to the end of the block the placeholder is in (the first less
+// indented line) or to the line that matches the next snippet line. Returns the expanded snippet,
+// how many block lines were matched, and the snippet lines of the placeholders matching no lines
+fn expand_placeholders(snippet: &str, block: &str) -> (String, usize, Vec<usize>) {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let is_placeholder = |line: &str| PLACEHOLDER_LINES.contains(&line.trim());
+    let snippet_lines: Vec<&str> = snippet.lines().collect();
+    let block_lines: Vec<&str> = block.lines().collect();
+
+    // The indentation that the block lines have over the snippet ones, as the snippet is dedented
+    let first_line = |lines: &[&str]| {
+        lines
+            .iter()
+            .find(|line| !line.trim().is_empty())
+            .map_or(0, |line| indent(line))
+    };
+    let offset = first_line(&block_lines).saturating_sub(first_line(&snippet_lines));
+
+    let mut expanded = Vec::new();
+    let mut empty = Vec::new();
+    let mut j = 0;
+    for (i, line) in snippet_lines.iter().enumerate() {
+        if !is_placeholder(line) {
+            expanded.push(line.to_string());
+            j += 1;
+            continue;
+        }
+        let level = indent(line) + offset;
+        let next = snippet_lines
+            .get(i + 1)
+            .filter(|next| !is_placeholder(next));
+        j = j.min(block_lines.len());
+        let start = j;
+        while let Some(code_line) = block_lines.get(j) {
+            let blank = code_line.trim().is_empty();
+            let ends_block = !blank && indent(code_line) < level;
+            let is_next = next.is_some_and(|next| {
+                next.trim() == code_line.trim() && indent(next) + offset == indent(code_line)
+            });
+            if ends_block || is_next {
+                break;
+            }
+            j += 1;
+        }
+        if j == start {
+            empty.push(i + 1);
+        }
+        expanded.extend(block_lines[start..j].iter().map(|code_line| {
+            match indent(code_line) >= offset {
+                true => code_line[offset..].to_string(),
+                false => code_line.trim_start().to_string(),
+            }
+        }));
+    }
+    (expanded.join("\n"), j.min(block_lines.len()), empty)
+}
+
+// Whether the snippet line is the code line, with each placeholder matching any text
+fn line_matches(line: &str, code_line: &str, placeholder: &str) -> bool {
+    if placeholder.is_empty() || !line.contains(placeholder) {
+        return line == code_line;
+    }
+    let parts: Vec<_> = line.split(placeholder).collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if code_line.len() < first.len() + last.len()
+        || !code_line.starts_with(first)
+        || !code_line.ends_with(last)
+    {
+        return false;
+    }
+
+    // The middle parts must be found in order, between the first and last ones
+    let mut rest = &code_line[first.len()..code_line.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+// Joins the lines of each expression wrapped over several lines, as rustfmt does with long calls
+// and method chains. A line is joined with the next ones while it has unclosed parentheses or
+// brackets, and with the following lines that start with a method call. This is only a best effort
+// heuristic, without parsing the code. The joined lines are left blank if `keep_lines`, so that the
+// line numbers don't change, and otherwise removed
+fn join_wrapped_lines(code: &str, keep_lines: bool) -> String {
+    let lines: Vec<&str> = code.lines().collect();
+    let mut joined_lines = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let mut joined = lines[i].trim_end().to_string();
+        let mut depth = wrap_depth(lines[i]);
+        let mut next = i + 1;
+        while let Some(line) = lines.get(next) {
+            let trimmed = line.trim();
+            let chained = trimmed.starts_with('.') && !trimmed.starts_with("..");
+            if depth <= 0 && !chained {
+                break;
+            }
+            // The comment lines inside the wrapped expression are dropped
+            if !trimmed.starts_with("//") && !trimmed.is_empty() {
+                if (trimmed.starts_with(')') || trimmed.starts_with(']')) && joined.ends_with(',') {
+                    joined.pop();
+                }
+                let tight =
+                    joined.ends_with(['(', '[']) || trimmed.starts_with([')', ']', '.', '?']);
+                if !tight {
+                    joined.push(' ');
+                }
+                joined.push_str(trimmed);
+            }
+            depth += wrap_depth(line);
+            next += 1;
+        }
+
+        joined_lines.push(joined);
+        if keep_lines {
+            joined_lines.extend((i + 1..next).map(|_| String::new()));
+        }
+        i = next;
+    }
+
+    let mut normalized = joined_lines.join("\n");
+    if code.ends_with('\n') {
+        normalized.push('\n');
+    }
+    normalized
+}
+
+// Returns how many parentheses and brackets the line opens, minus the ones it closes
+fn wrap_depth(line: &str) -> i32 {
+    code_chars(line).iter().fold(0, |depth, c| match c {
+        '(' | '[' => depth + 1,
+        ')' | ']' => depth - 1,
+        _ => depth,
+    })
+}
+
+fn sort_use_lines(code: &str) -> String {
+    let is_use = |line: &str| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("pub ").unwrap_or(line);
+        line.starts_with("use ") && line.trim_end().ends_with(';')
+    };
+
+    let mut lines: Vec<_> = code.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let run = lines[i..].iter().take_while(|line| is_use(line)).count();
+        lines[i..i + run].sort_unstable();
+        i += run.max(1);
+    }
+    lines.join("\n")
+}
+
+// Which of the code lines matching the first snippet line starts the code block, counted from 1
+#[derive(Clone, Copy)]
+enum Occurrence {
+    Nth(usize),
+    Last,
+}
+
+// How to find the code block that a snippet is compared with
+enum BlockSelector<'a> {
+    // Search for the first file line that matches the first snippet line
+    Search,
+    // Take the lines given by a `:start-end` path suffix (1-based and inclusive)
+    Lines(usize, usize),
+    // Take the items given by a `#name` or `#name_a+name_b` path suffix, in order
+    Anchors(Vec<&'a str>),
+    // Take the body of the inline module given by a `::mod name` path suffix
+    Module(&'a str),
+}
+
+// Splits an optional line range, anchor list or module from the snippet path, failing if the line
+// numbers are too large
+fn parse_snippet_path(snippet_path: &str) -> Result<(&str, BlockSelector<'_>), String> {
+    let range_regex = Regex::new(r"^(.*):(\d+)-(\d+)$").unwrap();
+    let anchors_regex = Regex::new(r"^(.*)#(\w+(?:\+\w+)*)$").unwrap();
+    let module_regex = Regex::new(r"^(.*)::mod\s+(\w+)$").unwrap();
+
+    if let Some(caps) = range_regex.captures(snippet_path) {
+        let invalid = |_| format!("invalid line range `{}-{}`", &caps[2], &caps[3]);
+        let start: usize = caps[2].parse().map_err(invalid)?;
+        let end = caps[3].parse().map_err(invalid)?;
+        Ok((
+            caps.get(1).unwrap().as_str(),
+            BlockSelector::Lines(start.max(1), end),
+        ))
+    } else if let Some(caps) = anchors_regex.captures(snippet_path) {
+        let anchors = caps.get(2).unwrap().as_str().split('+').collect();
+        Ok((
+            caps.get(1).unwrap().as_str(),
+            BlockSelector::Anchors(anchors),
+        ))
+    } else if let Some(caps) = module_regex.captures(snippet_path) {
+        Ok((
+            caps.get(1).unwrap().as_str(),
+            BlockSelector::Module(caps.get(2).unwrap().as_str()),
+        ))
+    } else {
+        Ok((snippet_path, BlockSelector::Search))
+    }
+}
+
+// Returns the first and last line numbers of the item (function, struct, etc.) named `anchor`,
+// spanning from its declaration line to its closing brace
+fn find_item_lines(file_content: &str, anchor: &str) -> Option<(usize, usize)> {
+    let item_regex = Regex::new(&format!(
+        r"\b(fn|struct|enum|union|trait|mod|type|const|static|macro_rules!)\s+{}\b",
+        regex::escape(anchor)
+    ))
+    .unwrap();
+
+    let lines: Vec<_> = file_content.lines().collect();
+    let start = lines.iter().position(|line| {
+        let trimmed = line.trim_start();
+        !trimmed.starts_with("//") && item_regex.is_match(trimmed)
+    })?;
+
+    let mut depth = 0;
+    let mut opened = false;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        for c in code_chars(line) {
+            match c {
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+
+        // Items without a body, like `type Foo = Bar;`, end at the first semicolon
+        if (opened && depth <= 0) || (!opened && line.trim_end().ends_with(';')) {
+            return Some((start + 1, i + 1));
+        }
+    }
+    None
+}
+
+// Returns the snippet with the placeholder body of its last line (like `{ todo!() }`) removed, up
+// to the opening brace, and the placeholder, if it ends with one
+fn signature_snippet(snippet: &str) -> Option<(String, &'static str)> {
+    let (rest, last) = snippet.rsplit_once('\n').unwrap_or(("", snippet));
+    PLACEHOLDER_LINES.iter().find_map(|placeholder| {
+        let signature = last
+            .trim_end()
+            .strip_suffix(&format!("{{ {placeholder} }}"))?;
+        let signature = match rest.is_empty() {
+            true => format!("{signature}{{"),
+            false => format!("{rest}\n{signature}{{"),
+        };
+        Some((signature, *placeholder))
+    })
+}
+
+// Returns the last line number and the lines of the signature that starts at this code line, up
+// to its opening brace. An item without a body, like a trait method declaration, has no signature
+fn code_signature(code: &str, start: usize, comments: Comments) -> Option<(usize, String)> {
+    let mut lines = Vec::new();
+    for (i, line) in code_lines(code, comments).skip_while(|(i, _)| *i + 1 < start) {
+        if code_chars(line).contains(&'{') {
+            lines.push(&line[..=line.find('{')?]);
+            return Some((i + 1, lines.join("\n")));
+        }
+        if line.trim_end().ends_with(';') {
+            return None;
+        }
+        lines.push(line);
+    }
+    None
+}
+
+// Returns the first and last line numbers of the body of the inline module `name`, between its
+// opening and closing brace lines. A module declared with `mod name;` has its body in another file
+fn find_module_lines(file_content: &str, path: &str, name: &str) -> Result<(usize, usize), String> {
+    let module_regex = Regex::new(&format!(r"\bmod\s+{}\b", regex::escape(name))).unwrap();
+
+    let lines: Vec<_> = file_content.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| {
+            let trimmed = line.trim_start();
+            !trimmed.starts_with("//") && module_regex.is_match(trimmed)
+        })
+        .ok_or_else(|| format!("no module `{name}` in {path}"))?;
+
+    let mut depth = 0;
+    let mut open = None;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let chars = code_chars(line);
+        if open.is_none() && !chars.contains(&'{') && chars.contains(&';') {
+            return Err(format!(
+                "module `{name}` is declared with `mod {name};` in {path}, so its body is in \
+                 another file"
+            ));
+        }
+        for c in chars {
+            match c {
+                '{' => {
+                    depth += 1;
+                    open.get_or_insert(i);
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        match open {
+            Some(open) if depth <= 0 && open + 1 < i => return Ok((open + 2, i)),
+            Some(_) if depth <= 0 => {
+                return Err(format!(
+                    "module `{name}` of {path} has no lines between its braces"
+                ))
+            }
+            _ => {}
+        }
+    }
+    Err(format!("module `{name}` of {path} has no closing brace"))
+}
+
+// Returns a description of the unbalanced `{}`, `()` and `[]` delimiters in the code, as the count
+// of unclosed (positive) or extra closing (negative) delimiters of each kind
+fn unbalanced_delimiters(code: &str) -> Option<String> {
+    let mut counts = [0i32; 3];
+    for c in code.lines().flat_map(code_chars) {
+        match c {
+            '{' => counts[0] += 1,
+            '}' => counts[0] -= 1,
+            '(' => counts[1] += 1,
+            ')' => counts[1] -= 1,
+            '[' => counts[2] += 1,
+            ']' => counts[2] -= 1,
+            _ => {}
+        }
+    }
+
+    let unbalanced: Vec<_> = ["{}", "()", "[]"]
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count != 0)
+        .map(|(delimiter, count)| format!("{delimiter} {count:+}"))
+        .collect();
+
+    if unbalanced.is_empty() {
+        None
+    } else {
+        Some(unbalanced.join(", "))
+    }
+}
+
+// Returns the chars of a code line, skipping string and char literals, and line comments
+fn code_chars(line: &str) -> Vec<char> {
+    let mut code = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // Skip until the closing quote, taking into account the escaped chars
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '\'' => {
+                // Char literals are either `'x'` or `'\x'`, otherwise this is a lifetime
+                let rest: String = chars.clone().take(3).collect();
+                if rest.starts_with('\\') {
+                    chars.nth(2);
+                } else if rest.chars().nth(1) == Some('\'') {
+                    chars.nth(1);
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => break,
+            _ => code.push(c),
+        }
+    }
+    code
+}
+
+// Returns the path relative to the code directory, so that the output doesn't depend on where the
+// floresta code is found. Note that the snippet paths are always reported as written in the book
+fn relative_path(code_dir: &str, path: &Path) -> String {
+    path.strip_prefix(code_dir)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+// Whether the snippet path could point outside of the directory it's resolved from, because it's
+// absolute or it has more `..` components than the directories before them
+fn escapes_code_dir(snippet_path: &str) -> bool {
+    let mut depth = 0;
+    for component in Path::new(snippet_path).components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return true,
+        }
+    }
+    false
+}
+
+// Function to validate the extracted file path and ensure it exists. Paths are looked up in the
+// `crates` directory first, and then in the root directory for workspace members outside of it.
+// If the file is not found, returns the paths that were tried
+fn validate_file_path(ctx: &CheckContext, snippet_path: &str) -> Result<PathBuf, Vec<PathBuf>> {
+    let code_dir = ctx.source_root();
+    let candidates = [
+        Path::new(code_dir).join("crates").join(snippet_path),
+        Path::new(code_dir).join(snippet_path),
+    ];
+
+    match candidates.iter().find(|path| ctx.is_code_file(path)) {
+        Some(path) => Ok(path.to_path_buf()),
+        None => Err(candidates.to_vec()),
+    }
+}
+
+// Returns the only source file matched by a snippet path with `*` or `?`, or none if it has no
+// glob chars or matches no file (so that it's reported as a missing path), and an error listing
+// the matched files if there are several
+fn resolve_glob_path<'a>(ctx: &'a CheckContext, pattern: &str) -> Result<Option<&'a str>, String> {
+    if !pattern.contains(['*', '?']) {
+        return Ok(None);
+    }
+    let mut matched: Vec<&str> = ctx
+        .source_files()
+        .iter()
+        .filter(|file| glob::glob_match(pattern, file))
+        .map(String::as_str)
+        .collect();
+    matched.sort();
+    match matched.as_slice() {
+        [] => Ok(None),
+        [file] => Ok(Some(file)),
+        files => Err(format!(
+            "the path `{pattern}` matches {} source files, {}; make it match only one",
+            files.len(),
+            files.join(", ")
+        )),
+    }
+}
+
+// Returns the Rust files of a directory snippet path (like `floresta-wire/src/p2p_wire/`), in name
+// order, and their content concatenated. The subdirectories are not included
+fn read_directory(
+    ctx: &CheckContext,
+    md_dir: &Path,
+    dir: &str,
+) -> Result<(Vec<PathBuf>, String), String> {
+    let mut files: Vec<PathBuf> = match dir.strip_prefix("./") {
+        Some(local_dir) => {
+            let local_dir = md_dir.join(local_dir.trim_end_matches('/'));
+            let overlay_files = ctx
+                .overlay
+                .keys()
+                .filter(|path| path.parent() == Some(local_dir.as_path()))
+                .cloned();
+            fs::read_dir(&local_dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.path())
+                        .filter(|path| path.is_file())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+                .into_iter()
+                .chain(overlay_files)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        }
+        None => ctx
+            .source_files()
+            .iter()
+            .filter(|file| {
+                file.strip_prefix(dir)
+                    .is_some_and(|name| !name.contains('/'))
+            })
+            .filter_map(|file| validate_file_path(ctx, file).ok())
+            .collect(),
+    };
+    files.retain(|file| file.extension().and_then(|e| e.to_str()) == Some("rs"));
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("no Rust files found in the directory {dir}"));
+    }
+
+    let mut contents = Vec::new();
+    for file in &files {
+        let read_start = Instant::now();
+        let bytes = match dir.starts_with("./") {
+            true => ctx
+                .read_local(file)
+                .map_err(|e| format!("Could not read {}: {e}", file.display())),
+            false => ctx.read_code(file).map_err(|e| e.to_string()),
+        }?;
+        ctx.record_read(file, bytes.len(), read_start.elapsed());
+        let content = String::from_utf8(bytes).map_err(|_| {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            format!("source file is not valid UTF-8: {dir}{name}")
+        })?;
+        let content: String = content
+            .split_inclusive('\n')
+            .skip(ctx.strip_header_lines)
+            .collect();
+        contents.push(content.trim_end_matches('\n').to_string());
+    }
+    Ok((files, contents.join("\n")))
+}
+
+// Checks that a `./` snippet path exists in the markdown file directory
+fn validate_local_path(
+    ctx: &CheckContext,
+    md_dir: &Path,
+    snippet_path: &str,
+) -> Result<PathBuf, Vec<PathBuf>> {
+    let path = md_dir.join(snippet_path.trim_start_matches("./"));
+    match path.is_file() || ctx.overlay.contains_key(&path) {
+        true => Ok(path),
+        false => Err(vec![path]),
+    }
+}
+
+// Returns the real path of the code file if it, or any of its parent directories inside the code
+// directory, is a symlink
+fn symlink_target(code_dir: &str, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(code_dir).ok()?;
+    let mut current = PathBuf::from(code_dir);
+    let through_symlink = relative.components().any(|component| {
+        current.push(component);
+        current.is_symlink()
+    });
+
+    if through_symlink {
+        fs::canonicalize(path).ok()
+    } else {
+        None
+    }
+}
+
+// Returns the resolved path of a source file that is outside of the directory once the symlinks
+// of both are resolved, or none if it is inside (or not on the filesystem, as an overlay file)
+fn resolved_outside(dir: &str, path: &Path) -> Option<PathBuf> {
+    let dir = fs::canonicalize(dir).ok()?;
+    let resolved = fs::canonicalize(path).ok()?;
+    match resolved.starts_with(dir) {
+        true => None,
+        false => Some(resolved),
+    }
+}
+
+// Returns the line as shown when mdBook hidden lines are visible
+fn unhide_line(line: &str) -> String {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    // Same rules as mdBook: `# ` and a lone `#` are hidden lines, and `##` is an escaped `#`.
+    // Any other leading `#`, as in `#[attribute]`, is part of the code
+    if let Some(escaped) = trimmed.strip_prefix("##") {
+        format!("{}#{}", indent, escaped)
+    } else if let Some(hidden) = trimmed.strip_prefix("# ") {
+        format!("{}{}", indent, hidden)
+    } else if trimmed == "#" {
+        String::new()
+    } else {
+        line.to_string()
+    }
+}
+
+// Which lines are skipped when comparing a snippet with the code
+#[derive(Clone, Copy)]
+enum Comments<'a> {
+    // Compare all the lines, including comments and empty lines
+    Keep,
+    // Skip empty lines and the lines starting with this comment prefix
+    Strip(&'a str),
+}
+
+// The line comment prefix of each snippet language, to which the `comment_prefix` setting adds
+const COMMENT_PREFIXES: &[(&str, &str)] = &[
+    ("rust", "//"),
+    ("c", "//"),
+    ("cpp", "//"),
+    ("go", "//"),
+    ("java", "//"),
+    ("javascript", "//"),
+    ("js", "//"),
+    ("bash", "#"),
+    ("sh", "#"),
+    ("shell", "#"),
+    ("python", "#"),
+    ("toml", "#"),
+    ("yaml", "#"),
+    ("sql", "--"),
+];
+
+// The snippet language given by a `snippetlang=` attribute in the fence info string
+fn snippet_language(info: &str) -> &str {
+    info.split([' ', ','])
+        .find_map(|attribute| attribute.strip_prefix("snippetlang="))
+        .unwrap_or("rust")
+}
+
+// Returns the comment prefix of the snippet language, which is Rust unless a `snippetlang=<lang>`
+// attribute is found in the fence info string after `rust` (or a `lang` directive is given). This
+// attribute takes precedence over `rust` for checking the snippet, while mdBook still highlights it
+// as Rust. The prefixes given as `lang=prefix` in `custom` take precedence over the built-in ones
+fn snippet_comment_prefix<'a>(language: &str, custom: &'a [String]) -> Result<&'a str, String> {
+    let custom = custom
+        .iter()
+        .rev()
+        .filter_map(|entry| entry.split_once('='));
+    custom
+        .chain(COMMENT_PREFIXES.iter().copied())
+        .find(|(name, _)| *name == language)
+        .map(|(_, prefix)| prefix)
+        .ok_or_else(|| format!("unknown snippetlang `{language}`"))
+}
+
+// The comparison options of a single snippet, given by a `snippet(...)` attribute in its fence info
+// string, like `rust,snippet(keep-comments,no-dedent)`. They are enabled on top of the settings
+#[derive(Default)]
+struct SnippetAttributes {
+    keep_comments: bool,
+    no_dedent: bool,
+    strip_trailing_comments: bool,
+    skip: bool,
+}
+
+// The directives of a snippet, given by the `# // <name>: <value>` lines right after its path line,
+// like `# // lines: 10-20` or `# // skip-comments: false`. The directive block ends at the first
+// line that is not a directive
+#[derive(Default)]
+struct SnippetDirectives<'a> {
+    lines: Option<(usize, usize)>,
+    anchors: Option<Vec<&'a str>>,
+    occurrence: Option<Occurrence>,
+    skip: Option<bool>,
+    lang: Option<&'a str>,
+    skip_comments: Option<bool>,
+    signature_only: Option<bool>,
+    // The lowest line similarity ratio with the code that passes the snippet, from 0 to 1
+    similarity: Option<f32>,
+    // The start of the SHA-256 hash of the code block, in lowercase hex digits
+    sha: Option<&'a str>,
+    // The number of directive lines, which are not part of the snippet
+    count: usize,
+}
+
+fn snippet_directives(snippet: &str) -> Result<SnippetDirectives<'_>, String> {
+    let directive_regex = Regex::new(
+        r"^# // (Path|Ref|lines|anchor|occurrence|skip|lang|skip-comments|similarity|sha|signature-only): *(.*?)\s*$",
+    )
+    .unwrap();
+    let mut directives = SnippetDirectives::default();
+    let mut names = Vec::new();
+
+    for line in snippet.lines() {
+        let Some(caps) = directive_regex.captures(line) else {
+            break;
+        };
+        let name = caps.get(1).unwrap().as_str();
+        let value = caps.get(2).unwrap().as_str();
+        if names.contains(&name) {
+            return Err(format!("the `{name}` directive is given twice"));
+        }
+        names.push(name);
+        let boolean = || match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(format!("`{name}` must be true or false, not `{value}`")),
+        };
+
+        match name {
+            "Path" | "Ref" => {
+                return Err(format!(
+                    "`{name}:` directive after the path line, which already gives the snippet path"
+                ))
+            }
+            "lines" => {
+                let range = value
+                    .split_once('-')
+                    .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)));
+                let range = range
+                    .ok_or_else(|| format!("`lines` must be a range like 10-20, not `{value}`"))?;
+                directives.lines = Some(range);
+            }
+            "anchor" => {
+                let anchors: Vec<&str> = value.split('+').collect();
+                if anchors.iter().any(|anchor| {
+                    anchor.is_empty() || !anchor.chars().all(|c| c.is_alphanumeric() || c == '_')
+                }) {
+                    return Err(format!(
+                        "`anchor` must be item names joined by `+`, not `{value}`"
+                    ));
+                }
+                directives.anchors = Some(anchors);
+            }
+            "occurrence" => {
+                let occurrence = match value {
+                    "first" => Some(Occurrence::Nth(1)),
+                    "last" => Some(Occurrence::Last),
+                    _ => value.parse().ok().filter(|n| *n > 0).map(Occurrence::Nth),
+                };
+                let occurrence = occurrence.ok_or_else(|| {
+                    format!("`occurrence` must be first, last or a number from 1, not `{value}`")
+                })?;
+                directives.occurrence = Some(occurrence);
+            }
+            "skip" => directives.skip = Some(boolean()?),
+            "skip-comments" => directives.skip_comments = Some(boolean()?),
+            "signature-only" => directives.signature_only = Some(boolean()?),
+            "similarity" => {
+                let similarity = value
+                    .parse()
+                    .ok()
+                    .filter(|ratio| (0.0..=1.0).contains(ratio));
+                let similarity = similarity.ok_or_else(|| {
+                    format!("`similarity` must be a ratio from 0 to 1, like 0.9, not `{value}`")
+                })?;
+                directives.similarity = Some(similarity);
+            }
+            "sha" => {
+                let valid = (7..=64).contains(&value.len())
+                    && value.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'));
+                if !valid {
+                    return Err(format!(
+                        "`sha` must be from 7 to 64 lowercase hex digits of a SHA-256 hash, not \
+                         `{value}`"
+                    ));
+                }
+                directives.sha = Some(value);
+            }
+            _ if value.is_empty() => return Err("`lang` must name a snippet language".to_string()),
+            _ => directives.lang = Some(value),
+        }
+        directives.count += 1;
+    }
+
+    if directives.lines.is_some() && directives.anchors.is_some() {
+        return Err("the `lines` and `anchor` directives can't be both given".to_string());
+    }
+    Ok(directives)
+}
+
+fn snippet_attributes(info: &str) -> Result<SnippetAttributes, String> {
+    let mut attributes = SnippetAttributes::default();
+    let Some(start) = info.find("snippet(") else {
+        return Ok(attributes);
+    };
+    let list = &info[start + "snippet(".len()..];
+    let list = list
+        .split_once(')')
+        .ok_or("unclosed `snippet(` attribute in the fence")?
+        .0;
+
+    for name in list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        match name {
+            "keep-comments" => attributes.keep_comments = true,
+            "no-dedent" => attributes.no_dedent = true,
+            "strip-trailing-comments" => attributes.strip_trailing_comments = true,
+            "skip" => attributes.skip = true,
+            _ => return Err(format!("unknown snippet attribute `{name}`")),
+        }
+    }
+    Ok(attributes)
+}
+
+// Removes the comments after the code of each line, outside of string literals. The whole line
+// comments are kept, as they are handled as the other comments
+fn strip_trailing_comments(code: &str, prefix: &str) -> String {
+    let stripped = code
+        .lines()
+        .map(|line| {
+            let mut in_string = false;
+            let mut escaped = false;
+            for (i, c) in line.char_indices() {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' if in_string => escaped = true,
+                    '"' => in_string = !in_string,
+                    _ if !in_string
+                        && line[i..].starts_with(prefix)
+                        && !line[..i].trim().is_empty() =>
+                    {
+                        return line[..i].trim_end();
+                    }
+                    _ => {}
+                }
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    match code.ends_with('\n') {
+        true => stripped + "\n",
+        false => stripped,
+    }
+}
+
+// Returns true for the lines that are compared, i.e., the lines that are not comments nor empty,
+// unless comments are kept
+fn is_code_line(line: &str, comments: Comments) -> bool {
+    match comments {
+        Comments::Keep => true,
+        Comments::Strip(prefix) => {
+            !line.trim_start().starts_with(prefix) && !line.trim().is_empty()
+        }
+    }
+}
+
+// Returns the existing file path that is closest to the missing one, if it's close enough to be a
+// typo (at most a third of the path characters differ)
+fn suggest_path<'a>(missing: &str, source_files: &'a [String]) -> Option<&'a str> {
+    let max_distance = missing.chars().count() / 3;
+
+    source_files
+        .iter()
+        .map(|path| (edit_distance(missing, path), path))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, path)| path.as_str())
+}
+
+// The Levenshtein distance between two strings, i.e., the minimum number of inserted, deleted or
+// replaced chars to turn one into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Distances from the current prefix of `a` to every prefix of `b`
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let replace = diagonal + usize::from(a_char != *b_char);
+            diagonal = distances[j + 1];
+            distances[j + 1] = replace.min(distances[j] + 1).min(diagonal + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+// Function to get the whole snippet, including hidden lines and excluding comments and empty lines
+fn strip_comments(code: &str, prefix: &str) -> String {
+    let unhidden = code.lines().map(unhide_line).collect::<Vec<_>>().join("\n");
+    code_lines(&unhidden, Comments::Strip(prefix))
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Returns the compared lines of the code, with their (0-based) index. Both the snippets and the
+// code blocks are cleaned with this, so their lines are counted alike
+fn code_lines<'a>(code: &'a str, comments: Comments<'a>) -> impl Iterator<Item = (usize, &'a str)> {
+    code.lines()
+        .enumerate()
+        .filter(move |(_, line)| is_code_line(line, comments))
+}
+
+// Function to get the whole snippet, including hidden lines, comments and inner empty lines
+fn unhide_lines(code: &str) -> String {
+    let lines: Vec<_> = code.lines().map(unhide_line).collect();
+    let first = lines.iter().position(|line| !line.trim().is_empty());
+    let last = lines.iter().rposition(|line| !line.trim().is_empty());
+
+    match (first, last) {
+        (Some(first), Some(last)) => lines[first..=last].join("\n"),
+        _ => String::new(),
+    }
+}
+
+// Function to print the differences between the documentation snippet and the actual code
+fn print_diff(doc_code: &str, real_code: &str, detect_moves: bool, source_lines: &[Option<usize>]) {
+    let diff = TextDiff::from_lines(doc_code, real_code); // Generate the diff
+    let changes: Vec<_> = diff.iter_all_changes().collect();
+    let moves = match detect_moves {
+        true => moved_runs(&changes),
+        false => Vec::new(),
+    };
+    // The move of each moved line, both where it was deleted and where it was inserted
+    let mut moved = vec![None; changes.len()];
+    for &(from, to, len) in &moves {
+        for j in 0..len {
+            moved[from + j] = Some((from, to, len));
+            moved[to + j] = Some((from, to, len));
+        }
+    }
+
+    // When a run of deleted lines is followed by as many inserted lines, we take each pair as a
+    // modified line, and store the index of the deleted line for the inserted one
+    let mut modified_from = vec![None; changes.len()];
+    let mut i = 0;
+    while i < changes.len() {
+        let deleted = changes[i..]
+            .iter()
+            .take_while(|change| change.tag() == ChangeTag::Delete)
+            .count();
+        let inserted = changes[i + deleted..]
+            .iter()
+            .take_while(|change| change.tag() == ChangeTag::Insert)
+            .count();
+
+        if deleted > 0
+            && deleted == inserted
+            && !moved[i..i + deleted * 2].iter().any(Option::is_some)
+        {
+            for j in i..i + deleted {
+                modified_from[j + deleted] = Some(j);
+            }
+        }
+        i += (deleted + inserted).max(1);
+    }
+
+    // Lines that only differ in trailing whitespace look identical, so we show that whitespace
+    let mut whitespace_only = vec![false; changes.len()];
+    for (i, from) in modified_from.iter().enumerate() {
+        if let Some(j) = *from {
+            let (old, new) = (changes[j].value(), changes[i].value());
+            if old.trim_end() == new.trim_end() {
+                whitespace_only[i] = true;
+                whitespace_only[j] = true;
+            }
+        }
+    }
+
+    // With the source lines of the code block, each line starts with its snippet line number, and its
+    // source file line number
+    let width = source_lines
+        .iter()
+        .flatten()
+        .copied()
+        .chain([doc_code.lines().count()])
+        .max()
+        .unwrap_or_default()
+        .to_string()
+        .len();
+    let gutter = |change: &similar::Change<&str>| {
+        if source_lines.is_empty() {
+            return String::new();
+        }
+        let snippet_line = change.old_index().map(|i| (i + 1).to_string());
+        let source_line = change
+            .new_index()
+            .and_then(|i| source_lines.get(i).copied().flatten())
+            .map(|line| line.to_string());
+        format!(
+            "{:>width$} {:>width$} ",
+            snippet_line.unwrap_or_default(),
+            source_line.unwrap_or_default()
+        )
+    };
+    let indent = match source_lines.is_empty() {
+        true => String::new(),
+        false => " ".repeat(width * 2 + 2),
+    };
+
+    for (i, change) in changes.iter().enumerate() {
+        // The moved lines are only shown where they are in the code, after a note of where they
+        // were in the snippet
+        if let Some((from, to, len)) = moved[i] {
+            let direction = match from < to {
+                true => ("below", "above"),
+                false => ("above", "below"),
+            };
+            let lines = match len {
+                1 => "1 line".to_string(),
+                _ => format!("{len} lines"),
+            };
+            if i == from {
+                println!(
+                    "{}",
+                    format!("{indent}  ({lines} moved {} in the code)", direction.0).warning()
+                );
+            }
+            if i == to {
+                println!(
+                    "{}",
+                    format!(
+                        "{indent}  ({lines} moved from {} in the snippet)",
+                        direction.1
+                    )
+                    .warning()
+                );
+            }
+            if change.tag() == ChangeTag::Insert {
+                print!("{}", format!("{}> {change}", gutter(change)).warning());
+            }
+            continue;
+        }
+        let line = match whitespace_only[i] {
+            true => show_trailing_whitespace(change.value()),
+            false => change.to_string(),
+        };
+        // Iterate through each change and format it visually
+        match change.tag() {
+            ChangeTag::Delete => {
+                print!("{}", format!("{}- {}", gutter(change), line).error()); // Deleted lines in red
+            }
+            ChangeTag::Insert => {
+                print!("{}", format!("{}+ {}", gutter(change), line).success());
+                // Added lines in green
+            }
+            ChangeTag::Equal => {
+                print!("{}", format!("{}  {}", gutter(change), line).plain()); // Unchanged lines in white
+            }
+        }
+
+        match modified_from[i] {
+            _ if whitespace_only[i] && change.tag() == ChangeTag::Insert => {
+                println!("{indent}{}", "  (trailing whitespace differs)".warning());
+            }
+            Some(j) => {
+                let (column, width) = differing_columns(changes[j].value(), change.value());
+                println!(
+                    "{indent}  {}{}",
+                    " ".repeat(column),
+                    "^".repeat(width).warning()
+                );
+            }
+            None => {}
+        }
+    }
+    println!(); // Add a blank line after printing the diff
+}
+
+// Returns the source file line of each line of the compared code block, found in order in the code
+// regions as the first line with the same trimmed content, as the comment and blank lines may have
+// been skipped
+fn block_source_lines(
+    block: &str,
+    code_content: &str,
+    regions: &[(usize, usize)],
+) -> Vec<Option<usize>> {
+    let code_lines: Vec<(usize, &str)> = regions
+        .iter()
+        .flat_map(|(start, end)| {
+            let lines = code_content.lines().enumerate().take(*end).skip(start - 1);
+            lines.map(|(i, line)| (i + 1, line))
+        })
+        .collect();
+    let mut next = 0;
+    block
+        .lines()
+        .map(|line| {
+            let found = code_lines[next..]
+                .iter()
+                .position(|(_, code_line)| code_line.trim() == line.trim())?;
+            let (line_number, _) = code_lines[next + found];
+            next += found + 1;
+            Some(line_number)
+        })
+        .collect()
+}
+
+// Returns the runs of deleted lines that are inserted elsewhere as they are, as the indexes of
+// their first deleted and inserted change, and their number of lines. A moved run can be a part of
+// longer deleted and inserted runs, as when a moved line is next to a changed one. The runs of only
+// empty lines or closing delimiters are not taken as moved, as they are likely found in both by
+// chance
+fn moved_runs(changes: &[similar::Change<&str>]) -> Vec<(usize, usize, usize)> {
+    let runs = |tag: ChangeTag| {
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for (i, change) in changes.iter().enumerate() {
+            match runs.last_mut() {
+                _ if change.tag() != tag => {}
+                Some((start, len)) if *start + *len == i => *len += 1,
+                _ => runs.push((i, 1)),
+            }
+        }
+        runs
+    };
+    let lines = |start: usize, len: usize| {
+        changes[start..start + len]
+            .iter()
+            .map(|change| change.value())
+    };
+
+    let inserted = runs(ChangeTag::Insert);
+    let mut used = vec![false; changes.len()];
+    let mut moves = Vec::new();
+    // The longest parts of each deleted run are searched first, so that a moved run is not split
+    for (start, len) in runs(ChangeTag::Delete) {
+        for width in (1..=len).rev() {
+            for from in start..=start + len - width {
+                let trivial = lines(from, width).all(|line| {
+                    line.trim()
+                        .chars()
+                        .all(|c| matches!(c, '}' | ')' | ']' | ';' | ','))
+                });
+                if trivial || used[from..from + width].contains(&true) {
+                    continue;
+                }
+                let found = inserted
+                    .iter()
+                    .filter(|(_, inserted_len)| *inserted_len >= width)
+                    .flat_map(|&(to, inserted_len)| to..=to + inserted_len - width)
+                    .find(|&to| {
+                        !used[to..to + width].contains(&true)
+                            && lines(from, width).eq(lines(to, width))
+                    });
+                if let Some(to) = found {
+                    used[from..from + width].fill(true);
+                    used[to..to + width].fill(true);
+                    moves.push((from, to, width));
+                }
+            }
+        }
+    }
+    moves
+}
+
+// Prints only the deleted and inserted lines of the diff, with a `@@ path:line @@` header before
+// each run of them. The line is counted from the block start, so skipped comments are not included
+fn print_changed_lines(diff: &SnippetDiff) {
+    let text_diff = TextDiff::from_lines(&diff.snippet, &diff.block);
+    // The block line of the next change, and whether the previous change was equal
+    let mut block_line = diff.start_line;
+    let mut after_equal = true;
+
+    for change in text_diff.iter_all_changes() {
+        if change.tag() != ChangeTag::Equal && after_equal {
+            println!("{}", format!("@@ {}:{} @@", diff.path, block_line).accent());
+        }
+        match change.tag() {
+            ChangeTag::Delete => print!("{}", format!("- {change}").error()),
+            ChangeTag::Insert => print!("{}", format!("+ {change}").success()),
+            ChangeTag::Equal => {}
+        }
+        if change.tag() != ChangeTag::Delete {
+            block_line += 1;
+        }
+        after_equal = change.tag() == ChangeTag::Equal;
+    }
+    println!();
+}
+
+// Returns the first column where the two lines differ, and how many columns differ until the
+// common end of both lines (at least one, to point at the end of the shorter line). Columns are
+// counted in display width, so that the carets line up with wide characters like CJK ones
+fn differing_columns(old: &str, new: &str) -> (usize, usize) {
+    let old: Vec<char> = old.trim_end_matches(['\n', '\r']).chars().collect();
+    let new: Vec<char> = new.trim_end_matches(['\n', '\r']).chars().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let width = |chars: &[char]| chars.iter().map(|c| char_width(*c)).sum::<usize>();
+    let differing =
+        width(&old[prefix..old.len() - suffix]).max(width(&new[prefix..new.len() - suffix]));
+    (width(&new[..prefix]), differing.max(1))
+}
+
+// Returns the columns a character takes in a terminal: two for East Asian wide and fullwidth
+// characters (and most emoji), none for combining marks and zero width characters, and one for the
+// rest
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x200B..=0x200F
+        | 0x20D0..=0x20FF
+        | 0xFE00..=0xFE0F
+        | 0xFE20..=0xFE2F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x2FFFD
+        | 0x30000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+// Returns the line with its trailing spaces shown as `·` and tabs as `→`, ending with a newline
+fn show_trailing_whitespace(line: &str) -> String {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let content = line.trim_end();
+    let whitespace: String = line[content.len()..]
+        .chars()
+        .map(|c| match c {
+            '\t' => '→',
+            _ => '·',
+        })
+        .collect();
+    format!("{content}{whitespace}\n")
+}
+
+// Whether a code block is returned with its common indentation removed, or as found in the file
+#[derive(Clone, Copy)]
+enum Indent {
+    Remove,
+    // For the `check_indent` mode, which only removes one indentation level
+    Keep,
+}
+
+// Extract the block of code from the file based on the snippet. Returns the first and last line
+// numbers of the block, and the block itself. With `Comments::Keep` all the lines are counted, as
+// the snippet will include comments and empty lines too
+fn extract_clean_block(
+    file_content: &str,
+    snippet: &str,
+    comments: Comments,
+    indent: Indent,
+    placeholder: &str,
+    occurrence: Option<Occurrence>,
+    max_candidates: usize,
+) -> Result<Option<(usize, usize, String)>, String> {
+    let snippet_lines = snippet.lines().count();
+    let mut meaningful_lines = snippet.lines().skip_while(|line| line.trim().is_empty());
+    // Get the first meaningful line
+    let Some(first_line) = meaningful_lines.next() else {
+        return Ok(None);
+    };
+    let second_line = meaningful_lines.next();
+
+    let file_lines: Vec<_> = file_content.lines().collect();
+    let candidates: Vec<_> = (0..file_lines.len())
+        .filter(|i| line_matches(first_line.trim(), file_lines[*i].trim(), placeholder))
+        .collect();
+
+    // As the first line can be quite generic, we start at the candidate that is followed by the
+    // second snippet line. If there's none, we start at the only candidate anyway (to show the
+    // diff), and if several are left, the snippet is ambiguous, as the wrong one could be taken. An
+    // explicit occurrence is taken from all the candidates instead. There can be at most
+    // `max_candidates`, so that a very common first line (like `}`) isn't followed from each of its
+    // occurrences
+    if occurrence.is_none() && max_candidates > 0 && candidates.len() > max_candidates {
+        return Err(format!(
+            "too many candidate matches for ambiguous first line (at {} lines of the file, more \
+             than the {max_candidates} of `--max-candidates`); use an anchor or line range",
+            candidates.len()
+        ));
+    }
+    let followed: Vec<_> = candidates
+        .iter()
+        .filter(|i| {
+            second_line.is_none_or(|second_line| {
+                file_lines[*i + 1..]
+                    .iter()
+                    .find(|line| is_code_line(line, comments))
+                    .is_some_and(|line| line_matches(second_line.trim(), line.trim(), placeholder))
+            })
+        })
+        .collect();
+    let start = match occurrence {
+        Some(Occurrence::Nth(n)) => candidates.get(n - 1),
+        Some(Occurrence::Last) => candidates.last(),
+        None if followed.len() > 1 => {
+            let lines: Vec<String> = followed.iter().map(|i| (*i + 1).to_string()).collect();
+            return Err(format!(
+                "the first snippet lines are at lines {} of the file; use an anchor, a line \
+                 range or an `occurrence` directive to choose one",
+                lines.join(", ")
+            ));
+        }
+        None if followed.is_empty() && candidates.len() > 1 => {
+            let lines: Vec<String> = candidates.iter().map(|i| (i + 1).to_string()).collect();
+            return Err(format!(
+                "the first snippet line is at lines {} of the file, and none is followed by the \
+                 second one; use an anchor, a line range or an `occurrence` directive to choose \
+                 one",
+                lines.join(", ")
+            ));
+        }
+        None => followed.first().copied().or(candidates.first()),
+    };
+    let Some(&start) = start else {
+        return Ok(None);
+    };
+
+    // Take as many lines as the snippet has, counting them as the snippet ones were counted. The
+    // block starts at its first code line, which is after the matched line if that one is not
+    // counted as code, like a comment or empty line matched by a placeholder
+    let block_lines: Vec<_> = code_lines(file_content, comments)
+        .skip_while(|(i, _)| *i < start)
+        .take(snippet_lines)
+        .collect();
+    let (Some((first, _)), Some((last, _))) = (block_lines.first(), block_lines.last()) else {
+        return Ok(None);
+    };
+    // The code lines start at number 1
+    let (block_start_line, block_end_line) = (first + 1, last + 1);
+    debug_assert!(block_start_line > 0, "the blocks start at line 1");
+    let block = block_lines
+        .iter()
+        .map(|(_, line)| *line)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let block = match indent {
+        Indent::Remove => dedent(&block),
+        Indent::Keep => block,
+    };
+    Ok(Some((block_start_line, block_end_line, block)))
+}
+
+// Extract the blocks of code from each of the (start, end) file line regions, concatenated
+fn extract_regions_block(
+    file_content: &str,
+    regions: &[(usize, usize)],
+    comments: Comments,
+) -> Option<(usize, usize, String)> {
+    let blocks = regions
+        .iter()
+        .map(|(start, end)| extract_range_block(file_content, *start, *end, comments))
+        .collect::<Option<Vec<_>>>()?;
+
+    let start = regions.first()?.0;
+    let end = regions.last()?.1;
+    Some((start, end, blocks.join("\n")))
+}
+
+// Extract the block of code between the `start` and `end` file lines, both included
+fn extract_range_block(
+    file_content: &str,
+    start: usize,
+    end: usize,
+    comments: Comments,
+) -> Option<String> {
+    let block = code_lines(file_content, comments)
+        .skip_while(|(i, _)| *i + 1 < start)
+        .take_while(|(i, _)| *i < end)
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // Return the block if anything is captured
+    if block.is_empty() {
+        None
+    } else {
+        Some(block)
+    }
+}
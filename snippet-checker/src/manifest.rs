@@ -0,0 +1,61 @@
+// A snippet manifest gives a short ID to each source location, so that snippets can reference it
+// with a `# // Ref: <id>` line instead of the full path. Each ID is a table with the `path`, and
+// either an `anchor` (or several, joined with `+`) or a line range in `lines`:
+//
+// [handshake]
+// path = "floresta-wire/src/p2p_wire/peer.rs"
+// anchor = "handshake"
+use crate::toml::{self, Value};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// The manifest that is read by default, from the current directory
+pub const MANIFEST_FILE: &str = "snippets.toml";
+
+// The snippet path of each ID, written as in the `# // Path:` lines
+pub type Refs = BTreeMap<String, String>;
+
+pub fn read(path: &Path) -> Result<Refs, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read snippet manifest {}: {e}", path.display()))?;
+    let invalid =
+        |message: String| format!("Invalid snippet manifest {}, {message}", path.display());
+    let entries = toml::parse(&content).map_err(invalid)?;
+
+    // The path, anchor and line range of each ID
+    let mut locations: BTreeMap<String, [Option<String>; 3]> = BTreeMap::new();
+    for (key, value) in entries {
+        let (id, field) = key
+            .rsplit_once('.')
+            .ok_or_else(|| invalid(format!("`{key}` is not in a snippet ID table")))?;
+        let index = match field {
+            "path" => 0,
+            "anchor" => 1,
+            "lines" => 2,
+            _ => return Err(invalid(format!("unknown key `{field}` for snippet `{id}`"))),
+        };
+        let Value::String(value) = value else {
+            return Err(invalid(format!("`{key}` must be a string")));
+        };
+        locations.entry(id.to_string()).or_default()[index] = Some(value);
+    }
+
+    let mut refs = Refs::new();
+    for (id, [path, anchor, lines]) in locations {
+        let path = path.ok_or_else(|| invalid(format!("snippet `{id}` has no `path`")))?;
+        let snippet_path = match (anchor, lines) {
+            (Some(_), Some(_)) => {
+                return Err(invalid(format!(
+                    "snippet `{id}` can't have both an `anchor` and `lines`"
+                )))
+            }
+            (Some(anchor), None) => format!("{path}#{anchor}"),
+            (None, Some(lines)) => format!("{path}:{lines}"),
+            (None, None) => path,
+        };
+        refs.insert(id, snippet_path);
+    }
+    Ok(refs)
+}
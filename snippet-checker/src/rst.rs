@@ -0,0 +1,75 @@
+// Support for the reStructuredText files of Sphinx docs, whose Rust snippets are written as
+// `.. code-block:: rust` directives. They are turned into markdown fences, keeping the line
+// numbers, so that the snippets are checked as the markdown ones
+use std::path::Path;
+
+pub fn is_rst(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("rst")
+}
+
+// Returns the content with each `.. code-block:: rust` directive replaced by a `rust` fence. The
+// directive and option lines are blanked, with the opening fence on the line before the code, and
+// the closing fence on the blank line after it, so that the other lines don't move
+pub fn to_markdown(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut markdown: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let directive_indent = indentation(line);
+        if line.trim() != ".. code-block:: rust" {
+            markdown.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        // The code starts at the first line more indented than the directive, after its options
+        let mut start = i + 1;
+        while start < lines.len()
+            && (lines[start].trim().is_empty() || lines[start].trim_start().starts_with(':'))
+        {
+            start += 1;
+        }
+        let code_indent = match lines.get(start) {
+            Some(line) if indentation(line) > directive_indent => indentation(line),
+            _ => {
+                markdown.push(line.to_string());
+                i += 1;
+                continue;
+            }
+        };
+
+        // The code ends before the first non blank line that is less indented
+        let mut end = start;
+        for (j, line) in lines.iter().enumerate().skip(start) {
+            if !line.trim().is_empty() {
+                if indentation(line) < code_indent {
+                    break;
+                }
+                end = j;
+            }
+        }
+
+        markdown.extend((i..start - 1).map(|_| String::new()));
+        markdown.push("```rust".to_string());
+        for line in &lines[start..=end] {
+            markdown.push(line.get(code_indent..).unwrap_or("").to_string());
+        }
+        markdown.push("```".to_string());
+        // The closing fence takes the place of the blank line after the code, if there's one
+        i = match lines.get(end + 1) {
+            Some(line) if line.trim().is_empty() => end + 2,
+            _ => end + 1,
+        };
+    }
+
+    let mut markdown = markdown.join("\n");
+    if content.ends_with('\n') {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
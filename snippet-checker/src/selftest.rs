@@ -0,0 +1,946 @@
+// A quick end to end check of the snippet checker, using the bundled fixtures instead of the
+// floresta code, so it can run without cloning floresta
+use crate::archive::{write_tar, Archive};
+use crate::comparator::{Comparator, StripComments};
+use crate::doc_tests;
+use crate::error::CheckError;
+use crate::fix::{fixed_markdown, update_hashes};
+use crate::rst;
+use crate::source::SourceResolver;
+use crate::theme::Themed;
+use crate::{
+    bold_red, check_files, conflicting_snippet_paths, get_md_snippets_diff, overlapping_snippets,
+    print_file_check, skips_report, snippets_report, tap_report, walk_book, CheckContext,
+    FileCheck, FileSkip, MissingPaths,
+};
+use colored::*;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+// Source files of the fixture code, relative to the root directory
+const CODE_FIXTURES: &[(&str, &[u8])] = &[
+    (
+        "crates/sample/src/lib.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/lib.rs"),
+    ),
+    (
+        "crates/sample/src/accents.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/accents.rs"),
+    ),
+    (
+        "crates/sample/src/attributes.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/attributes.rs"),
+    ),
+    (
+        "crates/sample/src/bom.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/bom.rs"),
+    ),
+    (
+        "crates/sample/src/documented.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/documented.rs"),
+    ),
+    (
+        "crates/sample/src/features.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/features.rs"),
+    ),
+    (
+        "crates/sample/src/greetings.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/greetings.rs"),
+    ),
+    // Not valid UTF-8, as a source file with a Latin-1 string
+    (
+        "crates/sample/src/latin1.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/latin1.rs"),
+    ),
+    (
+        "crates/sample/src/handlers.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/handlers.rs"),
+    ),
+    (
+        "crates/sample/src/imports.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/imports.rs"),
+    ),
+    (
+        "crates/sample/src/peer/message.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/peer/message.rs"),
+    ),
+    (
+        "crates/sample/src/licensed/first.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/licensed/first.rs"),
+    ),
+    (
+        "crates/sample/src/licensed/second.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/licensed/second.rs"),
+    ),
+    (
+        "crates/sample/src/modules.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/modules.rs"),
+    ),
+    (
+        "crates/sample/src/peer/mod.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/peer/mod.rs"),
+    ),
+    (
+        "crates/sample/src/wrapping.rs",
+        include_bytes!("../fixtures/code/crates/sample/src/wrapping.rs"),
+    ),
+    (
+        "tools/src/main.rs",
+        include_bytes!("../fixtures/code/tools/src/main.rs"),
+    ),
+    (
+        "tools/Cargo.toml",
+        include_bytes!("../fixtures/code/tools/Cargo.toml"),
+    ),
+    (
+        "tools/run.sh",
+        include_bytes!("../fixtures/code/tools/run.sh"),
+    ),
+    (
+        "tools/schema.sql",
+        include_bytes!("../fixtures/code/tools/schema.sql"),
+    ),
+];
+
+// Source files next to the fixture chapters, relative to the book directory
+const BOOK_CODE_FIXTURES: &[(&str, &[u8])] = &[(
+    "examples/greeting.rs",
+    include_bytes!("../fixtures/book/examples/greeting.rs"),
+)];
+
+// A markdown file of the fixture book, and the expected outcome of checking it
+struct Fixture {
+    name: &'static str,
+    content: &'static str,
+    // Changes the default settings before checking this fixture
+    setup: fn(&mut CheckContext),
+    expected: &'static str,
+}
+
+fn defaults(_: &mut CheckContext) {}
+
+// A custom comparator, taking each run of whitespace as a single space
+pub(crate) struct CollapseWhitespace;
+
+impl Comparator for CollapseWhitespace {
+    fn normalize(&self, code: &str) -> String {
+        StripComments("//")
+            .normalize(code)
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// A clean chapter and a seeded diff, and the fixtures of the checks below. The results of each
+// fixture, like its diffs and issues, are checked by the unit tests
+const BOOK_FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "matching.md",
+        content: include_str!("../fixtures/book/matching.md"),
+        setup: defaults,
+        expected: "ok",
+    },
+    Fixture {
+        name: "local_path.md",
+        content: include_str!("../fixtures/book/local_path.md"),
+        setup: defaults,
+        expected: "ok",
+    },
+    Fixture {
+        name: "includes.md",
+        content: include_str!("../fixtures/book/includes.md"),
+        setup: |ctx| ctx.check_includes = true,
+        expected: "no snippets",
+    },
+    Fixture {
+        name: "placeholder_diff.md",
+        content: include_str!("../fixtures/book/placeholder_diff.md"),
+        setup: defaults,
+        expected: "diff",
+    },
+    Fixture {
+        name: "directory.md",
+        content: include_str!("../fixtures/book/directory.md"),
+        setup: defaults,
+        expected: "ok",
+    },
+    Fixture {
+        name: "comparator.md",
+        content: include_str!("../fixtures/book/comparator.md"),
+        setup: |ctx| {
+            ctx.keep_comments = true;
+            ctx.comparator = Box::new(CollapseWhitespace);
+        },
+        expected: "ok",
+    },
+    Fixture {
+        name: "diff.md",
+        content: include_str!("../fixtures/book/diff.md"),
+        setup: defaults,
+        expected: "diff",
+    },
+    Fixture {
+        name: "no_snippets.md",
+        content: include_str!("../fixtures/book/no_snippets.md"),
+        setup: defaults,
+        expected: "no snippets",
+    },
+    Fixture {
+        name: "missing_path.md",
+        content: include_str!("../fixtures/book/missing_path.md"),
+        setup: defaults,
+        expected: "error",
+    },
+    Fixture {
+        name: "signature_diff.md",
+        content: include_str!("../fixtures/book/signature_diff.md"),
+        setup: defaults,
+        expected: "diff",
+    },
+    Fixture {
+        name: "sha.md",
+        content: include_str!("../fixtures/book/sha.md"),
+        setup: defaults,
+        expected: "ok",
+    },
+];
+
+// Fixture chapters fixed with `fixed_markdown`, and whether fixing changes them. The fixed
+// chapters must match the code, also when they use CRLF line endings, which are kept
+const FIX_FIXTURES: &[(&str, &str, bool)] = &[
+    ("diff.md", include_str!("../fixtures/book/diff.md"), true),
+    (
+        "directives_moved.md",
+        include_str!("../fixtures/book/directives_moved.md"),
+        true,
+    ),
+    (
+        "matching.md",
+        include_str!("../fixtures/book/matching.md"),
+        false,
+    ),
+    (
+        "list_fences.md",
+        include_str!("../fixtures/book/list_fences.md"),
+        false,
+    ),
+    (
+        "signature_diff.md",
+        include_str!("../fixtures/book/signature_diff.md"),
+        true,
+    ),
+    (
+        "blockquote_diff.md",
+        include_str!("../fixtures/book/blockquote_diff.md"),
+        true,
+    ),
+    (
+        "comments_diff.md",
+        include_str!("../fixtures/book/comments_diff.md"),
+        true,
+    ),
+    (
+        "hidden_diff.md",
+        include_str!("../fixtures/book/hidden_diff.md"),
+        true,
+    ),
+];
+
+// The book fixtures that are also checked from an in-memory overlay of the fixture files, with
+// code and book directories that don't exist
+const OVERLAY_FIXTURES: &[&str] = &[
+    "matching.md",
+    "diff.md",
+    "local_path.md",
+    "includes.md",
+    "directory.md",
+];
+
+// The snippets whose code block is not found, each followed by a matching snippet that is still
+// checked, with the reason of their issue
+const BLOCK_NOT_FOUND: &[(&str, &str)] = &[
+    (
+        "```rust\n# // Path: sample/src/lib.rs:6-900\npub struct PeerTracker {\n```",
+        "its line range 6-900 is out of bounds (the file has",
+    ),
+    (
+        "```rust\n# // Path: sample/src/lib.rs#NoSuchItem\npub struct PeerTracker {\n```",
+        "it has no item named by the anchor `NoSuchItem`",
+    ),
+    (
+        "```rust\n# // Path: sample/src/lib.rs\npub struct PeerRegistry {\n```",
+        "no code lines match the snippet",
+    ),
+];
+const MATCHING_SNIPPET: &str =
+    "```rust\n# // Path: sample/src/lib.rs\npub fn remove_peer(&mut self, id: u32) -> Option<String> {\n    self.peers.remove(&id)\n}\n```";
+
+// A source backend whose files exist, but can't be read
+struct UnreadableSource;
+
+impl SourceResolver for UnreadableSource {
+    fn exists(&self, _: &str) -> Result<bool, String> {
+        Ok(true)
+    }
+
+    fn read(&self, _: &str) -> Result<Vec<u8>, String> {
+        Err("permission denied".to_string())
+    }
+
+    fn files(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// A source backend whose reads panic, as a bug in the checker would
+struct PanickingSource;
+
+impl SourceResolver for PanickingSource {
+    fn exists(&self, _: &str) -> Result<bool, String> {
+        Ok(true)
+    }
+
+    fn read(&self, rel: &str) -> Result<Vec<u8>, String> {
+        panic!("reading {rel}")
+    }
+
+    fn files(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+fn crlf(content: &str) -> String {
+    content.replace('\n', "\r\n")
+}
+
+fn outcome(check: &FileCheck) -> &'static str {
+    match check.diff {
+        Some(true) => "diff",
+        _ if check.failed() => "error",
+        Some(false) => "ok",
+        None => "no snippets",
+    }
+}
+
+pub fn run() -> Result<(), CheckError> {
+    // Write the fixture code to a temporary directory, which will act as the floresta root, and
+    // the code next to the chapters to another one, which will act as the book directory
+    let code_dir = env::temp_dir().join(format!("snippet-checker-selftest-{}", process::id()));
+    let book_dir = env::temp_dir().join(format!("snippet-checker-selftest-book-{}", process::id()));
+    let _removed = [FixtureDir(code_dir.clone()), FixtureDir(book_dir.clone())];
+    for (dir, fixtures) in [(&code_dir, CODE_FIXTURES), (&book_dir, BOOK_CODE_FIXTURES)] {
+        for (path, content) in fixtures {
+            let file_path = dir.join(path);
+            let parent = file_path.parent().unwrap();
+            fs::create_dir_all(parent).map_err(fixture_error(parent))?;
+            fs::write(&file_path, content).map_err(fixture_error(&file_path))?;
+        }
+    }
+
+    let mut failures = 0;
+    for fixture in BOOK_FIXTURES {
+        let mut ctx = CheckContext::new(code_dir.display().to_string());
+        (fixture.setup)(&mut ctx);
+
+        let path = Path::new(fixture.name);
+        let content = match (rst::is_rst(path), doc_tests::is_rust_file(path)) {
+            (true, _) => rst::to_markdown(fixture.content),
+            (_, true) => doc_tests::to_markdown(fixture.content),
+            _ => fixture.content.to_string(),
+        };
+        let check = get_md_snippets_diff(content, &book_dir, &ctx)?;
+        print_file_check(fixture.name, &check, &ctx);
+
+        if outcome(&check) == fixture.expected {
+            println!("  {}", format!("expected {}", fixture.expected).success());
+        } else {
+            failures += 1;
+            println!(
+                "  {}",
+                bold_red(&format!(
+                    "expected {}, found {}",
+                    fixture.expected,
+                    outcome(&check)
+                ))
+            );
+        }
+    }
+    let memory_dir =
+        env::temp_dir().join(format!("snippet-checker-selftest-memory-{}", process::id()));
+    let (memory_code_dir, memory_book_dir) = (memory_dir.join("code"), memory_dir.join("book"));
+    let overlay = [
+        (&memory_code_dir, CODE_FIXTURES),
+        (&memory_book_dir, BOOK_CODE_FIXTURES),
+    ]
+    .into_iter()
+    .flat_map(|(dir, fixtures)| {
+        fixtures.iter().filter_map(|(path, content)| {
+            Some((dir.join(path), String::from_utf8(content.to_vec()).ok()?))
+        })
+    });
+    let overlay: HashMap<PathBuf, String> = overlay.collect();
+    for name in OVERLAY_FIXTURES {
+        let fixture = BOOK_FIXTURES
+            .iter()
+            .find(|fixture| fixture.name == *name)
+            .unwrap();
+        let mut ctx = CheckContext::new(memory_code_dir.display().to_string());
+        (fixture.setup)(&mut ctx);
+        ctx.overlay = overlay.clone();
+
+        let content = fixture.content.to_string();
+        let check = get_md_snippets_diff(content, &memory_book_dir, &ctx)?;
+        if outcome(&check) == fixture.expected {
+            println!(
+                "{name} (from memory) ... {}",
+                format!("expected {}", fixture.expected).success()
+            );
+        } else {
+            failures += 1;
+            println!(
+                "{name} (from memory) ... {}",
+                bold_red(&format!(
+                    "expected {}, found {}",
+                    fixture.expected,
+                    outcome(&check)
+                ))
+            );
+        }
+    }
+    // The stale hash is updated in a copy of its fixture, which then passes
+    let ctx = CheckContext::new(code_dir.display().to_string());
+    let stale_path = book_dir.join("sha_stale.md");
+    let stale_error = fixture_error(&stale_path);
+    fs::write(&stale_path, include_str!("../fixtures/book/sha_stale.md")).map_err(stale_error)?;
+    let content = fs::read_to_string(&stale_path).map_err(fixture_error(&stale_path))?;
+    let check = get_md_snippets_diff(content, &book_dir, &ctx)?;
+    update_hashes(&stale_path, &check)?;
+    let content = fs::read_to_string(&stale_path).map_err(fixture_error(&stale_path))?;
+    let check = get_md_snippets_diff(content, &book_dir, &ctx)?;
+    fs::remove_file(&stale_path).map_err(fixture_error(&stale_path))?;
+    if outcome(&check) == "ok" {
+        println!(
+            "sha_stale.md (updated) ... {}",
+            "updated as expected".success()
+        );
+    } else {
+        failures += 1;
+        println!(
+            "sha_stale.md (updated) ... {}",
+            bold_red("not updated as expected")
+        );
+    }
+    // The files checked on several threads get the same checks as on one, in their order
+    let stressed: Vec<&Fixture> = BOOK_FIXTURES
+        .iter()
+        .filter(|fixture| ["diff.md", "missing_path.md", "sha.md"].contains(&fixture.name))
+        .collect();
+    let paths: Vec<PathBuf> = (0..64)
+        .map(|i| book_dir.join(format!("stress_{i}.md")))
+        .collect();
+    let files: Vec<(&Path, &str)> = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (path.as_path(), stressed[i % stressed.len()].content))
+        .collect();
+    let summarize = |checks: Vec<Result<(FileCheck, Duration), String>>| {
+        checks
+            .into_iter()
+            .map(|checked| match checked {
+                Ok((check, _)) => {
+                    let diffs = check.diffs.iter().map(|diff| &diff.block);
+                    format!("{} {:?}", outcome(&check), diffs.collect::<Vec<_>>())
+                }
+                Err(e) => e,
+            })
+            .collect::<Vec<_>>()
+    };
+    let single = summarize(check_files(&files, &ctx, 1));
+    let multiple = summarize(check_files(&files, &ctx, 8));
+    let ordered = single
+        .iter()
+        .enumerate()
+        .all(|(i, summary)| summary.starts_with(stressed[i % stressed.len()].expected));
+    if single == multiple && ordered {
+        println!(
+            "{} files on 8 threads ... {}",
+            files.len(),
+            "checked as expected".success()
+        );
+    } else {
+        failures += 1;
+        println!(
+            "{} files on 8 threads ... {}",
+            files.len(),
+            bold_red("not checked as expected")
+        );
+    }
+    // A panic while checking a file only fails that file, telling which one
+    let mut panic_ctx = CheckContext::new(code_dir.display().to_string());
+    panic_ctx.source = Some(Box::new(PanickingSource));
+    let panicking = book_dir.join("panicking.md");
+    let no_snippets = book_dir.join("no_snippets.md");
+    let files: [(&Path, &str); 2] = [
+        (
+            &panicking,
+            "```rust\n# // Path: sample/src/lib.rs\nuse std::fmt;\n```",
+        ),
+        (&no_snippets, "# No Snippets"),
+    ];
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let checks = check_files(&files, &panic_ctx, 2);
+    panic::set_hook(hook);
+    match checks.as_slice() {
+        [Err(e), Ok(_)]
+            if e.contains("panicking.md") && e.contains("reading crates/sample/src/lib.rs") =>
+        {
+            println!("panicking check ... {}", "failed as expected".success());
+        }
+        _ => {
+            failures += 1;
+            println!("panicking check ... {}", bold_red("not failed as expected"));
+        }
+    }
+    // A code block that is not found only fails its snippet, with an issue
+    for (md, reason) in BLOCK_NOT_FOUND {
+        let ctx = CheckContext::new(code_dir.display().to_string());
+        let md = format!("{md}\n\n{MATCHING_SNIPPET}");
+        let found = get_md_snippets_diff(md.clone(), &book_dir, &ctx);
+        let name = format!(
+            "{:?} (block not found)",
+            md.lines().nth(1).unwrap_or_default()
+        );
+        let expected = format!("could not find the code block in sample/src/lib.rs, as {reason}");
+        match found {
+            Ok(check)
+                if check.issues.len() == 1
+                    && check.issues[0].index == 0
+                    && check.issues[0].message.starts_with(&expected)
+                    && check.verified.len() == 1
+                    && check.verified[0].index == 1 =>
+            {
+                println!("{name} ... {}", "failed as expected".success());
+            }
+            _ => {
+                failures += 1;
+                println!("{name} ... {}", bold_red("not failed as expected"));
+            }
+        }
+    }
+    let mut ctx = CheckContext::new(String::new());
+    ctx.source = Some(Box::new(UnreadableSource));
+    let md = "```rust\n# // Path: sample/src/lib.rs\nuse std::collections::HashMap;\n```";
+    match get_md_snippets_diff(md.to_string(), &book_dir, &ctx) {
+        Err(CheckError::Io { path, .. }) if path.ends_with("sample/src/lib.rs") => {
+            println!(
+                "unreadable source file ... {}",
+                "failed as expected".success()
+            );
+        }
+        _ => {
+            failures += 1;
+            println!(
+                "unreadable source file ... {}",
+                bold_red("not failed as expected")
+            );
+        }
+    }
+    for (name, content, changes) in FIX_FIXTURES {
+        let ctx = CheckContext::new(code_dir.display().to_string());
+        for (line_endings, content) in [("LF", content.to_string()), ("CRLF", crlf(content))] {
+            let (fixed, _) = fixed_markdown(&content, &book_dir, &ctx)?;
+            let check = get_md_snippets_diff(fixed.clone(), &book_dir, &ctx)?;
+            let kept_endings = line_endings == "LF" || !fixed.replace("\r\n", "").contains('\n');
+
+            let name = format!("{name} (fixed, with {line_endings} line endings)");
+            if (fixed != content) == *changes && outcome(&check) == "ok" && kept_endings {
+                println!("{name} ... {}", "fixed as expected".success());
+            } else {
+                failures += 1;
+                println!("{name} ... {}", bold_red("not fixed as expected"));
+            }
+        }
+    }
+    // The fixed snippets keep their hidden lines, identation and comments
+    let ctx = CheckContext::new(code_dir.display().to_string());
+    let fixed_files = [
+        (
+            "hidden_diff.md",
+            include_str!("../fixtures/book/hidden_diff.md"),
+            include_str!("../fixtures/book/hidden_diff_fixed.md"),
+        ),
+        (
+            "comments_diff.md",
+            include_str!("../fixtures/book/comments_diff.md"),
+            include_str!("../fixtures/book/comments_diff_fixed.md"),
+        ),
+    ];
+    for (name, content, expected) in fixed_files {
+        let (fixed, _) = fixed_markdown(content, &book_dir, &ctx)?;
+        if fixed == expected {
+            println!("{name} (fixed lines) ... {}", "kept as expected".success());
+        } else {
+            failures += 1;
+            println!(
+                "{name} (fixed lines) ... {}",
+                bold_red(&format!("fixed as {fixed:?}"))
+            );
+        }
+    }
+    // The tar archives written for the bundles are read back, even with long paths, and their
+    // snippets checked as with the fixture archives
+    let mut files: Vec<(String, Vec<u8>)> = CODE_FIXTURES
+        .iter()
+        .map(|(path, content)| (path.to_string(), content.to_vec()))
+        .collect();
+    files.push((
+        format!("crates/{}long.rs", "nested/".repeat(15)),
+        b"fn long() {}\n".to_vec(),
+    ));
+    files.push(("bundle.toml".to_string(), b"books = []\n".to_vec()));
+    let written =
+        Archive::parse(Path::new("bundle.tar"), &write_tar(&files)).map_err(CheckError::Failed)?;
+    let read_back = written.entries().count() == files.len()
+        && files
+            .iter()
+            .all(|(path, content)| written.read(path).as_ref() == Ok(content));
+    let mut ctx = CheckContext::new(String::new());
+    ctx.source = Some(Box::new(written));
+    let content = include_str!("../fixtures/book/similarity.md");
+    let check = get_md_snippets_diff(content.to_string(), &book_dir, &ctx)?;
+    if read_back && outcome(&check) == "ok" {
+        println!("bundle.tar (written) ... {}", "read as expected".success());
+    } else {
+        failures += 1;
+        println!(
+            "bundle.tar (written) ... {}",
+            bold_red("not read as expected")
+        );
+    }
+    // The snippets with the same content are only found after checking the book
+    let ctx = CheckContext::new(code_dir.display().to_string());
+    let content = include_str!("../fixtures/book/same_content.md");
+    let check = get_md_snippets_diff(content.to_string(), &book_dir, &ctx)?;
+    let conflicts = conflicting_snippet_paths(&[("same_content.md".to_string(), check)]);
+    if conflicts.len() == 1 && conflicts[0].1.len() == 2 {
+        println!(
+            "same_content.md (conflicting paths) ... {}",
+            "found as expected".success()
+        );
+    } else {
+        failures += 1;
+        println!(
+            "same_content.md (conflicting paths) ... {}",
+            bold_red(&format!("found {conflicts:?}"))
+        );
+    }
+    // The stale line range and anchor only fail their snippets, naming their range and anchor
+    let content = include_str!("../fixtures/book/stale_range.md");
+    let check = get_md_snippets_diff(content.to_string(), &book_dir, &ctx)?;
+    let issues: Vec<(usize, &str)> = check
+        .issues
+        .iter()
+        .map(|issue| (issue.index, issue.message.as_str()))
+        .collect();
+    let verified: Vec<usize> = check.verified.iter().map(|snippet| snippet.index).collect();
+    let expected = [
+        (
+            1,
+            "could not find the code block in sample/src/lib.rs, as its line range 60-64 is out \
+             of bounds (the file has 52 lines)",
+        ),
+        (
+            2,
+            "could not find the code block in sample/src/lib.rs, as it has no item named by the \
+             anchor `forget_peer`",
+        ),
+    ];
+    if issues == expected && verified == [0, 3] && check.diffs.is_empty() {
+        println!(
+            "stale_range.md (stale ranges) ... {}",
+            "found as expected".success()
+        );
+    } else {
+        failures += 1;
+        println!(
+            "stale_range.md (stale ranges) ... {}",
+            bold_red(&format!("found {issues:?}, verified {verified:?}"))
+        );
+    }
+    // The snippets that differ are still counted, so that accepting them in the baseline doesn't
+    // fail the declared snippet count
+    let content = include_str!("../fixtures/book/snippet_count_diff.md");
+    let check = get_md_snippets_diff(content.to_string(), &book_dir, &ctx)?;
+    if check.checked_snippets() == 2 && !check.snippet_count_mismatch() {
+        println!(
+            "snippet_count_diff.md (snippet count) ... {}",
+            "counted as expected".success()
+        );
+    } else {
+        failures += 1;
+        println!(
+            "snippet_count_diff.md (snippet count) ... {}",
+            bold_red(&format!("counted {} snippets", check.checked_snippets()))
+        );
+    }
+    // The overlapping snippets are also only found after checking the book
+    let content = include_str!("../fixtures/book/overlap.md");
+    let check = get_md_snippets_diff(content.to_string(), &book_dir, &ctx)?;
+    let overlaps = overlapping_snippets(&[("overlap.md".to_string(), check)]);
+    let expected = "sample/src/lib.rs: overlap.md#0 (lines 12-17) and overlap.md#1 (lines 13-16)";
+    if overlaps == [expected] {
+        println!(
+            "overlap.md (overlapping snippets) ... {}",
+            "found as expected".success()
+        );
+    } else {
+        failures += 1;
+        println!(
+            "overlap.md (overlapping snippets) ... {}",
+            bold_red(&format!("found {overlaps:?}"))
+        );
+    }
+    // The skipped files, snippets and fences are listed by category after checking the book
+    let mut skips_ctx = CheckContext::new(code_dir.display().to_string());
+    skips_ctx.explain_skips = true;
+    skips_ctx.missing_paths = MissingPaths::Skip;
+    let content = include_str!("../fixtures/book/skips.md");
+    let check = get_md_snippets_diff(content.to_string(), &book_dir, &skips_ctx)?;
+    let report = skips_report(
+        &[("drafts.md".to_string(), FileSkip::Excluded)],
+        &[("skips.md".to_string(), check)],
+    );
+    let expected = [
+        (
+            "Excluded by the include, exclude or ignore file patterns",
+            "drafts.md",
+        ),
+        (
+            "Skipped by a `skip` directive or fence attribute",
+            "skips.md#0 (sample/src/lib.rs)",
+        ),
+        (
+            "Missing paths skipped with --missing-paths=skip",
+            "skips.md#1 (sample/src/gone.rs)",
+        ),
+        ("Rust fences without a snippet header", "skips.md:16"),
+    ];
+    let found: Vec<_> = report
+        .iter()
+        .map(|(reason, skipped)| (*reason, skipped.join(", ")))
+        .collect();
+    if found
+        .iter()
+        .map(|(reason, skipped)| (*reason, skipped.as_str()))
+        .eq(expected)
+    {
+        println!(
+            "skips.md (skips report) ... {}",
+            "listed as expected".success()
+        );
+    } else {
+        failures += 1;
+        println!(
+            "skips.md (skips report) ... {}",
+            bold_red(&format!("listed {found:?}"))
+        );
+    }
+    // The JSON report has an entry for each snippet, with the changes of the diffs
+    let checks = [
+        ("diff.md", include_str!("../fixtures/book/diff.md")),
+        ("matching.md", include_str!("../fixtures/book/matching.md")),
+        ("no_snippets.md", "# No Snippets\n"),
+        (
+            "stale_range.md",
+            include_str!("../fixtures/book/stale_range.md"),
+        ),
+        (
+            "missing_path.md",
+            include_str!("../fixtures/book/missing_path.md"),
+        ),
+    ];
+    let mut checked = Vec::new();
+    for (name, content) in checks {
+        let check = get_md_snippets_diff(content.to_string(), &book_dir, &ctx)?;
+        checked.push((name.to_string(), check));
+    }
+    let report = snippets_report(&checked).to_string();
+    let expected = [
+        r#"{"md":"diff.md","index":0,"path":"sample/src/lib.rs","source":"crates/sample/src/lib.rs","block_start_line":20,"status":"diff","message":null,"#,
+        r#"{"tag":"delete","line":"    if self.peers.len() > self.max_peers {"}"#,
+        r#"{"tag":"insert","line":"    if self.peers.len() >= self.max_peers {"}"#,
+        r#"{"md":"matching.md","index":0,"path":"sample/src/lib.rs","source":"crates/sample/src/lib.rs","block_start_line":20,"status":"ok","message":null}"#,
+        r#"{"md":"no_snippets.md","index":null,"path":null,"source":null,"block_start_line":null,"status":"no_snippets","message":null}"#,
+        r#"{"md":"stale_range.md","index":0,"path":"sample/src/lib.rs","source":"crates/sample/src/lib.rs","block_start_line":30,"status":"ok","message":null}"#,
+        r#"{"md":"stale_range.md","index":1,"path":"sample/src/lib.rs","source":null,"block_start_line":null,"status":"issue","message":"could not find the code block in sample/src/lib.rs, as its line range 60-64 is out of bounds (the file has 52 lines)"}"#,
+        r#"{"md":"missing_path.md","index":0,"path":"sample/scr/lib.rs","source":null,"block_start_line":null,"status":"missing","message":"file path does not exist, tried crates/sample/scr/lib.rs, sample/scr/lib.rs"}"#,
+    ];
+    if expected.iter().all(|entry| report.contains(entry)) {
+        println!("JSON report ... {}", "written as expected".success());
+    } else {
+        failures += 1;
+        println!(
+            "JSON report ... {}",
+            bold_red(&format!("written as {report}"))
+        );
+    }
+    // The TAP report has a test for each snippet, after the version and plan lines
+    for (name, content, expected) in [
+        (
+            "similarity.md",
+            include_str!("../fixtures/book/similarity.md"),
+            "ok 1 - similarity.md snippet 0 (sample/src/lib.rs)",
+        ),
+        (
+            "similarity_low.md",
+            include_str!("../fixtures/book/similarity_low.md"),
+            "not ok 1 - similarity_low.md snippet 0 (sample/src/lib.rs)",
+        ),
+    ] {
+        let check = get_md_snippets_diff(content.to_string(), &book_dir, &ctx)?;
+        let report = tap_report([(name, &check)].into_iter(), 3);
+        if report[..3] == ["TAP version 13", "1..1", expected] {
+            println!(
+                "{name} (TAP report) ... {}",
+                "reported as expected".success()
+            );
+        } else {
+            failures += 1;
+            println!(
+                "{name} (TAP report) ... {}",
+                bold_red(&format!("reported as {report:?}"))
+            );
+        }
+    }
+    failures += check_walk_errors(&book_dir)?;
+    #[cfg(unix)]
+    {
+        failures += check_symlink_escape(&code_dir, &book_dir)?;
+    }
+
+    if failures == 0 {
+        println!("\nSelf-test: {}", "OK".success());
+        Ok(())
+    } else {
+        println!("\nSelf-test: {}", "FAILED".bold().error());
+        Err(CheckError::Failed(format!(
+            "{failures} self-test fixtures did not give the expected result"
+        )))
+    }
+}
+
+// A temporary fixture directory, which is removed when dropped, also if the self-test stops early
+struct FixtureDir(PathBuf);
+
+impl Drop for FixtureDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// The error of a fixture file or directory that could not be written, read or removed
+fn fixture_error(path: &Path) -> impl FnOnce(io::Error) -> CheckError + '_ {
+    move |e| CheckError::Io {
+        path: path.to_path_buf(),
+        message: format!("Could not set up the fixture {}: {e}", path.display()),
+    }
+}
+
+// Checks that a snippet path with a symlink to a file outside of the code directory is refused,
+// while a symlink inside of it is only noted, returning the number of failures
+#[cfg(unix)]
+fn check_symlink_escape(code_dir: &Path, book_dir: &Path) -> Result<usize, CheckError> {
+    let sample = code_dir.join("crates/sample/src");
+    let outside = book_dir.join("examples/greeting.rs");
+    let (outside_link, alias) = (sample.join("outside.rs"), sample.join("alias.rs"));
+    std::os::unix::fs::symlink(&outside, &outside_link).map_err(fixture_error(&outside_link))?;
+    std::os::unix::fs::symlink(sample.join("handlers.rs"), &alias)
+        .map_err(fixture_error(&alias))?;
+
+    let ctx = CheckContext::new(code_dir.display().to_string());
+    let content = include_str!("../fixtures/book/symlink_escape.md");
+    let check = get_md_snippets_diff(content.to_string(), book_dir, &ctx)?;
+    let expected = format!(
+        "refusing to read sample/src/outside.rs, as it resolves to {} outside of the code directory",
+        fs::canonicalize(&outside)
+            .map_err(fixture_error(&outside))?
+            .display()
+    );
+    let issues: Vec<(usize, &str)> = check
+        .issues
+        .iter()
+        .map(|issue| (issue.index, issue.message.as_str()))
+        .collect();
+    let verified: Vec<usize> = check.verified.iter().map(|snippet| snippet.index).collect();
+    let name = "symlink_escape.md (symlinks)";
+    if issues == [(0, expected.as_str())] && verified == [1] && check.notes.len() == 1 {
+        println!("{name} ... {}", "refused as expected".success());
+        return Ok(0);
+    }
+    println!(
+        "{name} ... {}",
+        bold_red(&format!("found {issues:?}, verified {verified:?}"))
+    );
+    Ok(1)
+}
+
+// Checks that the book walk reports a missing directory, and a subdirectory that can't be read by
+// the current user (which root still reads), returning the number of failures
+fn check_walk_errors(book_dir: &Path) -> Result<usize, CheckError> {
+    let mut failures = 0;
+    let missing = book_dir.join("missing");
+    let unreadable = book_dir.join("unreadable");
+    let chapter = unreadable.join("chapter.md");
+    fs::create_dir_all(&unreadable).map_err(fixture_error(&unreadable))?;
+    fs::write(&chapter, "# Unreadable\n").map_err(fixture_error(&chapter))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&unreadable, fs::Permissions::from_mode(0o000))
+            .map_err(fixture_error(&unreadable))?;
+    }
+    let expected = [
+        ("missing directory", missing.as_path(), 1),
+        (
+            "unreadable directory",
+            book_dir,
+            fs::read_dir(&unreadable).is_err() as usize,
+        ),
+    ];
+
+    for (name, dir, expected) in expected {
+        let (_, errors) = walk_book(&dir.display().to_string());
+        if errors.len() == expected {
+            println!(
+                "{name} ... {}",
+                format!("expected {expected} walk errors").success()
+            );
+        } else {
+            failures += 1;
+            println!(
+                "{name} ... {}",
+                bold_red(&format!(
+                    "expected {expected} walk errors, found {}",
+                    errors.len()
+                ))
+            );
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&unreadable, fs::Permissions::from_mode(0o755))
+            .map_err(fixture_error(&unreadable))?;
+    }
+    Ok(failures)
+}
@@ -0,0 +1,59 @@
+// The backends the source files are read from, like the code directory or a code archive. The
+// paths are relative to the floresta root directory, and written with `/`
+use walkdir::WalkDir;
+
+use std::fs;
+use std::path::Path;
+
+pub trait SourceResolver {
+    // Whether there is a file at this path
+    fn exists(&self, rel: &str) -> Result<bool, String>;
+
+    // Reads the file at this path. Its bytes are returned as they are, so that a source file that
+    // is not text only fails the snippets that show it
+    fn read(&self, rel: &str) -> Result<Vec<u8>, String>;
+
+    // Returns the paths of all the source files
+    fn files(&self) -> Vec<String>;
+}
+
+// Reads the source files from a directory, which is the default backend
+pub struct FsResolver<'a> {
+    dir: &'a Path,
+}
+
+impl<'a> FsResolver<'a> {
+    pub fn new(dir: &'a str) -> Self {
+        FsResolver {
+            dir: Path::new(dir),
+        }
+    }
+}
+
+impl SourceResolver for FsResolver<'_> {
+    fn exists(&self, rel: &str) -> Result<bool, String> {
+        let path = self.dir.join(rel);
+        let exists = path.try_exists().map_err(|e| e.to_string())?;
+        Ok(exists && path.is_file())
+    }
+
+    fn read(&self, rel: &str) -> Result<Vec<u8>, String> {
+        fs::read(self.dir.join(rel)).map_err(|e| e.to_string())
+    }
+
+    fn files(&self) -> Vec<String> {
+        WalkDir::new(self.dir)
+            .into_iter()
+            .filter_entry(|entry| {
+                let name = entry.file_name().to_str().unwrap_or_default();
+                name != "target" && name != ".git"
+            })
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| {
+                let path = entry.path().strip_prefix(self.dir).ok()?;
+                Some(path.display().to_string())
+            })
+            .collect()
+    }
+}
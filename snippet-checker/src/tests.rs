@@ -0,0 +1,1549 @@
+// The book fixtures, checked with the code fixtures for their specific result: the code lines of
+// the matching snippets, the changed lines of the diffs and the messages of the issues. The
+// helpers they are checked with, and those of the output formats, are checked after them
+use crate::archive::{write_tar, Archive};
+use crate::checker::{diff_lines, DiffLine};
+use crate::comparator::StripComments;
+use crate::config::{check_min_version, Config};
+use crate::doc_tests;
+use crate::dotenv;
+use crate::fix::fixed_markdown;
+use crate::inflate::{inflate, inflate_limited};
+use crate::rst;
+use crate::selftest::CollapseWhitespace;
+use crate::sha256;
+use crate::toml::{self, Value};
+use crate::{
+    annotated_markdown, block_source_lines, differing_columns, extract_clean_block,
+    get_md_snippets_diff, github_summary, largest_diffs, moved_runs, renamed_path_line,
+    CheckContext, Comments, FileCheck, Indent, LeadingAttributes, MissingPaths,
+};
+use similar::TextDiff;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+const CODE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/code");
+const BOOK_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/book");
+
+// Checks a book fixture after changing the default settings
+fn check_with(name: &str, setup: impl FnOnce(&mut CheckContext)) -> FileCheck {
+    let mut ctx = CheckContext::new(CODE_DIR.to_string());
+    setup(&mut ctx);
+
+    let path = Path::new(BOOK_DIR).join(name);
+    let content = fs::read_to_string(&path).unwrap();
+    let content = match (rst::is_rst(&path), doc_tests::is_rust_file(&path)) {
+        (true, _) => rst::to_markdown(&content),
+        (_, true) => doc_tests::to_markdown(&content),
+        _ => content,
+    };
+    get_md_snippets_diff(content, Path::new(BOOK_DIR), &ctx).unwrap()
+}
+
+fn check(name: &str) -> FileCheck {
+    check_with(name, |_| {})
+}
+
+fn archive(name: &str) -> Archive {
+    let path = Path::new(CODE_DIR).parent().unwrap().join(name);
+    Archive::parse(&path, &fs::read(&path).unwrap()).unwrap()
+}
+
+// The index and the first and last code lines of each matching snippet
+fn verified(check: &FileCheck) -> Vec<(usize, usize, usize)> {
+    check
+        .verified
+        .iter()
+        .map(|snippet| (snippet.index, snippet.start_line, snippet.end_line))
+        .collect()
+}
+
+// The same, for a file whose snippets all match
+fn matched(check: &FileCheck) -> Vec<(usize, usize, usize)> {
+    assert!(
+        !check.failed(),
+        "expected all the snippets to match, found the issues {:?} and the diffs {:?}",
+        issues(check),
+        changes(check)
+    );
+    verified(check)
+}
+
+fn issues(check: &FileCheck) -> Vec<(usize, &str)> {
+    check
+        .issues
+        .iter()
+        .map(|issue| (issue.index, issue.message.as_str()))
+        .collect()
+}
+
+fn warnings(check: &FileCheck) -> Vec<(usize, &str)> {
+    check
+        .warnings
+        .iter()
+        .map(|warning| (warning.index, warning.message.as_str()))
+        .collect()
+}
+
+// The index of each differing snippet, and its deleted (snippet) and inserted (code) lines, with
+// a `-` or `+` before them
+fn changes(check: &FileCheck) -> Vec<(usize, Vec<String>)> {
+    check
+        .diffs
+        .iter()
+        .map(|diff| {
+            let lines = diff_lines(&diff.snippet, &diff.block)
+                .into_iter()
+                .filter_map(|line| match line {
+                    DiffLine::Equal(_) => None,
+                    DiffLine::Delete(line) => Some(format!("-{line}")),
+                    DiffLine::Insert(line) => Some(format!("+{line}")),
+                })
+                .collect();
+            (diff.index, lines)
+        })
+        .collect()
+}
+
+fn missing(check: &FileCheck) -> Vec<(usize, &str)> {
+    check
+        .missing
+        .iter()
+        .map(|missing| (missing.index, missing.path.as_str()))
+        .collect()
+}
+
+#[test]
+fn matching_snippets() {
+    // The first snippet ends at the last captured line, after skipping a comment and a blank line
+    let lines = [(0, 20, 28), (1, 6, 9), (2, 12, 32)];
+    assert_eq!(matched(&check("matching.md")), lines);
+    let from_archive = check_with("matching.md", |ctx| {
+        ctx.source = Some(Box::new(archive("code.tar.gz")))
+    });
+    assert_eq!(matched(&from_archive), lines);
+
+    assert_eq!(matched(&check("hidden_lines.md")), [(0, 49, 52)]);
+    // The comments between the attributes are skipped, but counted in the line numbers
+    assert_eq!(matched(&check("attributes.md")), [(0, 2, 10)]);
+    assert_eq!(matched(&check("bom.md")), [(0, 1, 1)]);
+    assert_eq!(matched(&check("tilde_fences.md")), [(0, 41, 46)]);
+    // Both fences are found, at their indentation in the list items
+    assert_eq!(
+        matched(&check("list_fences.md")),
+        [(0, 12, 17), (1, 20, 28)]
+    );
+    assert_eq!(matched(&check("snippetlang.md")), [(0, 5, 7)]);
+    assert_eq!(matched(&check("local_path.md")), [(0, 4, 6)]);
+    assert_eq!(matched(&check("same_content.md")), [(0, 3, 3), (1, 3, 3)]);
+    assert_eq!(matched(&check("hidden_diff_fixed.md")), [(0, 11, 17)]);
+    assert_eq!(matched(&check("sha.md")), [(0, 6, 9)]);
+}
+
+#[test]
+fn snippets_outside_the_crates() {
+    assert_eq!(matched(&check("outside_crates.md")), [(0, 3, 6)]);
+    let from_archive = check_with("outside_crates.md", |ctx| {
+        ctx.source = Some(Box::new(archive("code.zip")))
+    });
+    assert_eq!(matched(&from_archive), [(0, 3, 6)]);
+}
+
+#[test]
+fn max_snippet_lines() {
+    let message = |lines| {
+        format!("has {lines} lines, more than the 5 allowed; split it or show only an anchor")
+    };
+    let check = check_with("matching.md", |ctx| ctx.max_snippet_lines = 5);
+    assert_eq!(matched(&check).len(), 3);
+    let (seven, nine) = (message(7), message(9));
+    assert_eq!(warnings(&check), [(0, seven.as_str()), (2, nine.as_str())]);
+
+    let check = check_with("matching.md", |ctx| {
+        ctx.max_snippet_lines = 5;
+        ctx.strict = true;
+    });
+    assert_eq!(issues(&check), [(0, seven.as_str()), (2, nine.as_str())]);
+}
+
+#[test]
+fn ambiguous_first_lines() {
+    // The first line repeats, but only one is followed by the second line
+    assert_eq!(matched(&check("ambiguous_first_line.md")), [(0, 42, 45)]);
+
+    let check_ambiguous = check("ambiguous_snippet.md");
+    assert_eq!(
+        issues(&check_ambiguous),
+        [(
+            0,
+            "the first snippet lines are at lines 3, 10 of the file; use an anchor, a line range \
+             or an `occurrence` directive to choose one"
+        )]
+    );
+    assert_eq!(verified(&check_ambiguous), [(1, 10, 13)]);
+
+    assert_eq!(
+        issues(&check("ambiguous_unfollowed.md")),
+        [(
+            0,
+            "the first snippet line is at lines 3, 10 of the file, and none is followed by the \
+             second one; use an anchor, a line range or an `occurrence` directive to choose one"
+        )]
+    );
+
+    let check = check_with("ambiguous_first_line.md", |ctx| ctx.max_candidates = 1);
+    assert_eq!(
+        issues(&check),
+        [(
+            0,
+            "too many candidate matches for ambiguous first line (at 2 lines of the file, more \
+             than the 1 of `--max-candidates`); use an anchor or line range"
+        )]
+    );
+}
+
+#[test]
+fn moved_lines() {
+    let check = check_with("moved_lines.md", |ctx| ctx.detect_moves = true);
+    assert_eq!(
+        changes(&check),
+        [(
+            0,
+            vec![
+                "-    self.peers.insert(id, address);".to_string(),
+                "+    self.peers.insert(id, address);".to_string(),
+            ]
+        )]
+    );
+}
+
+#[test]
+fn overlapping_snippets_match() {
+    let lines = [(0, 12, 17), (1, 13, 16), (2, 19, 20)];
+    assert_eq!(matched(&check("overlap.md")), lines);
+}
+
+#[test]
+fn skipped_snippets() {
+    let check = check_with("skips.md", |ctx| ctx.missing_paths = MissingPaths::Skip);
+    assert_eq!(matched(&check), [(2, 3, 3)]);
+    assert_eq!(check.skipped.len(), 2);
+}
+
+#[test]
+fn stale_ranges_and_anchors() {
+    let check = check("stale_range.md");
+    assert_eq!(
+        issues(&check),
+        [
+            (
+                1,
+                "could not find the code block in sample/src/lib.rs, as its line range 60-64 is \
+                 out of bounds (the file has 52 lines)"
+            ),
+            (
+                2,
+                "could not find the code block in sample/src/lib.rs, as it has no item named by \
+                 the anchor `forget_peer`"
+            ),
+        ]
+    );
+    assert_eq!(verified(&check), [(0, 30, 32), (3, 41, 46)]);
+}
+
+#[test]
+fn fence_languages() {
+    assert_eq!(check("fence_languages.md").diff, None);
+    let check_languages = check_with("fence_languages.md", |ctx| {
+        ctx.fence_languages = vec!["toml".to_string(), "sh".to_string()]
+    });
+    assert_eq!(matched(&check_languages), [(0, 6, 9), (1, 5, 5)]);
+
+    let check = check_with("fence_languages_diff.md", |ctx| {
+        ctx.fence_languages = vec!["sh".to_string()]
+    });
+    assert_eq!(
+        changes(&check),
+        [(
+            0,
+            vec![
+                "-NETWORK=${NETWORK:-mainnet}".to_string(),
+                "+NETWORK=${NETWORK:-signet}".to_string(),
+            ]
+        )]
+    );
+}
+
+#[test]
+fn fixed_fence_languages() {
+    // The `#` lines of the other languages are comments, not hidden lines, so they are kept
+    let mut ctx = CheckContext::new(CODE_DIR.to_string());
+    ctx.fence_languages = vec!["sh".to_string()];
+    let content = fs::read_to_string(Path::new(BOOK_DIR).join("fence_languages_diff.md")).unwrap();
+    let (fixed, _) = fixed_markdown(&content, Path::new(BOOK_DIR), &ctx).unwrap();
+    assert_eq!(fixed, content.replace("mainnet", "signet"));
+}
+
+#[test]
+fn fixed_snippet_lines() {
+    let ctx = CheckContext::new(CODE_DIR.to_string());
+    let fix = |content: &str| {
+        let (fixed, _) = fixed_markdown(content, Path::new(BOOK_DIR), &ctx).unwrap();
+        fixed
+    };
+    // The unchanged lines are kept as they were written, as these hidden lines
+    let content = [
+        "```rust",
+        "# // Path: sample/src/lib.rs:11-17",
+        "# impl PeerTracker {",
+        "#     pub fn new(max_peers: usize) -> Self {",
+        "        PeerTracker {",
+        "            peers: HashMap::new(),",
+        "            max: max_peers,",
+        "        }",
+        "    }",
+        "```\n",
+    ]
+    .join("\n");
+    assert_eq!(
+        fix(&content),
+        content.replace("max: max_peers", "max_peers")
+    );
+
+    // The snippet comments are kept, but not the comments of the code, which were not compared
+    for name in ["hidden_diff.md", "comments_diff.md"] {
+        let content = fs::read_to_string(Path::new(BOOK_DIR).join(name)).unwrap();
+        let fixed_name = name.replace(".md", "_fixed.md");
+        let expected = fs::read_to_string(Path::new(BOOK_DIR).join(fixed_name)).unwrap();
+        assert_eq!(fix(&content), expected, "{name} is not fixed as expected");
+    }
+}
+
+#[test]
+fn toml_snippets() {
+    assert_eq!(matched(&check("toml_snippet.md")), [(0, 6, 9)]);
+    let check = check("toml_snippet_diff.md");
+    assert_eq!(
+        check.diffs[0].hint.as_deref(),
+        Some(
+            "key `dependencies.tokio.version` differs, \"1.37\" in the snippet and \"1.38\" in \
+             the code"
+        )
+    );
+}
+
+#[test]
+fn comment_prefixes() {
+    let check_lua = check_with("comment_prefix.md", |ctx| {
+        ctx.comment_prefixes = vec!["lua=--".to_string()]
+    });
+    assert_eq!(matched(&check_lua), [(0, 3, 7), (1, 3, 7)]);
+
+    let check = check("comment_prefix.md");
+    assert_eq!(issues(&check), [(1, "unknown snippetlang `lua`")]);
+    assert_eq!(verified(&check), [(0, 3, 7)]);
+}
+
+#[test]
+fn sorted_use_lines() {
+    let check_sorted = check_with("sort_use.md", |ctx| ctx.sort_use = true);
+    assert_eq!(matched(&check_sorted), [(0, 3, 7)]);
+
+    assert_eq!(
+        issues(&check("sort_use.md")),
+        [(
+            0,
+            "captured 2 of 4 expected lines, as sample/src/imports.rs has no more code after line 7"
+        )]
+    );
+}
+
+#[test]
+fn whole_file_snippets() {
+    let message = "compared with all the code of sample/src/documented.rs, which is much longer \
+                   than the snippet";
+    let check_default = check("whole_file.md");
+    assert_eq!(matched(&check_default), [(0, 25, 35)]);
+    assert_eq!(warnings(&check_default), [(0, message)]);
+
+    let check = check_with("whole_file.md", |ctx| ctx.strict = true);
+    assert_eq!(issues(&check), [(0, message)]);
+}
+
+#[test]
+fn snippet_counts() {
+    let check_count = check("snippet_count.md");
+    assert!(check_count.snippet_count_mismatch());
+    assert_eq!(check_count.expected_snippets, Some(2));
+    assert_eq!(check_count.checked_snippets(), 1);
+
+    let check = check("snippet_count_diff.md");
+    assert!(!check.snippet_count_mismatch());
+    assert_eq!(
+        changes(&check),
+        [(
+            1,
+            vec![
+                "-    self.peers.remove(id)".to_string(),
+                "+    self.peers.remove(&id)".to_string(),
+            ]
+        )]
+    );
+}
+
+#[test]
+fn includes() {
+    let check_includes = check_with("includes.md", |ctx| ctx.check_includes = true);
+    assert_eq!(check_includes.diff, None);
+    assert!(check_includes.broken_includes.is_empty());
+
+    let check = check_with("includes_broken.md", |ctx| ctx.check_includes = true);
+    let broken: Vec<_> = check
+        .broken_includes
+        .iter()
+        .map(|include| (include.line, include.message.as_str()))
+        .collect();
+    assert_eq!(
+        broken,
+        [
+            (
+                7,
+                "could not read examples/farewell.rs: No such file or directory (os error 2)"
+            ),
+            (
+                11,
+                "lines 4-60 out of bounds (7 lines) in examples/greeting.rs"
+            ),
+            (15, "anchor `farewell` not found in examples/greeting.rs"),
+        ]
+    );
+}
+
+#[test]
+fn wide_chars_diff() {
+    assert_eq!(
+        changes(&check("wide_chars.md")),
+        [(
+            0,
+            vec![
+                "-        \"zh\" => \"你好，朋友\",".to_string(),
+                "+        \"zh\" => \"你好，世界\",".to_string(),
+            ]
+        )]
+    );
+}
+
+#[test]
+fn placeholders() {
+    assert_eq!(matched(&check("placeholder.md")), [(0, 3, 9)]);
+    let check_empty = check_with("placeholder.md", |ctx| ctx.placeholder = String::new());
+    assert_eq!(
+        changes(&check_empty),
+        [(
+            0,
+            vec![
+                "-        \"ja\" => /* ... */,".to_string(),
+                "-        \"zh\" => \"你好，/* ... */\",".to_string(),
+                "+        \"ja\" => \"こんにちは、世界\",".to_string(),
+                "+        \"zh\" => \"你好，世界\",".to_string(),
+            ]
+        )]
+    );
+    assert_eq!(
+        changes(&check("placeholder_diff.md")),
+        [(
+            0,
+            vec![
+                "-        _ => \"Hello, everyone\",".to_string(),
+                "+        _ => \"Hello, world\",".to_string(),
+            ]
+        )]
+    );
+}
+
+#[test]
+fn placeholder_lines() {
+    let check_allowed = check_with("allow_placeholder.md", |ctx| ctx.allow_placeholder = true);
+    assert_eq!(matched(&check_allowed), [(0, 20, 28), (1, 34, 39)]);
+    let check_default = check("allow_placeholder.md");
+    // Without the setting, the placeholder macros are compared as code
+    let changed = changes(&check_default);
+    assert_eq!(
+        changed[0],
+        (
+            0,
+            vec![
+                "-    todo!()".to_string(),
+                "-}".to_string(),
+                "+    if self.peers.len() >= self.max_peers {".to_string(),
+                "+        return false;".to_string(),
+            ]
+        )
+    );
+    assert_eq!(
+        changed[1],
+        (
+            1,
+            vec![
+                "-    unimplemented!()".to_string(),
+                "+    match self.peers.get(&id) {".to_string(),
+                "+        Some(address) => address,".to_string(),
+                "+        None => \"unknown\",".to_string(),
+                "+    }".to_string(),
+            ]
+        )
+    );
+
+    // A placeholder that matches no lines is likely a mistake
+    let message =
+        "the placeholder at snippet line 3 matches no code lines, which is likely a mistake";
+    let check_empty = check_with("allow_placeholder_empty.md", |ctx| {
+        ctx.allow_placeholder = true
+    });
+    assert_eq!(matched(&check_empty), [(0, 30, 32)]);
+    assert_eq!(warnings(&check_empty), [(0, message)]);
+    let check = check_with("allow_placeholder_empty.md", |ctx| {
+        ctx.allow_placeholder = true;
+        ctx.strict = true;
+    });
+    assert_eq!(issues(&check), [(0, message)]);
+}
+
+#[test]
+fn cfg_options() {
+    let compact = || vec!["feature=compact".to_string()];
+    // The disabled items are blanked, so they still count in the line numbers
+    let check_compact = check_with("cfg.md", |ctx| ctx.cfg = compact());
+    assert_eq!(matched(&check_compact), [(0, 3, 14)]);
+
+    let check_default = check("cfg.md");
+    assert!(changes(&check_default)[0]
+        .1
+        .contains(&"+    #[cfg(feature = \"compact\")]".to_string()));
+
+    let check = check_with("cfg.md", |ctx| {
+        ctx.cfg = [compact(), vec!["test".to_string()]].concat()
+    });
+    assert_eq!(
+        changes(&check),
+        [(
+            0,
+            vec!["-    \"compact\"".to_string(), "+    \"full\"".to_string()]
+        )]
+    );
+}
+
+#[test]
+fn ignored_attributes() {
+    let ignored = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+    let check_ignored = check_with("ignore_attributes.md", |ctx| {
+        ctx.ignore_attributes = ignored(&["non_exhaustive", "allow(dead_code)"])
+    });
+    assert_eq!(matched(&check_ignored), [(0, 2, 10)]);
+    let check_default = check("ignore_attributes.md");
+    assert!(changes(&check_default)[0]
+        .1
+        .contains(&"+#[non_exhaustive]".to_string()));
+
+    let check_derive = check_with("ignore_attributes_derive.md", |ctx| {
+        ctx.ignore_attributes = ignored(&["non_exhaustive", "allow(dead_code)"])
+    });
+    assert_eq!(
+        changes(&check_derive),
+        [(0, vec!["+#[derive(Debug, Clone, Copy)]".to_string()])]
+    );
+    assert_eq!(check_derive.diffs[0].moved_to, Some((5, 10)));
+    let check = check_with("ignore_attributes_derive.md", |ctx| {
+        ctx.ignore_attributes = ignored(&["non_exhaustive", "allow", "derive"])
+    });
+    assert_eq!(matched(&check), [(0, 2, 10)]);
+}
+
+#[test]
+fn invalid_utf8_source() {
+    let message = "source file is not valid UTF-8: sample/src/latin1.rs";
+    // The snippet after the one with the invalid source file is still checked
+    let check_default = check("invalid_utf8.md");
+    assert_eq!(issues(&check_default), [(0, message)]);
+    assert_eq!(verified(&check_default), [(1, 6, 9)]);
+
+    let check = check_with("invalid_utf8.md", |ctx| {
+        ctx.source = Some(Box::new(archive("code.zip")))
+    });
+    assert_eq!(issues(&check), [(0, message)]);
+    assert_eq!(verified(&check), [(1, 6, 9)]);
+}
+
+#[test]
+fn snippet_attributes() {
+    // The skipped snippet is not verified
+    let lines = [(0, 20, 28), (1, 30, 32), (2, 6, 9)];
+    assert_eq!(matched(&check("snippet_attributes.md")), lines);
+    let check_diff = check("snippet_attributes_diff.md");
+    assert_eq!(
+        changes(&check_diff)[0].1[..2],
+        [
+            "-pub fn remove_peer(&mut self, id: u32) -> Option<String> {",
+            "-    self.peers.remove(&id)",
+        ]
+    );
+    assert_eq!(
+        issues(&check("snippet_attributes_unknown.md")),
+        [(0, "unknown snippet attribute `keep-coments`")]
+    );
+}
+
+#[test]
+fn indentation() {
+    assert_eq!(matched(&check("indented.md")), [(0, 30, 32), (1, 6, 9)]);
+    let check = check_with("indented.md", |ctx| ctx.check_indent = true);
+    assert_eq!(
+        issues(&check),
+        [(0, "snippet has leading indentation; dedent it")]
+    );
+    assert_eq!(verified(&check), [(1, 6, 9)]);
+}
+
+#[test]
+fn directives() {
+    let lines = [(0, 6, 9), (1, 30, 32), (2, 3, 7)];
+    assert_eq!(matched(&check("directives.md")), lines);
+
+    let check_moved = check("directives_moved.md");
+    assert_eq!(check_moved.diffs[0].moved_to, Some((6, 9)));
+    assert_eq!(check_moved.diffs[0].start_line, 5);
+
+    assert_eq!(
+        issues(&check("directives_invalid.md")),
+        [
+            (0, "the `lines` and `anchor` directives can't be both given"),
+            (1, "`skip` must be true or false, not `maybe`"),
+        ]
+    );
+}
+
+#[test]
+fn occurrences() {
+    // The requested occurrences of a repeated first line, instead of the one followed by the second
+    let lines = [(0, 42, 45), (1, 35, 38)];
+    assert_eq!(matched(&check("occurrence.md")), lines);
+    let check_order = check_with("occurrence.md", |ctx| ctx.check_intra_file_order = true);
+    assert_eq!(
+        issues(&check_order),
+        [(
+            1,
+            "shows sample/src/lib.rs:35, before line 42 shown by snippet 0, so the snippets are \
+             out of the source order"
+        )]
+    );
+    let check_fences = check_with("list_fences.md", |ctx| ctx.check_intra_file_order = true);
+    assert_eq!(matched(&check_fences).len(), 2);
+
+    assert_eq!(
+        changes(&check("occurrence_diff.md")),
+        [(
+            0,
+            vec![
+                "-    None => false,".to_string(),
+                "-    Some(_) => true,".to_string(),
+                "+    Some(address) => address,".to_string(),
+                "+    None => \"unknown\",".to_string(),
+            ]
+        )]
+    );
+    assert_eq!(
+        issues(&check("occurrence_invalid.md")),
+        [
+            (
+                0,
+                "no occurrence 3 of the first snippet line in sample/src/lib.rs"
+            ),
+            (
+                1,
+                "the `occurrence` directive only applies to snippets found by their first line, \
+                 not by a line range, anchors or a module"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn similarity() {
+    let check_similar = check("similarity.md");
+    assert_eq!(matched(&check_similar), [(0, 20, 28)]);
+    assert_eq!(
+        check_similar.notes[0].message,
+        "passed with a line similarity of 0.71, at least 0.7"
+    );
+    assert_eq!(
+        check("similarity_low.md").diffs[0].hint.as_deref(),
+        Some("line similarity of 0.71, below the 0.9 of the `similarity` directive")
+    );
+}
+
+#[test]
+fn directories() {
+    assert_eq!(matched(&check("directory.md")), [(0, 1, 7)]);
+    let from_archive = check_with("directory.md", |ctx| {
+        ctx.source = Some(Box::new(archive("code.tar.gz")))
+    });
+    assert_eq!(matched(&from_archive), [(0, 1, 7)]);
+    assert_eq!(
+        issues(&check("directory_missing.md")),
+        [(
+            0,
+            "no Rust files found in the directory sample/src/connection/"
+        )]
+    );
+}
+
+#[test]
+fn token_comparison() {
+    let check_tokens = check_with("ast_compare.md", |ctx| ctx.ast_compare = true);
+    assert_eq!(matched(&check_tokens), [(0, 12, 17)]);
+    assert_eq!(
+        changes(&check("ast_compare.md"))[0].1[0],
+        "-    PeerTracker { peers: HashMap::new(), max_peers } /* rustfmt splits this */"
+    );
+    let check = check_with("ast_compare_diff.md", |ctx| ctx.ast_compare = true);
+    assert_eq!(
+        changes(&check)[0].1[0],
+        "-    PeerTracker { peers: HashMap::new(), max: max_peers }"
+    );
+}
+
+#[test]
+fn line_wrapping() {
+    // The wrapped lines are joined in the first one, so the line numbers are those of the file
+    let check_joined = check_with("line_wrapping.md", |ctx| ctx.normalize_line_wrapping = true);
+    assert_eq!(matched(&check_joined), [(0, 5, 15), (1, 17, 19)]);
+
+    let check = check("line_wrapping.md");
+    assert_eq!(
+        changes(&check)[0].1[4],
+        "+    let connected = tracker.add_peer("
+    );
+    assert_eq!(
+        issues(&check),
+        [(
+            1,
+            "captured 3 of 5 expected lines, as sample/src/wrapping.rs has no more code after \
+             line 19"
+        )]
+    );
+}
+
+#[test]
+fn unicode_normalization() {
+    let check_composed = check_with("unicode_normalize.md", |ctx| ctx.unicode_normalize = true);
+    assert_eq!(matched(&check_composed), [(0, 3, 4)]);
+    assert_eq!(
+        changes(&check("unicode_normalize.md"))[0].1[2],
+        "+pub const WELCOME: &str = \"Bem-vindo a\u{300} rede\";"
+    );
+}
+
+#[test]
+fn other_book_formats() {
+    // The directives keep the line numbers of the reStructuredText file
+    assert_eq!(matched(&check("sphinx.rst")), [(0, 6, 9), (1, 20, 28)]);
+    // Only the doc examples with a path line, in `///` and `//!` doc comments
+    assert_eq!(matched(&check("doc_tests.rs")), [(0, 6, 9), (1, 20, 28)]);
+}
+
+#[test]
+fn manifest_references() {
+    let refs = || {
+        [
+            ("tracker", "sample/src/lib.rs:6-9"),
+            ("tracker_new", "sample/src/lib.rs#new"),
+        ]
+        .into_iter()
+        .map(|(id, path)| (id.to_string(), path.to_string()))
+        .collect()
+    };
+    // The manifest references resolve to the same locations as the path lines
+    let check_refs = check_with("manifest.md", |ctx| ctx.snippet_refs = refs());
+    assert_eq!(matched(&check_refs), [(0, 6, 9), (1, 12, 17)]);
+    let check = check_with("manifest_unknown.md", |ctx| ctx.snippet_refs = refs());
+    assert_eq!(
+        issues(&check),
+        [(
+            0,
+            "unknown snippet ID `tracker_remove`, not in the manifest"
+        )]
+    );
+}
+
+#[test]
+fn custom_comparator() {
+    let check_collapsed = check_with("comparator.md", |ctx| {
+        ctx.keep_comments = true;
+        ctx.comparator = Box::new(CollapseWhitespace);
+    });
+    assert_eq!(matched(&check_collapsed), [(0, 20, 28), (1, 34, 39)]);
+
+    let check = check_with("comparator.md", |ctx| {
+        ctx.keep_comments = true;
+        ctx.comparator = Box::new(StripComments("//"));
+    });
+    assert_eq!(
+        changes(&check),
+        [(
+            1,
+            vec![
+                "-    match  self.peers.get(&id)  {".to_string(),
+                "+    match self.peers.get(&id) {".to_string(),
+            ]
+        )]
+    );
+}
+
+#[test]
+fn seeded_diff() {
+    let diff = [(
+        0,
+        vec![
+            "-    if self.peers.len() > self.max_peers {".to_string(),
+            "+    if self.peers.len() >= self.max_peers {".to_string(),
+            "-}".to_string(),
+            "+    self.peers.insert(id, address);".to_string(),
+        ],
+    )];
+    let check_diff = check("diff.md");
+    assert_eq!(changes(&check_diff), diff);
+    // The snippet checks the old limit, and is missing the lines before the closing brace
+    assert_eq!(check_diff.diffs[0].md_lines, [8, 11]);
+    assert_eq!(
+        check("placeholder_diff.md").diffs[0].md_lines,
+        [11],
+        "only the default greeting differs, as the line with a placeholder matches"
+    );
+
+    let check_raw = check_with("diff.md", |ctx| ctx.show_raw = true);
+    assert_eq!(changes(&check_raw), diff);
+    assert_eq!(check_raw.diffs[0].raw[1], "# // Path: sample/src/lib.rs");
+    // Only the code block is extracted, as in `grep`
+    let check = check_with("diff.md", |ctx| ctx.extract_only = true);
+    assert_eq!(matched(&check), [(0, 20, 26)]);
+    assert!(check.verified[0].block.starts_with("pub fn add_peer("));
+}
+
+#[test]
+fn missing_paths() {
+    assert_eq!(check("no_snippets.md").diff, None);
+    let check_default = check("missing_path.md");
+    assert_eq!(missing(&check_default), [(0, "sample/scr/lib.rs")]);
+    assert!(check_default.failed());
+
+    let check_warn = check_with("missing_path.md", |ctx| {
+        ctx.missing_paths = MissingPaths::Warn
+    });
+    assert_eq!(missing(&check_warn), [(0, "sample/scr/lib.rs")]);
+    assert!(!check_warn.failed());
+    let check_strict = check_with("missing_path.md", |ctx| {
+        ctx.missing_paths = MissingPaths::Warn;
+        ctx.strict = true;
+    });
+    assert!(check_strict.failed());
+
+    let check_skip = check_with("missing_path.md", |ctx| {
+        ctx.missing_paths = MissingPaths::Skip
+    });
+    assert!(check_skip.missing.is_empty() && !check_skip.failed());
+    // The missing paths are the point of `only_missing`, so they are never skipped
+    let check = check_with("missing_path.md", |ctx| {
+        ctx.missing_paths = MissingPaths::Skip;
+        ctx.only_missing = true;
+    });
+    assert_eq!(missing(&check), [(0, "sample/scr/lib.rs")]);
+}
+
+#[test]
+fn only_missing_paths() {
+    // The snippets are not compared, and their count is not checked
+    let check_diff = check_with("diff.md", |ctx| ctx.only_missing = true);
+    assert!(!check_diff.failed() && check_diff.diffs.is_empty());
+    assert_eq!(check_diff.sources.len(), 1);
+    let check = check_with("snippet_count.md", |ctx| ctx.only_missing = true);
+    assert!(!check.failed() && !check.snippet_count_mismatch());
+}
+
+#[test]
+fn path_traversal() {
+    let message = |path| {
+        format!("refusing to read {path}, as snippet paths must stay inside the code directory")
+    };
+    let expected = [
+        message("../../etc/passwd"),
+        message("sample/../../../etc/passwd"),
+        message("/etc/passwd"),
+    ];
+    let check_paths = check("path_traversal.md");
+    let found: Vec<_> = check_paths
+        .issues
+        .iter()
+        .map(|issue| &issue.message)
+        .collect();
+    assert_eq!(found, expected.iter().collect::<Vec<_>>());
+    assert_eq!(
+        issues(&check("path_traversal_existing.md")),
+        [(0, message("sample/../../tools/src/main.rs").as_str())]
+    );
+}
+
+#[test]
+fn malformed_headers() {
+    let header = "malformed snippet header, block not checked: # // path: sample/src/lib.rs:6-9";
+    let headers = |check: &FileCheck| {
+        let headers = check.malformed_headers.iter();
+        headers
+            .map(|header| (header.line, header.message()))
+            .collect::<Vec<_>>()
+    };
+    let check_default = check("malformed_header.md");
+    assert_eq!(verified(&check_default), [(0, 6, 9)]);
+    assert_eq!(headers(&check_default), [(16, header.to_string())]);
+    assert!(!check_default.failed());
+    assert!(check_with("malformed_header.md", |ctx| ctx.strict = true).failed());
+    let check_unparsed = check_with("malformed_header.md", |ctx| {
+        ctx.fail_on_unparsed_fence = true
+    });
+    assert_eq!(headers(&check_unparsed), [(16, header.to_string())]);
+    assert!(check_unparsed.failed());
+
+    let check_fence = check("unparsed_fence.md");
+    assert!(headers(&check_fence).is_empty() && !check_fence.failed());
+    let check = check_with("unparsed_fence.md", |ctx| ctx.fail_on_unparsed_fence = true);
+    assert_eq!(
+        headers(&check),
+        [(
+            16,
+            "Rust fence without a snippet header, block not checked (add a `# // Path:` line, or \
+             use another language for an illustrative block)"
+                .to_string()
+        )]
+    );
+}
+
+#[test]
+fn modules() {
+    assert_eq!(matched(&check("module.md")), [(0, 25, 30), (1, 16, 20)]);
+    assert_eq!(
+        issues(&check("module_invalid.md")),
+        [
+            (0, "no module `benches` in sample/src/modules.rs"),
+            (
+                1,
+                "module `storage` is declared with `mod storage;` in sample/src/modules.rs, so \
+                 its body is in another file"
+            ),
+            (
+                2,
+                "module `empty` of sample/src/modules.rs has no lines between its braces"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn header_lines() {
+    let check_stripped = check_with("licensed.md", |ctx| ctx.strip_header_lines = 3);
+    assert_eq!(matched(&check_stripped), [(0, 1, 6), (1, 4, 6)]);
+    let check_default = check("licensed.md");
+    assert_eq!(
+        changes(&check_default)[1].1[0],
+        "+/* Copyright (c) The Floresta developers"
+    );
+    let check = check_with("licensed_invalid.md", |ctx| ctx.strip_header_lines = 3);
+    assert_eq!(
+        issues(&check),
+        [(
+            0,
+            "its line range 1-3 only has header lines of sample/src/licensed/first.rs (the first 3)"
+        )]
+    );
+}
+
+#[test]
+fn glob_paths() {
+    let check_glob = check_with("glob_paths.md", |ctx| ctx.allow_glob_paths = true);
+    assert_eq!(matched(&check_glob), [(0, 6, 9)]);
+    assert_eq!(missing(&check("glob_paths.md")), [(0, "sam*/src/lib.rs")]);
+
+    let check = check_with("glob_paths_invalid.md", |ctx| ctx.allow_glob_paths = true);
+    assert_eq!(
+        issues(&check),
+        [(
+            0,
+            "the path `sample/src/*s.rs` matches 7 source files, sample/src/accents.rs, \
+             sample/src/attributes.rs, sample/src/features.rs, sample/src/greetings.rs, \
+             sample/src/handlers.rs, sample/src/imports.rs, sample/src/modules.rs; make it match \
+             only one"
+        )]
+    );
+    assert_eq!(missing(&check), [(1, "sample-v?/src/lib.rs")]);
+}
+
+#[test]
+fn signatures() {
+    let check_signature = check_with("signature.md", |ctx| ctx.signature_only = true);
+    assert_eq!(matched(&check_signature), [(0, 20, 20), (1, 8, 11)]);
+    assert_eq!(
+        changes(&check("signature.md")),
+        [(
+            1,
+            vec![
+                "-) -> u32 { todo!() }".to_string(),
+                "+) -> u32 {".to_string()
+            ]
+        )]
+    );
+    let check = check("signature_diff.md");
+    assert_eq!(
+        changes(&check)[1],
+        (
+            1,
+            vec![
+                "-    value: u64,".to_string(),
+                "+    value: u32,".to_string()
+            ]
+        )
+    );
+}
+
+#[test]
+fn hidden_line_diff() {
+    assert_eq!(
+        changes(&check("hidden_diff.md")),
+        [(
+            0,
+            vec![
+                "-            max: max_peers,".to_string(),
+                "+            max_peers,".to_string(),
+            ]
+        )]
+    );
+}
+
+#[test]
+fn hashes() {
+    let check_stale = check("sha_stale.md");
+    assert_eq!(
+        issues(&check_stale),
+        [(
+            0,
+            "matches the code, but its `sha` directive 0123abc is not the hash of the code block, \
+             770d81c; pass `--update-hashes` to update it"
+        )]
+    );
+    assert_eq!(check_stale.stale_hashes, [(0, "770d81c".to_string())]);
+    assert_eq!(
+        check("sha_edited.md").diffs[0].hint.as_deref(),
+        Some(
+            "the `sha` directive still matches the code block, so the snippet was changed \
+             instead of the code"
+        )
+    );
+}
+
+#[test]
+fn leading_attributes() {
+    assert_eq!(matched(&check("leading_attributes.md")), [(0, 5, 10)]);
+    let check_required = check_with("leading_attributes.md", |ctx| {
+        ctx.leading_attributes = LeadingAttributes::Require
+    });
+    assert_eq!(
+        issues(&check_required),
+        [(
+            0,
+            "omits the attribute `#[non_exhaustive]` before the code at line 5, and \
+             `--attributes=require` is set"
+        )]
+    );
+
+    assert_eq!(
+        changes(&check("leading_attributes_diff.md")),
+        [(
+            0,
+            vec![
+                "-#[derive(Debug, Clone)]".to_string(),
+                "+#[derive(Debug, Clone, Copy)]".to_string(),
+                "+#[non_exhaustive]".to_string(),
+            ]
+        )]
+    );
+    let check = check_with("leading_attributes_diff.md", |ctx| {
+        ctx.leading_attributes = LeadingAttributes::Ignore
+    });
+    assert_eq!(matched(&check), [(0, 2, 10)]);
+}
+
+#[test]
+fn truncated_snippets() {
+    assert_eq!(
+        issues(&check("short_capture.md")),
+        [(
+            0,
+            "captured 1 of 2 expected lines, as sample/src/documented.rs has no more code after \
+             line 35"
+        )]
+    );
+    let check = check_with("truncated.md", |ctx| ctx.require_balanced_braces = true);
+    assert_eq!(
+        issues(&check),
+        [(0, "unbalanced delimiters ({} +1), likely truncated")]
+    );
+}
+
+// The helpers of the checks and the output formats, with their exact results
+
+#[test]
+fn caret_columns() {
+    // The columns and widths are counted in display width
+    let lines = [
+        ("let x = 1;", "let x = 2;", (8, 1)),
+        (
+            "\"zh\" => \"你好，朋友\",",
+            "\"zh\" => \"你好，世界\",",
+            (15, 4),
+        ),
+        ("let 名前 = 1;", "let 名前 = 2;", (11, 1)),
+        ("// café", "// cafe\u{301}!", (6, 2)),
+    ];
+    for (old, new, expected) in lines {
+        assert_eq!(differing_columns(old, new), expected, "{new}");
+    }
+}
+
+#[test]
+fn annotated_fixtures() {
+    let fixtures: [(&str, &[&str]); 3] = [
+        (
+            "matching.md",
+            &[
+                "<!-- snippet ok: sample/src/lib.rs:20 -->",
+                "<!-- snippet ok: sample/src/lib.rs:6 -->",
+                "> <!-- snippet ok: sample/src/lib.rs:12 -->",
+            ],
+        ),
+        ("snippet_attributes_unknown.md", &["<!-- snippet ERROR -->"]),
+        ("diff.md", &["<!-- snippet DIFF -->"]),
+    ];
+    for (name, expected) in fixtures {
+        let content = fs::read_to_string(Path::new(BOOK_DIR).join(name)).unwrap();
+        let annotated = annotated_markdown(&content, &check(name));
+        let is_annotation = |line: &&str| line.contains("<!-- snippet ");
+        let found: Vec<&str> = annotated.lines().filter(is_annotation).collect();
+        assert_eq!(found, expected, "{name}");
+        // The other lines are kept as they were
+        let kept = annotated.lines().filter(|line| !is_annotation(line));
+        assert!(kept.eq(content.lines()), "{name}");
+    }
+}
+
+#[test]
+fn renamed_path_lines() {
+    let lines = [
+        (
+            "# // Path: sample/src/lib.rs:6-9\n",
+            "sample/src/lib.rs",
+            "sample/src/tracker.rs",
+            Some("# // Path: sample/src/tracker.rs:6-9\n"),
+        ),
+        (
+            "> # // Path: sample/src/lib.rs#new+remove_peer\r\n",
+            "sample/src/",
+            "sample/peers/",
+            Some("> # // Path: sample/peers/lib.rs#new+remove_peer\r\n"),
+        ),
+        (
+            "# // Path: sample/src/lib.rs.bak",
+            "sample/src/lib.rs",
+            "sample/src/tracker.rs",
+            None,
+        ),
+    ];
+    for (line, old, new, expected) in lines {
+        assert_eq!(renamed_path_line(line, old, new).as_deref(), expected);
+    }
+}
+
+#[test]
+fn moved_runs_lengths() {
+    // The number of lines of each run that only moved between the snippet and the code
+    let moves: [(&str, &str, &[usize]); 4] = [
+        ("a();\nb();\nc();\n", "b();\nc();\na();\n", &[1]),
+        (
+            "a();\nb();\nc();\nd();\n",
+            "c();\nd();\nx();\na();\nb();\n",
+            &[2],
+        ),
+        ("a();\n}\nb();\n", "b();\n}\na();\n", &[1]),
+        ("a();\nb();\n", "a();\nc();\n", &[]),
+    ];
+    for (snippet, code, expected) in moves {
+        let diff = TextDiff::from_lines(snippet, code);
+        let changes: Vec<_> = diff.iter_all_changes().collect();
+        let found: Vec<usize> = moved_runs(&changes).iter().map(|run| run.2).collect();
+        assert_eq!(found, expected, "{snippet:?} moved in {code:?}");
+    }
+}
+
+#[test]
+fn first_line_blocks() {
+    let blocks = [
+        ("fn a() {}\nfn b() {}\n", "fn a() {}", Some((1, 1))),
+        (
+            "fn a() {\n    b();\n}\n",
+            "fn a() {\n    b();\n}",
+            Some((1, 3)),
+        ),
+        ("// Comment\nfn a() {}\n", "fn a() {}", Some((2, 2))),
+        // The placeholder matches the comment line, which is not part of the block
+        (
+            "// Comment\nfn a() {}\n",
+            "/* ... */\nfn a() {}",
+            Some((2, 2)),
+        ),
+        ("fn a() {}\n", "fn b() {}", None),
+    ];
+    for (code, snippet, expected) in blocks {
+        let found = extract_clean_block(
+            code,
+            snippet,
+            Comments::Strip("//"),
+            Indent::Remove,
+            "/* ... */",
+            None,
+            0,
+        )
+        .unwrap()
+        .map(|(start, end, _)| (start, end));
+        assert_eq!(found, expected, "{snippet:?} found in {code:?}");
+    }
+}
+
+#[test]
+fn block_source_line_numbers() {
+    let blocks: [(&str, &str, &[Option<usize>]); 3] = [
+        ("a();\nb();\n", "a();\nb();\n", &[Some(1), Some(2)]),
+        (
+            "a();\nb();\n",
+            "a();\n\n    // Comment\n    b();\n",
+            &[Some(1), Some(4)],
+        ),
+        (
+            "a();\nx();\nb();\n",
+            "a();\nb();\n",
+            &[Some(1), None, Some(2)],
+        ),
+    ];
+    for (block, code, expected) in blocks {
+        let found = block_source_lines(block, code, &[(1, code.lines().count())]);
+        assert_eq!(found, expected, "{block:?} in {code:?}");
+    }
+}
+
+#[test]
+fn sha256_hashes() {
+    let hashes = [
+        (
+            "",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ),
+        (
+            "abc",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+    ];
+    for (data, expected) in hashes {
+        assert_eq!(sha256::hex(data.as_bytes()), expected, "{data:?}");
+    }
+}
+
+#[test]
+fn min_versions() {
+    // A `min_version` setting and a checker version, and whether the checker can run
+    let versions = [
+        ("0.1", "0.1.0", true),
+        ("0.1.0", "0.2.0", true),
+        ("1.2", "1.10.0", true),
+        ("0.1.1", "0.1.0", false),
+        ("1.2", "0.1.0", false),
+        ("latest", "0.1.0", false),
+    ];
+    for (min_version, version, runs) in versions {
+        let found = check_min_version(min_version, version).is_ok();
+        assert_eq!(found, runs, "{min_version:?} with version {version}");
+    }
+}
+
+#[test]
+fn dotenv_lines() {
+    let files: [(&str, &[(&str, &str)]); 3] = [
+        ("CODE_DIR=../Floresta\n", &[("CODE_DIR", "../Floresta")]),
+        (
+            "# Local paths\n\nexport CODE_DIR = \"~/Floresta code\" # a comment\r\n",
+            &[("CODE_DIR", "~/Floresta code")],
+        ),
+        (
+            "BOOK='docs # src'\nCODE_DIR=../code # the code\n",
+            &[("BOOK", "docs # src"), ("CODE_DIR", "../code")],
+        ),
+    ];
+    let path = env::temp_dir().join(format!("snippet-checker-dotenv-{}", process::id()));
+    for (content, expected) in files {
+        fs::write(&path, content).unwrap();
+        let found = dotenv::read(&path).unwrap();
+        let found: Vec<(&str, &str)> = found.iter().map(|(k, v)| (&k[..], &v[..])).collect();
+        assert_eq!(found, expected, "{content:?}");
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn github_summary_table() {
+    // The table has a row for each file with snippets, and the totals
+    let results: Vec<(String, FileCheck)> = ["missing_path.md", "no_snippets.md", "sha.md"]
+        .into_iter()
+        .map(|name| (name.to_string(), check(name)))
+        .collect();
+    assert_eq!(
+        github_summary(&results, true),
+        "## Snippet check: DIFF FOUND\n\n\
+         | File | Snippets | OK | Diff | Missing |\n\
+         | --- | ---: | ---: | ---: | ---: |\n\
+         | missing_path.md | 1 | 0 | 0 | 1 |\n\
+         | sha.md | 1 | 1 | 0 | 0 |\n\
+         | **Total** | 2 | 1 | 0 | 1 |\n"
+    );
+}
+
+#[test]
+fn largest_diffs_order() {
+    // The diffs are ranked by their changed lines, and then in the book order
+    let results: Vec<(String, FileCheck)> = ["diff.md", "placeholder_diff.md", "signature_diff.md"]
+        .into_iter()
+        .map(|name| (name.to_string(), check(name)))
+        .collect();
+    let ranked: Vec<_> = largest_diffs(&results, 3)
+        .into_iter()
+        .map(|(md_name, diff, changed)| (md_name, diff.index, changed))
+        .collect();
+    assert_eq!(
+        ranked,
+        [
+            ("diff.md", 0, 4),
+            ("placeholder_diff.md", 0, 2),
+            ("signature_diff.md", 0, 2),
+        ]
+    );
+}
+
+#[test]
+fn inflate_blocks() {
+    // A stored block, and a fixed Huffman block with a run of copies
+    assert_eq!(
+        inflate(&[1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c']).unwrap(),
+        b"abc"
+    );
+    let run = [75, 76, 28, 5, 163, 96, 20, 12, 119, 0, 0];
+    assert_eq!(inflate(&run).unwrap(), [b'a'; 1000]);
+
+    assert_eq!(
+        inflate(&[1, 3, 0, 0, 0, b'a', b'b', b'c']).unwrap_err(),
+        "corrupted stored block length"
+    );
+    assert_eq!(
+        inflate(&[1, 3, 0, 0xfc, 0xff, b'a']).unwrap_err(),
+        "unexpected end of compressed data"
+    );
+    // A copy of the previous 3 bytes, when there are none
+    assert_eq!(inflate(&[3, 2]).unwrap_err(), "distance too far back");
+    assert_eq!(inflate(&[7]).unwrap_err(), "invalid block type");
+    assert_eq!(
+        inflate(&run[..5]).unwrap_err(),
+        "unexpected end of compressed data"
+    );
+}
+
+#[test]
+fn inflate_output_limit() {
+    let run = [75, 76, 28, 5, 163, 96, 20, 12, 119, 0, 0];
+    assert_eq!(inflate_limited(&run, 1000).unwrap().len(), 1000);
+    assert_eq!(
+        inflate_limited(&run, 999).unwrap_err(),
+        "decompressed data larger than 999 bytes"
+    );
+    let stored = [1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c'];
+    assert_eq!(
+        inflate_limited(&stored, 2).unwrap_err(),
+        "decompressed data larger than 2 bytes"
+    );
+}
+
+#[test]
+fn truncated_archives() {
+    // The gzip trailer is not checked, but any shorter prefix cuts the compressed data
+    for name in ["code.tar.gz", "code.zip"] {
+        let path = Path::new(CODE_DIR).parent().unwrap().join(name);
+        let data = fs::read(&path).unwrap();
+        for len in 0..data.len() - 8 {
+            assert!(
+                Archive::parse(&path, &data[..len]).is_err(),
+                "{name} cut at {len} bytes"
+            );
+        }
+    }
+}
+
+#[test]
+fn corrupted_archives() {
+    // A corrupted byte may still give an archive, but it never panics. Only some bytes spread
+    // over the archives are corrupted, so that the test is fast in the debug builds
+    for name in ["code.tar.gz", "code.zip"] {
+        let path = Path::new(CODE_DIR).parent().unwrap().join(name);
+        let data = fs::read(&path).unwrap();
+        for i in (0..data.len()).step_by(11) {
+            let mut corrupted = data.clone();
+            corrupted[i] ^= 0xff;
+            let _ = Archive::parse(&path, &corrupted);
+        }
+    }
+}
+
+#[test]
+fn invalid_archive_headers() {
+    let invalid = |name: &str, data: &[u8]| match Archive::parse(Path::new(name), data) {
+        Ok(_) => panic!("{name} parsed"),
+        Err(e) => e,
+    };
+    // A gzip header with an extra field longer than the file
+    let gzip = [
+        0x1f, 0x8b, 8, 0x0c, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 0, 0, 0, 0,
+    ];
+    assert_eq!(
+        invalid("code.tar.gz", &gzip),
+        "unterminated gzip header field"
+    );
+    assert_eq!(invalid("code.tgz", b"not gzip data"), "not a gzip file");
+
+    // A tar entry larger than the archive, and one with an invalid size
+    let mut tar = write_tar(&[("lib.rs".to_string(), b"fn a() {}\n".to_vec())]);
+    tar[124..136].copy_from_slice(b"77777777777\0");
+    assert_eq!(invalid("code.tar", &tar), "unexpected end of data");
+    tar[124..136].copy_from_slice(b"9\0\0\0\0\0\0\0\0\0\0\0");
+    assert_eq!(
+        invalid("code.tar", &tar),
+        "invalid tar entry size at offset 0"
+    );
+
+    // A zip central directory past the end of the file
+    let mut zip = vec![0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0, 1, 0, 1, 0];
+    zip.extend([0, 0, 0, 0, 0xff, 0xff, 0, 0, 0, 0]);
+    assert_eq!(invalid("code.zip", &zip), "invalid central directory entry");
+    assert_eq!(invalid("code.zip", b"PK"), "not a zip file");
+}
+
+#[test]
+fn toml_subset() {
+    let content = r#"
+name = "checker" # a comment
+"quoted.key" = 'C:\path'
+dotted . key = "tab\tunicode \u00e9 \U0001F600"
+
+[[bin]]
+path = "a.rs"
+
+[[bin]]
+path = "b.rs"
+dependencies = { serde = { version = "1" } }
+list = [
+    1_000,
+    true,
+]
+"#;
+    let string = |value: &str| Value::String(value.to_string());
+    assert_eq!(
+        toml::parse(content).unwrap(),
+        [
+            ("name".to_string(), string("checker")),
+            ("quoted.key".to_string(), string("C:\\path")),
+            ("dotted.key".to_string(), string("tab\tunicode é 😀")),
+            ("bin.0.path".to_string(), string("a.rs")),
+            ("bin.1.path".to_string(), string("b.rs")),
+            ("bin.1.dependencies.serde.version".to_string(), string("1")),
+            (
+                "bin.1.list".to_string(),
+                Value::Array(vec![Value::Integer(1000), Value::Boolean(true)])
+            ),
+        ]
+    );
+
+    // The TOML outside of the subset is an error, instead of being read differently
+    let errors = [
+        ("a = 1\na = 2", "line 2: duplicate key `a`"),
+        ("[a]\nb = 1\n[a]\nb = 2", "line 4: duplicate key `a.b`"),
+        ("a b = 1", "line 1: invalid key `a b`"),
+        ("[a b]", "line 1: invalid key `a b`"),
+        ("= 1", "line 1: missing key"),
+        ("a = 'unclosed", "line 1: unclosed string"),
+        (
+            "a = \"\"\"\nmulti-line\n\"\"\"",
+            "line 1: multi-line strings are not supported",
+        ),
+        (
+            "a = '''text'''",
+            "line 1: multi-line strings are not supported",
+        ),
+        ("a = 1.5", "line 1: invalid value `1.5`"),
+        ("a = 1979-05-27", "line 1: invalid value `1979-05-27`"),
+        ("[[bin]", "line 1: unclosed array of tables header"),
+    ];
+    for (content, expected) in errors {
+        assert_eq!(toml::parse(content).unwrap_err(), expected, "{content:?}");
+    }
+}
+
+#[test]
+fn switch_values() {
+    let load = |args: &[&str]| Config::load(args.iter().map(|arg| arg.to_string()));
+    assert!(load(&["--fix"]).unwrap().fix);
+    assert!(load(&["--fix=true"]).unwrap().fix);
+    assert!(!load(&["--fix=false"]).unwrap().fix);
+    // The next argument is not taken as the value of a switch
+    let config = load(&["--print-config", "--book-dir", BOOK_DIR]).unwrap();
+    assert!(config.print_config);
+
+    let error = load(&["--fix=no"]).err().unwrap();
+    assert_eq!(
+        error.0,
+        "Invalid value for --fix: expected `true` or `false`, found `no`"
+    );
+}
+
+#[test]
+fn since_values() {
+    let load = |since: &str| Config::load(["--since".to_string(), since.to_string()].into_iter());
+    assert!(load("2h").is_ok());
+    assert!(load("2024-01-31").is_ok());
+    // The duration overflows the seconds of a `u64`
+    for since in ["30500000000000w", "18446744073709551615d"] {
+        let error = load(since).err().unwrap();
+        assert_eq!(
+            error.0,
+            format!(
+                "Invalid `since` value: {since}, expected a duration like `2h` or a date like \
+                 `2024-01-31`"
+            )
+        );
+    }
+}
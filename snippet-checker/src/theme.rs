@@ -0,0 +1,107 @@
+// The colors of the printed output, which can be changed for terminals where the default ones are
+// hard to read. The printing functions use the colors by their role (like `error` or `success`),
+// through the `Themed` methods, instead of by name
+use colored::{control, Color, ColoredString, Colorize};
+
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorTheme {
+    Default,
+    // Bright and bold variants of the default colors
+    HighContrast,
+    // No colors, leaving only the `+`/`-` diff prefixes and the status words
+    Monochrome,
+}
+
+struct Colors {
+    error: Color,
+    success: Color,
+    warning: Color,
+    plain: Color,
+    accent: Color,
+    bold: bool,
+}
+
+static COLORS: OnceLock<Colors> = OnceLock::new();
+
+impl ColorTheme {
+    pub fn parse(theme: &str) -> Option<Self> {
+        match theme {
+            "default" => Some(ColorTheme::Default),
+            "high-contrast" => Some(ColorTheme::HighContrast),
+            "monochrome" => Some(ColorTheme::Monochrome),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorTheme::Default => "default",
+            ColorTheme::HighContrast => "high-contrast",
+            ColorTheme::Monochrome => "monochrome",
+        }
+    }
+
+    // Sets the colors used by the rest of the run
+    pub fn apply(self) {
+        if self == ColorTheme::Monochrome {
+            control::set_override(false);
+        }
+        if self == ColorTheme::HighContrast {
+            let _ = COLORS.set(Colors {
+                error: Color::BrightRed,
+                success: Color::BrightGreen,
+                warning: Color::BrightYellow,
+                plain: Color::BrightWhite,
+                accent: Color::BrightCyan,
+                bold: true,
+            });
+        }
+    }
+}
+
+fn colors() -> &'static Colors {
+    COLORS.get_or_init(|| Colors {
+        error: Color::Red,
+        success: Color::Green,
+        warning: Color::Yellow,
+        plain: Color::White,
+        accent: Color::Cyan,
+        bold: false,
+    })
+}
+
+fn paint(text: impl Colorize, color: fn(&Colors) -> Color) -> ColoredString {
+    let colors = colors();
+    let painted = text.color(color(colors));
+    match colors.bold {
+        true => painted.bold(),
+        false => painted,
+    }
+}
+
+pub trait Themed: Colorize + Sized {
+    fn error(self) -> ColoredString {
+        paint(self, |colors| colors.error)
+    }
+
+    fn success(self) -> ColoredString {
+        paint(self, |colors| colors.success)
+    }
+
+    fn warning(self) -> ColoredString {
+        paint(self, |colors| colors.warning)
+    }
+
+    // The unchanged diff lines
+    fn plain(self) -> ColoredString {
+        paint(self, |colors| colors.plain)
+    }
+
+    fn accent(self) -> ColoredString {
+        paint(self, |colors| colors.accent)
+    }
+}
+
+impl<T: Colorize> Themed for T {}
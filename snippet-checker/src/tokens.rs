@@ -0,0 +1,144 @@
+// A Rust tokenizer for the `ast_compare` mode, where the snippet and the code are equal if they have
+// the same tokens, whatever their whitespace, comments and line breaks. The trailing commas before
+// a closing delimiter are skipped too, as rustfmt adds or removes them when reformatting
+pub fn tokenize(code: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start = i;
+        match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '/' if next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if next == Some('*') => {
+                i = block_comment_end(&chars, i).ok_or("unclosed block comment")?;
+                continue;
+            }
+            '"' => i = string_end(&chars, i + 1).ok_or("unclosed string literal")?,
+            'r' | 'b' | 'c' if raw_string_start(&chars, i).is_some() => {
+                let (hashes, quote) = raw_string_start(&chars, i).unwrap();
+                i = raw_string_end(&chars, quote + 1, hashes).ok_or("unclosed raw string")?;
+            }
+            'b' | 'c' if next == Some('"') => {
+                i = string_end(&chars, i + 2).ok_or("unclosed string literal")?;
+            }
+            'b' if next == Some('\'') => {
+                i = char_end(&chars, i + 1).ok_or("unclosed byte literal")?;
+            }
+            '\'' => {
+                // A char literal, or a lifetime (or label) if there's no closing quote
+                i = match char_end(&chars, i) {
+                    Some(end) => end,
+                    None => identifier_end(&chars, i + 1),
+                };
+            }
+            _ if c.is_alphabetic() || c == '_' => i = identifier_end(&chars, i),
+            _ if c.is_ascii_digit() => {
+                // Numbers, with their suffix, and the decimal point if followed by a digit
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric()
+                        || chars[i] == '_'
+                        || (chars[i] == '.'
+                            && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())))
+                {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+
+        let token: String = chars[start..i].iter().collect();
+        if matches!(token.as_str(), ")" | "]" | "}") && tokens.last().is_some_and(|t| t == ",") {
+            tokens.pop();
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn identifier_end(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+        i += 1;
+    }
+    i
+}
+
+// Returns the index after the closing quote of a string whose content starts at `i`
+fn string_end(chars: &[char], mut i: usize) -> Option<usize> {
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+// Returns the index after a char literal starting with the quote at `i`, if it is one
+fn char_end(chars: &[char], i: usize) -> Option<usize> {
+    let end = match chars.get(i + 1)? {
+        '\\' => (i + 3..chars.len().min(i + 12)).find(|&j| chars[j] == '\'')?,
+        _ => i + 2,
+    };
+    (chars.get(end) == Some(&'\'')).then_some(end + 1)
+}
+
+// Returns the number of `#` and the index of the opening quote of a raw string (like `r#"`,
+// `br"` or `cr##"`) starting at `i`
+fn raw_string_start(chars: &[char], i: usize) -> Option<(usize, usize)> {
+    let mut j = i;
+    if chars[j] != 'r' {
+        j += 1;
+    }
+    if chars.get(j) != Some(&'r') {
+        return None;
+    }
+    let hashes = chars[j + 1..].iter().take_while(|c| **c == '#').count();
+    let quote = j + 1 + hashes;
+    (chars.get(quote) == Some(&'"')).then_some((hashes, quote))
+}
+
+fn raw_string_end(chars: &[char], mut i: usize, hashes: usize) -> Option<usize> {
+    while i < chars.len() {
+        if chars[i] == '"' && chars[i + 1..].iter().take_while(|c| **c == '#').count() >= hashes {
+            return Some(i + 1 + hashes);
+        }
+        i += 1;
+    }
+    None
+}
+
+// Returns the index after a block comment starting at `i`, which can be nested
+fn block_comment_end(chars: &[char], mut i: usize) -> Option<usize> {
+    let mut depth = 0;
+    while i + 1 < chars.len() {
+        match (chars[i], chars[i + 1]) {
+            ('/', '*') => {
+                depth += 1;
+                i += 2;
+            }
+            ('*', '/') => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
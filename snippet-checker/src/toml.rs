@@ -0,0 +1,386 @@
+// A minimal TOML parser, enough for the config files and the manifest snippets. It reads a subset
+// of TOML, and everything outside of it is an error instead of being read differently:
+// - `[table]` and `[[array of tables]]` headers, and `key = value` pairs, with bare (`a-z`, `A-Z`,
+//   `0-9`, `_` and `-`), quoted or dotted keys, each defined once
+// - basic and literal strings on a single line, decimal integers, booleans, inline tables, and
+//   arrays, which can span multiple lines
+// - `#` comments
+// The multi-line strings, floats and dates are not supported
+use std::collections::HashSet;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>),
+}
+
+// Parses the TOML document into its `key = value` pairs, in order. Keys inside tables are
+// prefixed with the table name, like `book.src` for the `src` key in the `[book]` table, and so are
+// the keys of inline tables, so `serde = { version = "1" }` gives `serde.version`. The tables of an
+// array are numbered from 0, so the `name` of the second `[[bin]]` table is `bin.1.name`
+pub fn parse(content: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut entries = Vec::new();
+    let mut keys = HashSet::new();
+    let mut table = String::new();
+    // The number of tables of each array of tables
+    let mut array_tables: Vec<(String, usize)> = Vec::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((i, line)) = lines.next() {
+        let mut line = strip_comment(line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", i + 1, message);
+
+        if let Some(name) = line.strip_prefix("[[") {
+            let name = name
+                .strip_suffix("]]")
+                .ok_or_else(|| error("unclosed array of tables header"))?;
+            let name = parse_key(name).map_err(|e| error(&e))?;
+            let index = match array_tables.iter_mut().find(|(array, _)| *array == name) {
+                Some((_, count)) => {
+                    *count += 1;
+                    *count - 1
+                }
+                None => {
+                    array_tables.push((name.clone(), 1));
+                    0
+                }
+            };
+            table = format!("{name}.{index}");
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name
+                .strip_suffix(']')
+                .ok_or_else(|| error("unclosed table header"))?;
+            table = parse_key(name).map_err(|e| error(&e))?;
+            continue;
+        }
+
+        // Arrays can span multiple lines, until the closing bracket
+        while open_brackets(&line) > 0 {
+            let (_, next) = lines.next().ok_or_else(|| error("unclosed array"))?;
+            line.push(' ');
+            line.push_str(strip_comment(next).trim());
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `key = value`"))?;
+        let key = parse_key(key).map_err(|e| error(&e))?;
+
+        let mut chars = value.trim().chars().peekable();
+        let value = parse_value(&mut chars).map_err(|e| error(&e))?;
+        if chars.any(|c| !c.is_whitespace()) {
+            return Err(error("unexpected characters after the value"));
+        }
+
+        let key = if table.is_empty() {
+            key.to_string()
+        } else {
+            format!("{table}.{key}")
+        };
+        push_entry(&mut entries, &mut keys, key, value)
+            .map_err(|key| error(&format!("duplicate key `{key}`")))?;
+    }
+    Ok(entries)
+}
+
+// Adds the entry, or the entries of an inline table, returning the key that was already defined
+fn push_entry(
+    entries: &mut Vec<(String, Value)>,
+    keys: &mut HashSet<String>,
+    key: String,
+    value: Value,
+) -> Result<(), String> {
+    match value {
+        Value::Table(table) => {
+            for (inner, value) in table {
+                push_entry(entries, keys, format!("{key}.{inner}"), value)?;
+            }
+        }
+        value => {
+            if !keys.insert(key.clone()) {
+                return Err(key);
+            }
+            entries.push((key, value));
+        }
+    }
+    Ok(())
+}
+
+// Returns the key of a pair or table header, whose dotted parts are joined with `.`. The quoted
+// parts are taken as written, without their quotes
+fn parse_key(key: &str) -> Result<String, String> {
+    let mut parts = Vec::new();
+    let mut chars = key.trim().chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let part: String = match chars.peek() {
+            Some(&quote @ ('"' | '\'')) => {
+                chars.next();
+                let part: String = chars.by_ref().take_while(|c| *c != quote).collect();
+                part
+            }
+            _ => {
+                let mut part = String::new();
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || "_-".contains(*c))
+                {
+                    part.push(c);
+                }
+                if part.is_empty() {
+                    return Err(match key.trim() {
+                        "" => "missing key".to_string(),
+                        key => format!("invalid key `{key}`"),
+                    });
+                }
+                part
+            }
+        };
+        parts.push(part);
+
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            None => return Ok(parts.join(".")),
+            Some('.') => {}
+            Some(_) => return Err(format!("invalid key `{}`", key.trim())),
+        }
+    }
+}
+
+// A key, with its value in each of the compared documents (`None` if it is missing)
+pub type KeyDifference = (String, Option<Value>, Option<Value>);
+
+// Compares two TOML documents by their keys and values, whatever their order and formatting.
+// Returns the first differing key, in key order
+pub fn difference(a: &str, b: &str) -> Result<Option<KeyDifference>, String> {
+    let mut a = parse(a)?;
+    let mut b = parse(b)?;
+    a.sort_by(|x, y| x.0.cmp(&y.0));
+    b.sort_by(|x, y| x.0.cmp(&y.0));
+
+    let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+    loop {
+        let (key, a_value, b_value) = match (a.peek(), b.peek()) {
+            (None, None) => return Ok(None),
+            (Some((a_key, _)), Some((b_key, _))) if a_key == b_key => {
+                let (key, a_value) = a.next().unwrap();
+                (key, Some(a_value), b.next().map(|(_, value)| value))
+            }
+            (Some((a_key, _)), Some((b_key, _))) if a_key > b_key => {
+                let (key, b_value) = b.next().unwrap();
+                (key, None, Some(b_value))
+            }
+            (Some(_), _) => {
+                let (key, a_value) = a.next().unwrap();
+                (key, Some(a_value), None)
+            }
+            (None, Some(_)) => {
+                let (key, b_value) = b.next().unwrap();
+                (key, None, Some(b_value))
+            }
+        };
+        if a_value != b_value {
+            return Ok(Some((key, a_value, b_value)));
+        }
+    }
+}
+
+// Removes a trailing `#` comment, unless the `#` is inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+// The number of unclosed brackets outside strings
+fn open_brackets(line: &str) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+    if is_multiline_string(chars) {
+        return Err("multi-line strings are not supported".to_string());
+    }
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next().ok_or("unclosed string")? {
+                    '"' => return Ok(Value::String(string)),
+                    '\\' => match chars.next().ok_or("unclosed string")? {
+                        'n' => string.push('\n'),
+                        't' => string.push('\t'),
+                        'r' => string.push('\r'),
+                        'b' => string.push('\u{8}'),
+                        'f' => string.push('\u{c}'),
+                        '"' => string.push('"'),
+                        '\\' => string.push('\\'),
+                        escape @ ('u' | 'U') => {
+                            let digits = if escape == 'u' { 4 } else { 8 };
+                            let hex: String = chars.by_ref().take(digits).collect();
+                            let c = u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or("invalid unicode escape")?;
+                            string.push(c);
+                        }
+                        c => return Err(format!("invalid escape `\\{c}`")),
+                    },
+                    c => string.push(c),
+                }
+            }
+        }
+        Some('\'') => {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next().ok_or("unclosed string")? {
+                    '\'' => return Ok(Value::String(string)),
+                    c => string.push(c),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut table = Vec::new();
+            loop {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next_if_eq(&'}').is_some() {
+                    return Ok(Value::Table(table));
+                }
+                let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+                let key = match key.trim() {
+                    "" => return Err("missing key in inline table".to_string()),
+                    key => parse_key(key)?,
+                };
+                table.push((key, parse_value(chars)?));
+
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                match chars.next() {
+                    Some(',') => {}
+                    Some('}') => return Ok(Value::Table(table)),
+                    _ => return Err("expected `,` or `}` in inline table".to_string()),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut values = Vec::new();
+            loop {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next_if_eq(&']').is_some() {
+                    return Ok(Value::Array(values));
+                }
+                values.push(parse_value(chars)?);
+
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Value::Array(values)),
+                    _ => return Err("expected `,` or `]` in array".to_string()),
+                }
+            }
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(c) =
+                chars.next_if(|c| !matches!(c, ',' | ']' | '}') && !c.is_whitespace())
+            {
+                word.push(c);
+            }
+
+            match word.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                _ => word
+                    .replace('_', "")
+                    .parse()
+                    .map(Value::Integer)
+                    .map_err(|_| format!("invalid value `{word}`")),
+            }
+        }
+        None => Err("missing value".to_string()),
+    }
+}
+
+// Whether the value starts with three quotes, as the multi-line strings
+fn is_multiline_string(chars: &Peekable<Chars>) -> bool {
+    let start: String = chars.clone().take(3).collect();
+    start == "\"\"\"" || start == "'''"
+}
+
+// Writes the value in TOML syntax
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(string) => write!(f, "{string:?}"),
+            Value::Integer(integer) => write!(f, "{integer}"),
+            Value::Boolean(boolean) => write!(f, "{boolean}"),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Table(table) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in table.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {key} = {value}")?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// A canonical composition (as in Unicode NFC) of the accented Latin letters, for the
+// `unicode_normalize` mode. Editors may save them as a base letter followed by combining marks
+// (decomposed, as in NFD), which looks the same as the composed letter but doesn't compare equal.
+// Only the letters of the Latin-1 Supplement, Latin Extended-A/B and Latin Extended Additional
+// blocks are composed, which covers the accents of most languages written with Latin letters.
+// The other scripts are left as written, and the marks are not reordered as in a full NFC
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Each composition as three chars: the base letter, the combining mark and the composed letter
+const COMPOSITIONS: &str = "\
+    A\u{300}\u{c0}A\u{301}\u{c1}A\u{302}\u{c2}A\u{303}\u{c3}A\u{308}\u{c4}A\u{30a}\u{c5}\
+    C\u{327}\u{c7}E\u{300}\u{c8}E\u{301}\u{c9}E\u{302}\u{ca}E\u{308}\u{cb}I\u{300}\u{cc}\
+    I\u{301}\u{cd}I\u{302}\u{ce}I\u{308}\u{cf}N\u{303}\u{d1}O\u{300}\u{d2}O\u{301}\u{d3}\
+    O\u{302}\u{d4}O\u{303}\u{d5}O\u{308}\u{d6}U\u{300}\u{d9}U\u{301}\u{da}U\u{302}\u{db}\
+    U\u{308}\u{dc}Y\u{301}\u{dd}a\u{300}\u{e0}a\u{301}\u{e1}a\u{302}\u{e2}a\u{303}\u{e3}\
+    a\u{308}\u{e4}a\u{30a}\u{e5}c\u{327}\u{e7}e\u{300}\u{e8}e\u{301}\u{e9}e\u{302}\u{ea}\
+    e\u{308}\u{eb}i\u{300}\u{ec}i\u{301}\u{ed}i\u{302}\u{ee}i\u{308}\u{ef}n\u{303}\u{f1}\
+    o\u{300}\u{f2}o\u{301}\u{f3}o\u{302}\u{f4}o\u{303}\u{f5}o\u{308}\u{f6}u\u{300}\u{f9}\
+    u\u{301}\u{fa}u\u{302}\u{fb}u\u{308}\u{fc}y\u{301}\u{fd}y\u{308}\u{ff}A\u{304}\u{100}\
+    a\u{304}\u{101}A\u{306}\u{102}a\u{306}\u{103}A\u{328}\u{104}a\u{328}\u{105}C\u{301}\u{106}\
+    c\u{301}\u{107}C\u{302}\u{108}c\u{302}\u{109}C\u{307}\u{10a}c\u{307}\u{10b}C\u{30c}\u{10c}\
+    c\u{30c}\u{10d}D\u{30c}\u{10e}d\u{30c}\u{10f}E\u{304}\u{112}e\u{304}\u{113}E\u{306}\u{114}\
+    e\u{306}\u{115}E\u{307}\u{116}e\u{307}\u{117}E\u{328}\u{118}e\u{328}\u{119}E\u{30c}\u{11a}\
+    e\u{30c}\u{11b}G\u{302}\u{11c}g\u{302}\u{11d}G\u{306}\u{11e}g\u{306}\u{11f}G\u{307}\u{120}\
+    g\u{307}\u{121}G\u{327}\u{122}g\u{327}\u{123}H\u{302}\u{124}h\u{302}\u{125}I\u{303}\u{128}\
+    i\u{303}\u{129}I\u{304}\u{12a}i\u{304}\u{12b}I\u{306}\u{12c}i\u{306}\u{12d}I\u{328}\u{12e}\
+    i\u{328}\u{12f}I\u{307}\u{130}J\u{302}\u{134}j\u{302}\u{135}K\u{327}\u{136}k\u{327}\u{137}\
+    L\u{301}\u{139}l\u{301}\u{13a}L\u{327}\u{13b}l\u{327}\u{13c}L\u{30c}\u{13d}l\u{30c}\u{13e}\
+    N\u{301}\u{143}n\u{301}\u{144}N\u{327}\u{145}n\u{327}\u{146}N\u{30c}\u{147}n\u{30c}\u{148}\
+    O\u{304}\u{14c}o\u{304}\u{14d}O\u{306}\u{14e}o\u{306}\u{14f}O\u{30b}\u{150}o\u{30b}\u{151}\
+    R\u{301}\u{154}r\u{301}\u{155}R\u{327}\u{156}r\u{327}\u{157}R\u{30c}\u{158}r\u{30c}\u{159}\
+    S\u{301}\u{15a}s\u{301}\u{15b}S\u{302}\u{15c}s\u{302}\u{15d}S\u{327}\u{15e}s\u{327}\u{15f}\
+    S\u{30c}\u{160}s\u{30c}\u{161}T\u{327}\u{162}t\u{327}\u{163}T\u{30c}\u{164}t\u{30c}\u{165}\
+    U\u{303}\u{168}u\u{303}\u{169}U\u{304}\u{16a}u\u{304}\u{16b}U\u{306}\u{16c}u\u{306}\u{16d}\
+    U\u{30a}\u{16e}u\u{30a}\u{16f}U\u{30b}\u{170}u\u{30b}\u{171}U\u{328}\u{172}u\u{328}\u{173}\
+    W\u{302}\u{174}w\u{302}\u{175}Y\u{302}\u{176}y\u{302}\u{177}Y\u{308}\u{178}Z\u{301}\u{179}\
+    z\u{301}\u{17a}Z\u{307}\u{17b}z\u{307}\u{17c}Z\u{30c}\u{17d}z\u{30c}\u{17e}O\u{31b}\u{1a0}\
+    o\u{31b}\u{1a1}U\u{31b}\u{1af}u\u{31b}\u{1b0}A\u{30c}\u{1cd}a\u{30c}\u{1ce}I\u{30c}\u{1cf}\
+    i\u{30c}\u{1d0}O\u{30c}\u{1d1}o\u{30c}\u{1d2}U\u{30c}\u{1d3}u\u{30c}\u{1d4}\
+    \u{dc}\u{304}\u{1d5}\u{fc}\u{304}\u{1d6}\u{dc}\u{301}\u{1d7}\u{fc}\u{301}\u{1d8}\
+    \u{dc}\u{30c}\u{1d9}\u{fc}\u{30c}\u{1da}\u{dc}\u{300}\u{1db}\u{fc}\u{300}\u{1dc}\
+    \u{c4}\u{304}\u{1de}\u{e4}\u{304}\u{1df}\u{226}\u{304}\u{1e0}\u{227}\u{304}\u{1e1}\
+    \u{c6}\u{304}\u{1e2}\u{e6}\u{304}\u{1e3}G\u{30c}\u{1e6}g\u{30c}\u{1e7}K\u{30c}\u{1e8}\
+    k\u{30c}\u{1e9}O\u{328}\u{1ea}o\u{328}\u{1eb}\u{1ea}\u{304}\u{1ec}\u{1eb}\u{304}\u{1ed}\
+    \u{1b7}\u{30c}\u{1ee}\u{292}\u{30c}\u{1ef}j\u{30c}\u{1f0}G\u{301}\u{1f4}g\u{301}\u{1f5}\
+    N\u{300}\u{1f8}n\u{300}\u{1f9}\u{c5}\u{301}\u{1fa}\u{e5}\u{301}\u{1fb}\u{c6}\u{301}\u{1fc}\
+    \u{e6}\u{301}\u{1fd}\u{d8}\u{301}\u{1fe}\u{f8}\u{301}\u{1ff}A\u{30f}\u{200}a\u{30f}\u{201}\
+    A\u{311}\u{202}a\u{311}\u{203}E\u{30f}\u{204}e\u{30f}\u{205}E\u{311}\u{206}e\u{311}\u{207}\
+    I\u{30f}\u{208}i\u{30f}\u{209}I\u{311}\u{20a}i\u{311}\u{20b}O\u{30f}\u{20c}o\u{30f}\u{20d}\
+    O\u{311}\u{20e}o\u{311}\u{20f}R\u{30f}\u{210}r\u{30f}\u{211}R\u{311}\u{212}r\u{311}\u{213}\
+    U\u{30f}\u{214}u\u{30f}\u{215}U\u{311}\u{216}u\u{311}\u{217}S\u{326}\u{218}s\u{326}\u{219}\
+    T\u{326}\u{21a}t\u{326}\u{21b}H\u{30c}\u{21e}h\u{30c}\u{21f}A\u{307}\u{226}a\u{307}\u{227}\
+    E\u{327}\u{228}e\u{327}\u{229}\u{d6}\u{304}\u{22a}\u{f6}\u{304}\u{22b}\u{d5}\u{304}\u{22c}\
+    \u{f5}\u{304}\u{22d}O\u{307}\u{22e}o\u{307}\u{22f}\u{22e}\u{304}\u{230}\u{22f}\u{304}\u{231}\
+    Y\u{304}\u{232}y\u{304}\u{233}A\u{325}\u{1e00}a\u{325}\u{1e01}B\u{307}\u{1e02}b\u{307}\u{1e03}\
+    B\u{323}\u{1e04}b\u{323}\u{1e05}B\u{331}\u{1e06}b\u{331}\u{1e07}\u{c7}\u{301}\u{1e08}\
+    \u{e7}\u{301}\u{1e09}D\u{307}\u{1e0a}d\u{307}\u{1e0b}D\u{323}\u{1e0c}d\u{323}\u{1e0d}\
+    D\u{331}\u{1e0e}d\u{331}\u{1e0f}D\u{327}\u{1e10}d\u{327}\u{1e11}D\u{32d}\u{1e12}\
+    d\u{32d}\u{1e13}\u{112}\u{300}\u{1e14}\u{113}\u{300}\u{1e15}\u{112}\u{301}\u{1e16}\
+    \u{113}\u{301}\u{1e17}E\u{32d}\u{1e18}e\u{32d}\u{1e19}E\u{330}\u{1e1a}e\u{330}\u{1e1b}\
+    \u{228}\u{306}\u{1e1c}\u{229}\u{306}\u{1e1d}F\u{307}\u{1e1e}f\u{307}\u{1e1f}G\u{304}\u{1e20}\
+    g\u{304}\u{1e21}H\u{307}\u{1e22}h\u{307}\u{1e23}H\u{323}\u{1e24}h\u{323}\u{1e25}\
+    H\u{308}\u{1e26}h\u{308}\u{1e27}H\u{327}\u{1e28}h\u{327}\u{1e29}H\u{32e}\u{1e2a}\
+    h\u{32e}\u{1e2b}I\u{330}\u{1e2c}i\u{330}\u{1e2d}\u{cf}\u{301}\u{1e2e}\u{ef}\u{301}\u{1e2f}\
+    K\u{301}\u{1e30}k\u{301}\u{1e31}K\u{323}\u{1e32}k\u{323}\u{1e33}K\u{331}\u{1e34}\
+    k\u{331}\u{1e35}L\u{323}\u{1e36}l\u{323}\u{1e37}\u{1e36}\u{304}\u{1e38}\u{1e37}\u{304}\u{1e39}\
+    L\u{331}\u{1e3a}l\u{331}\u{1e3b}L\u{32d}\u{1e3c}l\u{32d}\u{1e3d}M\u{301}\u{1e3e}\
+    m\u{301}\u{1e3f}M\u{307}\u{1e40}m\u{307}\u{1e41}M\u{323}\u{1e42}m\u{323}\u{1e43}\
+    N\u{307}\u{1e44}n\u{307}\u{1e45}N\u{323}\u{1e46}n\u{323}\u{1e47}N\u{331}\u{1e48}\
+    n\u{331}\u{1e49}N\u{32d}\u{1e4a}n\u{32d}\u{1e4b}\u{d5}\u{301}\u{1e4c}\u{f5}\u{301}\u{1e4d}\
+    \u{d5}\u{308}\u{1e4e}\u{f5}\u{308}\u{1e4f}\u{14c}\u{300}\u{1e50}\u{14d}\u{300}\u{1e51}\
+    \u{14c}\u{301}\u{1e52}\u{14d}\u{301}\u{1e53}P\u{301}\u{1e54}p\u{301}\u{1e55}P\u{307}\u{1e56}\
+    p\u{307}\u{1e57}R\u{307}\u{1e58}r\u{307}\u{1e59}R\u{323}\u{1e5a}r\u{323}\u{1e5b}\
+    \u{1e5a}\u{304}\u{1e5c}\u{1e5b}\u{304}\u{1e5d}R\u{331}\u{1e5e}r\u{331}\u{1e5f}S\u{307}\u{1e60}\
+    s\u{307}\u{1e61}S\u{323}\u{1e62}s\u{323}\u{1e63}\u{15a}\u{307}\u{1e64}\u{15b}\u{307}\u{1e65}\
+    \u{160}\u{307}\u{1e66}\u{161}\u{307}\u{1e67}\u{1e62}\u{307}\u{1e68}\u{1e63}\u{307}\u{1e69}\
+    T\u{307}\u{1e6a}t\u{307}\u{1e6b}T\u{323}\u{1e6c}t\u{323}\u{1e6d}T\u{331}\u{1e6e}\
+    t\u{331}\u{1e6f}T\u{32d}\u{1e70}t\u{32d}\u{1e71}U\u{324}\u{1e72}u\u{324}\u{1e73}\
+    U\u{330}\u{1e74}u\u{330}\u{1e75}U\u{32d}\u{1e76}u\u{32d}\u{1e77}\u{168}\u{301}\u{1e78}\
+    \u{169}\u{301}\u{1e79}\u{16a}\u{308}\u{1e7a}\u{16b}\u{308}\u{1e7b}V\u{303}\u{1e7c}\
+    v\u{303}\u{1e7d}V\u{323}\u{1e7e}v\u{323}\u{1e7f}W\u{300}\u{1e80}w\u{300}\u{1e81}\
+    W\u{301}\u{1e82}w\u{301}\u{1e83}W\u{308}\u{1e84}w\u{308}\u{1e85}W\u{307}\u{1e86}\
+    w\u{307}\u{1e87}W\u{323}\u{1e88}w\u{323}\u{1e89}X\u{307}\u{1e8a}x\u{307}\u{1e8b}\
+    X\u{308}\u{1e8c}x\u{308}\u{1e8d}Y\u{307}\u{1e8e}y\u{307}\u{1e8f}Z\u{302}\u{1e90}\
+    z\u{302}\u{1e91}Z\u{323}\u{1e92}z\u{323}\u{1e93}Z\u{331}\u{1e94}z\u{331}\u{1e95}\
+    h\u{331}\u{1e96}t\u{308}\u{1e97}w\u{30a}\u{1e98}y\u{30a}\u{1e99}\u{17f}\u{307}\u{1e9b}\
+    A\u{323}\u{1ea0}a\u{323}\u{1ea1}A\u{309}\u{1ea2}a\u{309}\u{1ea3}\u{c2}\u{301}\u{1ea4}\
+    \u{e2}\u{301}\u{1ea5}\u{c2}\u{300}\u{1ea6}\u{e2}\u{300}\u{1ea7}\u{c2}\u{309}\u{1ea8}\
+    \u{e2}\u{309}\u{1ea9}\u{c2}\u{303}\u{1eaa}\u{e2}\u{303}\u{1eab}\u{1ea0}\u{302}\u{1eac}\
+    \u{1ea1}\u{302}\u{1ead}\u{102}\u{301}\u{1eae}\u{103}\u{301}\u{1eaf}\u{102}\u{300}\u{1eb0}\
+    \u{103}\u{300}\u{1eb1}\u{102}\u{309}\u{1eb2}\u{103}\u{309}\u{1eb3}\u{102}\u{303}\u{1eb4}\
+    \u{103}\u{303}\u{1eb5}\u{1ea0}\u{306}\u{1eb6}\u{1ea1}\u{306}\u{1eb7}E\u{323}\u{1eb8}\
+    e\u{323}\u{1eb9}E\u{309}\u{1eba}e\u{309}\u{1ebb}E\u{303}\u{1ebc}e\u{303}\u{1ebd}\
+    \u{ca}\u{301}\u{1ebe}\u{ea}\u{301}\u{1ebf}\u{ca}\u{300}\u{1ec0}\u{ea}\u{300}\u{1ec1}\
+    \u{ca}\u{309}\u{1ec2}\u{ea}\u{309}\u{1ec3}\u{ca}\u{303}\u{1ec4}\u{ea}\u{303}\u{1ec5}\
+    \u{1eb8}\u{302}\u{1ec6}\u{1eb9}\u{302}\u{1ec7}I\u{309}\u{1ec8}i\u{309}\u{1ec9}I\u{323}\u{1eca}\
+    i\u{323}\u{1ecb}O\u{323}\u{1ecc}o\u{323}\u{1ecd}O\u{309}\u{1ece}o\u{309}\u{1ecf}\
+    \u{d4}\u{301}\u{1ed0}\u{f4}\u{301}\u{1ed1}\u{d4}\u{300}\u{1ed2}\u{f4}\u{300}\u{1ed3}\
+    \u{d4}\u{309}\u{1ed4}\u{f4}\u{309}\u{1ed5}\u{d4}\u{303}\u{1ed6}\u{f4}\u{303}\u{1ed7}\
+    \u{1ecc}\u{302}\u{1ed8}\u{1ecd}\u{302}\u{1ed9}\u{1a0}\u{301}\u{1eda}\u{1a1}\u{301}\u{1edb}\
+    \u{1a0}\u{300}\u{1edc}\u{1a1}\u{300}\u{1edd}\u{1a0}\u{309}\u{1ede}\u{1a1}\u{309}\u{1edf}\
+    \u{1a0}\u{303}\u{1ee0}\u{1a1}\u{303}\u{1ee1}\u{1a0}\u{323}\u{1ee2}\u{1a1}\u{323}\u{1ee3}\
+    U\u{323}\u{1ee4}u\u{323}\u{1ee5}U\u{309}\u{1ee6}u\u{309}\u{1ee7}\u{1af}\u{301}\u{1ee8}\
+    \u{1b0}\u{301}\u{1ee9}\u{1af}\u{300}\u{1eea}\u{1b0}\u{300}\u{1eeb}\u{1af}\u{309}\u{1eec}\
+    \u{1b0}\u{309}\u{1eed}\u{1af}\u{303}\u{1eee}\u{1b0}\u{303}\u{1eef}\u{1af}\u{323}\u{1ef0}\
+    \u{1b0}\u{323}\u{1ef1}Y\u{300}\u{1ef2}y\u{300}\u{1ef3}Y\u{323}\u{1ef4}y\u{323}\u{1ef5}\
+    Y\u{309}\u{1ef6}y\u{309}\u{1ef7}Y\u{303}\u{1ef8}y\u{303}\u{1ef9}";
+
+fn compositions() -> &'static HashMap<(char, char), char> {
+    static COMPOSED: OnceLock<HashMap<(char, char), char>> = OnceLock::new();
+    COMPOSED.get_or_init(|| {
+        let chars: Vec<char> = COMPOSITIONS.chars().collect();
+        chars
+            .chunks(3)
+            .map(|composition| ((composition[0], composition[1]), composition[2]))
+            .collect()
+    })
+}
+
+// Returns the text with each base letter and combining mark composed, when there's a composed
+// letter for them. Several marks are composed one after the other, as in `ệ`
+pub fn compose(text: &str) -> String {
+    let mut composed: Vec<char> = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        let letter = composed
+            .last()
+            .and_then(|last| compositions().get(&(*last, c)));
+        match letter {
+            Some(letter) => *composed.last_mut().unwrap() = *letter,
+            None => composed.push(c),
+        }
+    }
+    composed.into_iter().collect()
+}
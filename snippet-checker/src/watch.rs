@@ -0,0 +1,100 @@
+// Checks the book again whenever something changes, polling the modification times of the files.
+// Only the markdown files that changed, or that have snippets of a source file that changed, are
+// checked again, taking the source files of each markdown file from its previous check
+use crate::error::CheckError;
+use crate::{get_md_snippets_diff, print_file_check, CheckContext, FileCheck};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// A checked markdown file, and where it was checked from
+pub struct WatchedFile {
+    pub md_path: PathBuf,
+    pub md_name: String,
+    pub code_dir: String,
+    // The modification time when it was last checked
+    pub modified: Option<SystemTime>,
+}
+
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Polls for changes until the process is stopped, printing each file check again
+pub fn run(
+    ctx: &mut CheckContext,
+    mut files: Vec<(WatchedFile, FileCheck)>,
+) -> Result<(), CheckError> {
+    // The markdown files (by index) that read each source file, and its modification time
+    let mut references: BTreeMap<PathBuf, (Option<SystemTime>, BTreeSet<usize>)> = BTreeMap::new();
+    for (i, (_, check)) in files.iter().enumerate() {
+        add_references(&mut references, i, check);
+    }
+    println!("\nWatching for changes, press Ctrl-C to stop");
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let mut outdated = BTreeSet::new();
+        for (path, (modified_at, md_files)) in &mut references {
+            let now = modified(path);
+            if now != *modified_at {
+                *modified_at = now;
+                outdated.extend(md_files.iter().copied());
+            }
+        }
+        for (i, (file, _)) in files.iter().enumerate() {
+            if modified(&file.md_path) != file.modified {
+                outdated.insert(i);
+            }
+        }
+        if outdated.is_empty() {
+            continue;
+        }
+
+        println!();
+        for i in outdated {
+            let (file, check) = &mut files[i];
+            file.modified = modified(&file.md_path);
+            // The file may have been removed, or be written right now
+            let Ok(md_content) = ctx.read_book_file(&file.md_path) else {
+                continue;
+            };
+            if ctx.code_dir != file.code_dir {
+                ctx.code_dir = file.code_dir.clone();
+                ctx.source_files = OnceLock::new();
+            }
+
+            let md_dir = file.md_path.parent().unwrap_or(Path::new(""));
+            *check = get_md_snippets_diff(md_content, md_dir, ctx)?;
+            print_file_check(&file.md_name, check, ctx);
+
+            for (_, md_files) in references.values_mut() {
+                md_files.remove(&i);
+            }
+            add_references(&mut references, i, check);
+        }
+    }
+}
+
+fn add_references(
+    references: &mut BTreeMap<PathBuf, (Option<SystemTime>, BTreeSet<usize>)>,
+    md_index: usize,
+    check: &FileCheck,
+) {
+    for source in &check.sources {
+        references
+            .entry(source.clone())
+            .or_insert_with(|| (modified(source), BTreeSet::new()))
+            .1
+            .insert(md_index);
+    }
+}
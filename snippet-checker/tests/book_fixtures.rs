@@ -0,0 +1,222 @@
+// The book fixtures of the self-test, checked through the library interface with the settings
+// given to the builder, as in the config file
+use snippet_checker::{
+    CheckError, Issue, IssueKind, SnippetChecker, SnippetOutcome, SnippetResult,
+};
+
+use std::fs;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+// Checks a book fixture with these settings
+fn check(name: &str, settings: &[(&str, &str)]) -> Vec<SnippetResult> {
+    let mut builder =
+        SnippetChecker::builder(format!("{FIXTURES}/book"), format!("{FIXTURES}/code"));
+    for (name, value) in settings {
+        builder = builder.setting(name, value).unwrap();
+    }
+    let md = fs::read_to_string(format!("{FIXTURES}/book/{name}")).unwrap();
+    builder.build().unwrap().check_file(name, &md).unwrap()
+}
+
+// Whether all the snippets match the code
+fn all_match(results: &[SnippetResult]) -> bool {
+    results
+        .iter()
+        .all(|result| result.outcome == SnippetOutcome::Match)
+}
+
+// Returns the kind and message of the only issue of an invalid snippet
+fn invalid(result: &SnippetResult) -> (IssueKind, &str) {
+    match &result.outcome {
+        SnippetOutcome::Invalid(issues) if issues.len() == 1 => {
+            (issues[0].kind, issues[0].message.as_str())
+        }
+        other => panic!(
+            "expected snippet {} to be invalid, found {other:?}",
+            result.index
+        ),
+    }
+}
+
+#[test]
+fn matching_fixture() {
+    let results = check("matching.md", &[]);
+    assert_eq!(results.len(), 3);
+    assert!(all_match(&results));
+}
+
+#[test]
+fn stale_range_and_anchor_keep_the_others_checked() {
+    let results = check("stale_range.md", &[]);
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].outcome, SnippetOutcome::Match);
+    assert_eq!(
+        invalid(&results[1]),
+        (
+            IssueKind::BlockNotFound,
+            "could not find the code block in sample/src/lib.rs, as its line range 60-64 is out \
+             of bounds (the file has 52 lines)"
+        )
+    );
+    assert_eq!(
+        invalid(&results[2]),
+        (
+            IssueKind::BlockNotFound,
+            "could not find the code block in sample/src/lib.rs, as it has no item named by the \
+             anchor `forget_peer`"
+        )
+    );
+    assert_eq!(results[3].outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn missing_path_fixture() {
+    let results = check("missing_path.md", &[]);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].path, "sample/scr/lib.rs");
+    assert_eq!(results[0].outcome, SnippetOutcome::Missing);
+
+    // The skipped snippets have no result
+    assert!(check("missing_path.md", &[("missing_paths", "\"skip\"")]).is_empty());
+}
+
+#[test]
+fn max_candidates_fails_the_ambiguous_snippet() {
+    let results = check("ambiguous_first_line.md", &[]);
+    assert!(all_match(&results));
+
+    let results = check("ambiguous_first_line.md", &[("max_candidates", "1")]);
+    assert_eq!(invalid(&results[0]).0, IssueKind::Ambiguous);
+}
+
+#[test]
+fn max_snippet_lines_fails_only_when_strict() {
+    let results = check("matching.md", &[("max_snippet_lines", "5")]);
+    assert!(all_match(&results));
+
+    let results = check(
+        "matching.md",
+        &[("max_snippet_lines", "5"), ("strict", "true")],
+    );
+    assert_eq!(
+        results[0].outcome,
+        SnippetOutcome::Invalid(vec![Issue {
+            kind: IssueKind::Other,
+            message: "has 7 lines, more than the 5 allowed; split it or show only an anchor"
+                .to_string(),
+        }])
+    );
+}
+
+#[test]
+fn whole_file_fails_when_strict() {
+    let results = check("whole_file.md", &[("strict", "true")]);
+    assert_eq!(
+        invalid(&results[0]),
+        (
+            IssueKind::Other,
+            "compared with all the code of sample/src/documented.rs, which is much longer than \
+             the snippet"
+        )
+    );
+}
+
+#[test]
+fn check_indent_fails_the_indented_snippet() {
+    assert!(all_match(&check("indented.md", &[])));
+    let results = check("indented.md", &[("check_indent", "true")]);
+    assert_eq!(
+        invalid(&results[0]),
+        (
+            IssueKind::Other,
+            "snippet has leading indentation; dedent it"
+        )
+    );
+}
+
+#[test]
+fn comment_prefix_reads_the_other_languages() {
+    let results = check("comment_prefix.md", &[]);
+    assert_eq!(
+        invalid(&results[1]),
+        (IssueKind::MalformedHeader, "unknown snippetlang `lua`")
+    );
+
+    let results = check("comment_prefix.md", &[("comment_prefix", "\"lua=--\"")]);
+    assert!(all_match(&results));
+}
+
+#[test]
+fn fence_language_checks_the_other_fences() {
+    assert!(check("fence_languages.md", &[]).is_empty());
+    let results = check(
+        "fence_languages.md",
+        &[("fence_language", "[\"toml\", \"sh\"]")],
+    );
+    assert!(!results.is_empty());
+    assert!(all_match(&results));
+}
+
+#[test]
+fn settings_that_change_the_comparison() {
+    let settings: &[(&str, &[(&str, &str)])] = &[
+        ("sort_use.md", &[("sort_use", "true")]),
+        ("allow_placeholder.md", &[("allow_placeholder", "true")]),
+        ("cfg.md", &[("cfg", "\"feature=compact\"")]),
+        ("signature.md", &[("signature_only", "true")]),
+        ("ast_compare.md", &[("ast_compare", "true")]),
+        ("line_wrapping.md", &[("normalize_line_wrapping", "true")]),
+        ("unicode_normalize.md", &[("unicode_normalize", "true")]),
+        (
+            "ignore_attributes.md",
+            &[(
+                "ignore_attributes",
+                "[\"non_exhaustive\", \"allow(dead_code)\"]",
+            )],
+        ),
+    ];
+    for (name, settings) in settings {
+        assert!(!all_match(&check(name, &[])), "{name} matches by default");
+        let results = check(name, settings);
+        assert!(
+            all_match(&results),
+            "{name} doesn't match with {settings:?}: {results:?}"
+        );
+    }
+}
+
+#[test]
+fn invalid_settings_fail() {
+    let builder =
+        || SnippetChecker::builder(format!("{FIXTURES}/book"), format!("{FIXTURES}/code"));
+    let message = |result: Result<_, CheckError>| match result {
+        Err(CheckError::Config(error)) => error.to_string(),
+        Err(error) => panic!("expected a config error, found {error}"),
+        Ok(_) => panic!("expected a config error"),
+    };
+
+    // The output settings are not about the snippet checks
+    assert_eq!(
+        message(builder().setting("format", "\"json\"").map(|_| ())),
+        "Unknown check setting `format`"
+    );
+    assert!(
+        message(builder().setting("max_candidates", "\"three\"").map(|_| ()))
+            .contains("max_candidates")
+    );
+    assert!(message(
+        builder()
+            .setting("strict", "true\nformat = \"json\"")
+            .map(|_| ())
+    )
+    .starts_with("Invalid value for `strict`"));
+    assert!(message(
+        builder()
+            .setting("fence_language", "\"lua\"")
+            .unwrap()
+            .build()
+            .map(|_| ())
+    )
+    .contains("the fence language `lua` has no comment prefix"));
+}
@@ -0,0 +1,333 @@
+// The matching and comment stripping edge cases, checked through the library interface with the
+// fixture code of the self-test, and the comparators, sources and fixes given to it
+use snippet_checker::{
+    compute_fixed_markdown, Comparator, DiffLine, Issue, IssueKind, SnippetChecker, SnippetOutcome,
+    SnippetResult, SourceResolver,
+};
+
+use std::collections::HashMap;
+use std::fs;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+fn checker() -> SnippetChecker {
+    SnippetChecker::new(
+        format!("{FIXTURES}/book"),
+        format!("{FIXTURES}/code/crates"),
+    )
+}
+
+// Returns the markdown document with a snippet of this header and content
+fn snippet(header: &str, content: &str) -> String {
+    format!("```rust\n# // Path: {header}\n{content}```\n")
+}
+
+// Checks a document with a single snippet, which must have a result
+fn check_one(header: &str, content: &str) -> SnippetResult {
+    let mut results = checker()
+        .check_file("chapter.md", &snippet(header, content))
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    results.remove(0)
+}
+
+// Returns the outcome of a snippet with a single issue
+fn invalid(kind: IssueKind, message: &str) -> SnippetOutcome {
+    SnippetOutcome::Invalid(vec![Issue {
+        kind,
+        message: message.to_string(),
+    }])
+}
+
+const ADD_PEER: &str = "\
+pub fn add_peer(&mut self, id: u32, address: String) -> bool {
+    if self.peers.len() >= self.max_peers {
+        return false;
+    }
+";
+
+#[test]
+fn matching_snippet() {
+    let result = check_one("sample/src/lib.rs", ADD_PEER);
+    assert_eq!(result.index, 0);
+    assert_eq!(result.path, "sample/src/lib.rs");
+    assert_eq!(result.start_line, 20);
+    assert_eq!(result.outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn outdated_snippet() {
+    let outdated = ADD_PEER.replace(">=", ">");
+    let result = check_one("sample/src/lib.rs", &outdated);
+    let SnippetOutcome::Diff(lines) = result.outcome else {
+        panic!("expected a diff, found {:?}", result.outcome);
+    };
+    assert!(lines.contains(&DiffLine::Delete(
+        "    if self.peers.len() > self.max_peers {".to_string()
+    )));
+    assert!(lines.contains(&DiffLine::Insert(
+        "    if self.peers.len() >= self.max_peers {".to_string()
+    )));
+    let equal = lines
+        .iter()
+        .filter(|line| matches!(line, DiffLine::Equal(_)))
+        .count();
+    assert_eq!(equal, 3);
+}
+
+#[test]
+fn code_comments_are_skipped() {
+    // The code has a comment before this line, which the snippet leaves out
+    let content = "    self.peers.insert(id, address);\n    true\n}\n";
+    let result = check_one("sample/src/lib.rs", &format!("{ADD_PEER}\n{content}"));
+    assert_eq!(result.outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn snippet_comments_are_skipped() {
+    let content = format!("// Explains the limit check\n{ADD_PEER}");
+    let result = check_one("sample/src/lib.rs", &content);
+    assert_eq!(result.outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn hidden_lines_are_compared() {
+    let content = "\
+# impl PeerTracker {
+    pub fn new(max_peers: usize) -> Self {
+        PeerTracker {
+            peers: HashMap::new(),
+            max_peers,
+        }
+    }
+";
+    let result = check_one("sample/src/lib.rs:11-17", content);
+    assert_eq!(result.start_line, 11);
+    assert_eq!(result.outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn identation_is_removed() {
+    let content = "        self.peers.remove(&id)\n";
+    let result = check_one("sample/src/lib.rs", content);
+    assert_eq!(result.start_line, 31);
+    assert_eq!(result.outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn anchor_selects_the_item() {
+    let content = "\
+pub fn remove_peer(&mut self, id: u32) -> Option<String> {
+    self.peers.remove(&id)
+}
+";
+    let result = check_one("sample/src/lib.rs#remove_peer", content);
+    assert_eq!(result.start_line, 30);
+    assert_eq!(result.outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn second_line_disambiguates_the_first() {
+    let content = "\
+match self.peers.get(&id) {
+    None => false,
+    Some(_) => true,
+}
+";
+    let result = check_one("sample/src/lib.rs", content);
+    assert_eq!(result.start_line, 42);
+    assert_eq!(result.outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn ambiguous_first_lines_fail() {
+    let content = "\
+if peer == 0 {
+    return false;
+}
+";
+    let result = check_one("sample/src/handlers.rs", content);
+    assert_eq!(
+        result.outcome,
+        invalid(
+            IssueKind::Ambiguous,
+            "the first snippet lines are at lines 3, 10 of the file; use an anchor, a line range \
+             or an `occurrence` directive to choose one"
+        )
+    );
+}
+
+#[test]
+fn missing_path_fails() {
+    let result = check_one("sample/src/gone.rs", "fn gone() {}\n");
+    assert_eq!(result.path, "sample/src/gone.rs");
+    assert_eq!(result.start_line, 0);
+    assert_eq!(result.outcome, SnippetOutcome::Missing);
+}
+
+#[test]
+fn out_of_bounds_line_range_fails() {
+    let result = check_one("sample/src/lib.rs:6-900", "pub struct PeerTracker {\n");
+    assert_eq!(result.path, "sample/src/lib.rs");
+    assert_eq!(
+        result.outcome,
+        invalid(
+            IssueKind::BlockNotFound,
+            "could not find the code block in sample/src/lib.rs, as its line range 6-900 is out \
+             of bounds (the file has 52 lines)"
+        )
+    );
+}
+
+#[test]
+fn too_large_line_range_fails() {
+    let result = check_one(
+        "sample/src/lib.rs:99999999999999999999999-1",
+        "pub struct PeerTracker {\n",
+    );
+    assert_eq!(
+        result.outcome,
+        invalid(
+            IssueKind::MalformedHeader,
+            "invalid line range `99999999999999999999999-1`"
+        )
+    );
+}
+
+#[test]
+fn path_outside_of_the_code_dir_fails() {
+    let result = check_one("../../etc/passwd", "root:x:0:0:root:/root:/bin/bash\n");
+    assert_eq!(
+        result.outcome,
+        invalid(
+            IssueKind::UnsafePath,
+            "refusing to read ../../etc/passwd, as snippet paths must stay inside the code \
+             directory"
+        )
+    );
+}
+
+#[test]
+fn results_are_in_snippet_order() {
+    let md = [
+        snippet("sample/src/lib.rs", &ADD_PEER.replace(">=", ">")),
+        "Some text between the snippets.\n\n".to_string(),
+        snippet("sample/src/lib.rs", ADD_PEER),
+    ]
+    .concat();
+    let results = checker().check_file("chapter.md", &md).unwrap();
+    let indexes: Vec<usize> = results.iter().map(|result| result.index).collect();
+    assert_eq!(indexes, [0, 1]);
+    assert!(matches!(results[0].outcome, SnippetOutcome::Diff(_)));
+    assert_eq!(results[1].outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn invalid_snippets_keep_the_others_checked() {
+    let md = [
+        snippet("sample/src/gone.rs", "fn gone() {}\n"),
+        snippet("sample/src/lib.rs#forget_peer", "fn forget_peer() {}\n"),
+        snippet("sample/src/lib.rs", ADD_PEER),
+    ]
+    .concat();
+    let results = checker().check_file("chapter.md", &md).unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].outcome, SnippetOutcome::Missing);
+    assert_eq!(
+        results[1].outcome,
+        invalid(
+            IssueKind::BlockNotFound,
+            "could not find the code block in sample/src/lib.rs, as it has no item named by the \
+             anchor `forget_peer`"
+        )
+    );
+    assert_eq!(results[2].outcome, SnippetOutcome::Match);
+}
+
+#[test]
+fn document_without_snippets() {
+    let md = "# Intro\n\n```rust\nfn illustrative() {}\n```\n";
+    assert!(checker().check_file("chapter.md", md).unwrap().is_empty());
+}
+
+#[test]
+fn local_paths_are_relative_to_the_markdown_file() {
+    let md = fs::read_to_string(format!("{FIXTURES}/book/local_path.md")).unwrap();
+    let checker = SnippetChecker::new(FIXTURES, format!("{FIXTURES}/code/crates"));
+    let results = checker.check_file("book/local_path.md", &md).unwrap();
+    assert_eq!(results[0].outcome, SnippetOutcome::Match);
+    // The file is not next to an `examples` directory here
+    let results = checker.check_file("local_path.md", &md).unwrap();
+    assert_eq!(results[0].outcome, SnippetOutcome::Missing);
+}
+
+// Takes each run of whitespace as a single space
+struct CollapseWhitespace;
+
+impl Comparator for CollapseWhitespace {
+    fn normalize(&self, code: &str) -> String {
+        code.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+#[test]
+fn custom_comparator() {
+    let md = snippet("sample/src/lib.rs", &ADD_PEER.replace(" >= ", "  >=  "));
+    let results = checker().check_file("chapter.md", &md).unwrap();
+    assert!(matches!(results[0].outcome, SnippetOutcome::Diff(_)));
+
+    let checker = SnippetChecker::builder(FIXTURES, format!("{FIXTURES}/code/crates"))
+        .comparator(CollapseWhitespace)
+        .build()
+        .unwrap();
+    let results = checker.check_file("chapter.md", &md).unwrap();
+    assert_eq!(results[0].outcome, SnippetOutcome::Match);
+}
+
+// The source files of a map, by their path
+struct MemorySource(HashMap<String, Vec<u8>>);
+
+impl SourceResolver for MemorySource {
+    fn exists(&self, rel: &str) -> Result<bool, String> {
+        Ok(self.0.contains_key(rel))
+    }
+
+    fn read(&self, rel: &str) -> Result<Vec<u8>, String> {
+        self.0.get(rel).cloned().ok_or(format!("no file {rel}"))
+    }
+
+    fn files(&self) -> Vec<String> {
+        self.0.keys().cloned().collect()
+    }
+}
+
+#[test]
+fn sources_from_memory() {
+    let code = "fn greeting() -> &'static str {\n    \"Hello\"\n}\n";
+    let md = snippet("greeting/src/lib.rs", code);
+    let files = [("greeting/src/lib.rs".to_string(), code.as_bytes().to_vec())];
+    let checker = SnippetChecker::builder("/no/book", "/no/code")
+        .source(MemorySource(files.into_iter().collect()))
+        .build()
+        .unwrap();
+    let results = checker.check_file("chapter.md", &md).unwrap();
+    assert_eq!(results[0].outcome, SnippetOutcome::Match);
+
+    // The overlay files are read instead of the code directory, which doesn't exist
+    let checker = SnippetChecker::builder("/no/book", "/no/code")
+        .overlay_file("/no/code/greeting/src/lib.rs", code)
+        .build()
+        .unwrap();
+    let results = checker.check_file("chapter.md", &md).unwrap();
+    assert_eq!(results[0].outcome, SnippetOutcome::Match);
+    assert_eq!(results[0].start_line, 1);
+}
+
+#[test]
+fn outdated_snippet_is_fixed() {
+    let outdated = snippet("sample/src/lib.rs", &ADD_PEER.replace(">=", ">"));
+    let (fixed, fixes) = compute_fixed_markdown(&checker(), "chapter.md", &outdated).unwrap();
+    assert_eq!(fixed, snippet("sample/src/lib.rs", ADD_PEER));
+    assert_eq!(fixes.len(), 1);
+    assert_eq!((fixes[0].index, fixes[0].moved_to), (0, None));
+}
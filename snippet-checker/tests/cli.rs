@@ -0,0 +1,59 @@
+// The exit codes of the command line interface, running the binary on the fixtures
+use std::env;
+use std::fs;
+use std::process::Command;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+#[test]
+fn unreadable_file_fails_after_the_others() {
+    let book_dir = env::temp_dir().join(format!("snippet-checker-cli-{}", std::process::id()));
+    fs::create_dir_all(&book_dir).unwrap();
+    fs::copy(
+        format!("{FIXTURES}/book/matching.md"),
+        book_dir.join("matching.md"),
+    )
+    .unwrap();
+    fs::write(book_dir.join("latin1.md"), b"Ol\xe1\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_snippet_checker"))
+        .args(["--book-dir", &book_dir.display().to_string()])
+        .args(["--code-dir", &format!("{FIXTURES}/code")])
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&book_dir).unwrap();
+
+    // The other file is still checked and reported, and the run fails as a tooling error
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains("matching.md ..."), "{stdout}");
+    assert!(stdout.contains("1 files could not be checked"), "{stdout}");
+    assert!(stderr.contains("latin1.md"), "{stderr}");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn baseline_write_keeps_the_file_errors() {
+    let book_dir = env::temp_dir().join(format!("snippet-checker-baseline-{}", std::process::id()));
+    fs::create_dir_all(&book_dir).unwrap();
+    for name in ["diff.md", "snippet_count.md"] {
+        fs::copy(format!("{FIXTURES}/book/{name}"), book_dir.join(name)).unwrap();
+    }
+    let baseline = book_dir.join("baseline.txt");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_snippet_checker"))
+        .args(["--book-dir", &book_dir.display().to_string()])
+        .args(["--code-dir", &format!("{FIXTURES}/code")])
+        .args(["--baseline-write", &baseline.display().to_string()])
+        .output()
+        .unwrap();
+    let written = fs::read_to_string(&baseline).unwrap();
+    fs::remove_dir_all(&book_dir).unwrap();
+
+    // The diff is written to the baseline, but the snippet count mismatch can't be, so the run
+    // still fails
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(written.contains("diff.md"), "{written}");
+    assert!(stdout.contains("DIFF FOUND"), "{stdout}");
+    assert_eq!(output.status.code(), Some(1));
+}